@@ -1,5 +1,10 @@
 #![allow(dead_code)]
 
+use bfs_common::{
+    notation::{CubeMove, Face, Layers, Turn},
+    puzzle::Puzzle,
+};
+
 use crate::coord_cube::CoordCube;
 
 pub const EDGES_SIZE: usize = 760320;
@@ -365,8 +370,81 @@ impl From<CoordCube<'_>> for Cube {
     }
 }
 
+impl Default for Cube {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Puzzle for Cube {
+    const ID: &'static str = "3x3-2-color-ufb";
+    const STATE_SIZE: u64 = EDGES_SIZE as u64 * CORNERS_SIZE as u64;
+    const MOVES: &'static [&'static str] = &["U", "L", "F", "R", "B", "D"];
+    const ORDERS: &'static [usize] = &[4; 6];
+
+    fn solved() -> Self {
+        Self::new()
+    }
+
+    fn encode(&self) -> u64 {
+        self.encode()
+    }
+
+    fn decode(&mut self, coord: u64) {
+        self.decode(coord);
+    }
+
+    fn sub_coords(&self) -> Vec<(&'static str, u64)> {
+        vec![
+            ("edges", self.edges_coord() as u64),
+            ("corners", self.corners_coord() as u64),
+        ]
+    }
+
+    fn pieces(&self) -> Vec<(&'static str, Vec<u8>)> {
+        vec![
+            ("ep", self.ep.to_vec()),
+            ("eo", self.eo.to_vec()),
+            ("cp", self.cp.to_vec()),
+            ("co", self.co.to_vec()),
+        ]
+    }
+
+    fn do_move(&mut self, mv: usize) {
+        match mv {
+            0 => self.u(),
+            1 => self.l(),
+            2 => self.f(),
+            3 => self.r(),
+            4 => self.b(),
+            5 => self.d(),
+            _ => panic!("Invalid move"),
+        }
+    }
+}
+
+impl Turn<CubeMove> for Cube {
+    fn turn(&mut self, mv: CubeMove) -> bool {
+        let quarter: fn(&mut Self) = match (mv.face, mv.layers) {
+            (Face::U, Layers::OUTER) => Self::u,
+            (Face::L, Layers::OUTER) => Self::l,
+            (Face::F, Layers::OUTER) => Self::f,
+            (Face::R, Layers::OUTER) => Self::r,
+            (Face::B, Layers::OUTER) => Self::b,
+            (Face::D, Layers::OUTER) => Self::d,
+            _ => return false,
+        };
+        for _ in 0..mv.power {
+            quarter(self);
+        }
+        true
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
     use super::*;
 
     #[test]
@@ -581,12 +659,10 @@ mod tests {
 
     #[test]
     fn test_encode() {
-        let mut x = 0u64;
+        let mut rng = StdRng::seed_from_u64(0);
         let mut cube = Cube::new();
         for _ in 0..65536 {
-            x = x.wrapping_mul(450349535401847371);
-            x = x.wrapping_add(380506838312516788);
-            let coord = x % 116397388800;
+            let coord = Cube::random_coord(&mut rng);
             cube.decode(coord);
             assert_eq!(cube.encode(), coord);
         }
@@ -1,3 +1,5 @@
+use bfs_common::notation::{CubeMove, Face, Layers, Turn};
+
 use crate::{
     cube::{Cube, CORNERS_SIZE},
     transposition_tables::TranspositionTables,
@@ -69,10 +71,29 @@ impl<'a> CoordCube<'a> {
     }
 }
 
+impl Turn<CubeMove> for CoordCube<'_> {
+    fn turn(&mut self, mv: CubeMove) -> bool {
+        let quarter: fn(&mut Self) = match (mv.face, mv.layers) {
+            (Face::U, Layers::OUTER) => Self::u,
+            (Face::L, Layers::OUTER) => Self::l,
+            (Face::F, Layers::OUTER) => Self::f,
+            (Face::R, Layers::OUTER) => Self::r,
+            (Face::B, Layers::OUTER) => Self::b,
+            (Face::D, Layers::OUTER) => Self::d,
+            _ => return false,
+        };
+        for _ in 0..mv.power {
+            quarter(self);
+        }
+        true
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
-    use crate::cube::STATE_SIZE;
+    use bfs_common::puzzle::Puzzle;
+    use rand::{rngs::StdRng, SeedableRng};
 
     use super::*;
 
@@ -82,12 +103,9 @@ mod tests {
         let mut coord_cube = CoordCube::new(&transposition_tables);
         let mut cube = Cube::new();
 
-        let mut x = 0u64;
+        let mut rng = StdRng::seed_from_u64(0);
         for _ in 0..65536 {
-            x = x.wrapping_mul(450349535401847371);
-            x = x.wrapping_add(380506838312516788);
-
-            let coord = x % STATE_SIZE as u64;
+            let coord = Cube::random_coord(&mut rng);
 
             cube.decode(coord);
             coord_cube.decode(coord);
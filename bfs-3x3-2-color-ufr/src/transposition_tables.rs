@@ -1,92 +1,119 @@
+use bfs_common::{
+    puzzle::Puzzle as _,
+    tables::{par_build, Table, TableMode, TableSet},
+};
+
 use crate::cube::{Cube, CORNERS_SIZE, EDGES_SIZE};
 
 pub struct TranspositionTables {
-    pub u_edges: Vec<u32>,
-    pub u_corners: Vec<u32>,
-    pub l_edges: Vec<u32>,
-    pub l_corners: Vec<u32>,
-    pub f_edges: Vec<u32>,
-    pub f_corners: Vec<u32>,
-    pub r_edges: Vec<u32>,
-    pub r_corners: Vec<u32>,
-    pub b_edges: Vec<u32>,
-    pub b_corners: Vec<u32>,
-    pub d_edges: Vec<u32>,
-    pub d_corners: Vec<u32>,
+    pub u_edges: Table,
+    pub u_corners: Table,
+    pub l_edges: Table,
+    pub l_corners: Table,
+    pub f_edges: Table,
+    pub f_corners: Table,
+    pub r_edges: Table,
+    pub r_corners: Table,
+    pub b_edges: Table,
+    pub b_corners: Table,
+    pub d_edges: Table,
+    pub d_corners: Table,
 }
 
 impl TranspositionTables {
+    /// Builds the tables in memory, without saving them.
     pub fn new() -> Self {
-        let mut u_edges = vec![0; EDGES_SIZE as usize];
-        let mut u_corners = vec![0; CORNERS_SIZE as usize];
-        let mut l_edges = vec![0; EDGES_SIZE as usize];
-        let mut l_corners = vec![0; CORNERS_SIZE as usize];
-        let mut f_edges = vec![0; EDGES_SIZE as usize];
-        let mut f_corners = vec![0; CORNERS_SIZE as usize];
-        let mut r_edges = vec![0; EDGES_SIZE as usize];
-        let mut r_corners = vec![0; CORNERS_SIZE as usize];
-        let mut b_edges = vec![0; EDGES_SIZE as usize];
-        let mut b_corners = vec![0; CORNERS_SIZE as usize];
-        let mut d_edges = vec![0; EDGES_SIZE as usize];
-        let mut d_corners = vec![0; CORNERS_SIZE as usize];
+        Self::load(None, TableMode::Full)
+    }
+}
 
-        let mut cube = Cube::new();
+impl TableSet for TranspositionTables {
+    const ID: &'static str = Cube::ID;
+    const LAYOUT: &'static [(&'static str, usize)] = &[
+        ("u_edges", EDGES_SIZE as usize),
+        ("u_corners", CORNERS_SIZE as usize),
+        ("l_edges", EDGES_SIZE as usize),
+        ("l_corners", CORNERS_SIZE as usize),
+        ("f_edges", EDGES_SIZE as usize),
+        ("f_corners", CORNERS_SIZE as usize),
+        ("r_edges", EDGES_SIZE as usize),
+        ("r_corners", CORNERS_SIZE as usize),
+        ("b_edges", EDGES_SIZE as usize),
+        ("b_corners", CORNERS_SIZE as usize),
+        ("d_edges", EDGES_SIZE as usize),
+        ("d_corners", CORNERS_SIZE as usize),
+    ];
 
-        for i in 0..EDGES_SIZE as usize {
-            cube.set_edges_coord(i as u32);
-            cube.u();
-            u_edges[i] = cube.edges_coord();
-            cube.up();
-            cube.l();
-            l_edges[i] = cube.edges_coord();
-            cube.lp();
-            cube.f();
-            f_edges[i] = cube.edges_coord();
-            cube.fp();
-            cube.r();
-            r_edges[i] = cube.edges_coord();
-            cube.rp();
-            cube.b();
-            b_edges[i] = cube.edges_coord();
-            cube.bp();
-            cube.d();
-            d_edges[i] = cube.edges_coord();
-        }
+    fn build(_mode: TableMode) -> Vec<Vec<u32>> {
+        let [u_edges, l_edges, f_edges, r_edges, b_edges, d_edges] =
+            par_build(EDGES_SIZE as usize, Cube::new, |cube, i| {
+                cube.set_edges_coord(i as u32);
+                cube.u();
+                let u = cube.edges_coord();
+                cube.up();
+                cube.l();
+                let l = cube.edges_coord();
+                cube.lp();
+                cube.f();
+                let f = cube.edges_coord();
+                cube.fp();
+                cube.r();
+                let r = cube.edges_coord();
+                cube.rp();
+                cube.b();
+                let b = cube.edges_coord();
+                cube.bp();
+                cube.d();
+                let d = cube.edges_coord();
+                [u, l, f, r, b, d]
+            });
 
-        for i in 0..CORNERS_SIZE as usize {
-            cube.set_corners_coord(i as u32);
-            cube.u();
-            u_corners[i] = cube.corners_coord();
-            cube.up();
-            cube.l();
-            l_corners[i] = cube.corners_coord();
-            cube.lp();
-            cube.f();
-            f_corners[i] = cube.corners_coord();
-            cube.fp();
-            cube.r();
-            r_corners[i] = cube.corners_coord();
-            cube.rp();
-            cube.b();
-            b_corners[i] = cube.corners_coord();
-            cube.bp();
-            cube.d();
-            d_corners[i] = cube.corners_coord();
-        }
+        let [u_corners, l_corners, f_corners, r_corners, b_corners, d_corners] =
+            par_build(CORNERS_SIZE as usize, Cube::new, |cube, i| {
+                cube.set_corners_coord(i as u32);
+                cube.u();
+                let u = cube.corners_coord();
+                cube.up();
+                cube.l();
+                let l = cube.corners_coord();
+                cube.lp();
+                cube.f();
+                let f = cube.corners_coord();
+                cube.fp();
+                cube.r();
+                let r = cube.corners_coord();
+                cube.rp();
+                cube.b();
+                let b = cube.corners_coord();
+                cube.bp();
+                cube.d();
+                let d = cube.corners_coord();
+                [u, l, f, r, b, d]
+            });
+
+        vec![
+            u_edges, u_corners, l_edges, l_corners, f_edges, f_corners, r_edges, r_corners,
+            b_edges, b_corners, d_edges, d_corners,
+        ]
+    }
+
+    fn from_tables(tables: Vec<Option<Table>>) -> Self {
+        let mut tables = tables.into_iter();
+        let mut next = || tables.next().flatten().unwrap();
 
         Self {
-            u_edges,
-            u_corners,
-            l_edges,
-            l_corners,
-            f_edges,
-            f_corners,
-            r_edges,
-            r_corners,
-            b_edges,
-            b_corners,
-            d_edges,
-            d_corners,
+            u_edges: next(),
+            u_corners: next(),
+            l_edges: next(),
+            l_corners: next(),
+            f_edges: next(),
+            f_corners: next(),
+            r_edges: next(),
+            r_corners: next(),
+            b_edges: next(),
+            b_corners: next(),
+            d_edges: next(),
+            d_corners: next(),
         }
     }
 }
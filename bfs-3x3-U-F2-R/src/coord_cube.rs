@@ -1,5 +1,7 @@
 #![allow(dead_code)]
 
+use bfs_common::notation::{CubeMove, Face, Layers, Turn};
+
 use crate::{
     cube::{Cube, CORNERS_SIZE},
     transposition_tables::TranspositionTables,
@@ -54,15 +56,6 @@ impl<'a> CoordCube<'a> {
         self.corners = self.transposition_tables.f2_corners[self.corners as usize];
     }
 
-    pub fn do_move(&mut self, m: u8) {
-        match m {
-            0 => self.u(),
-            1 => self.r(),
-            2 => self.f2(),
-            _ => panic!("Invalid move"),
-        }
-    }
-
     pub fn encode(&self) -> u64 {
         self.edges as u64 * CORNERS_SIZE as u64 + self.corners as u64
     }
@@ -77,3 +70,19 @@ impl<'a> CoordCube<'a> {
         self.is_even_perm = cube.is_even_perm;
     }
 }
+
+impl Turn<CubeMove> for CoordCube<'_> {
+    fn turn(&mut self, mv: CubeMove) -> bool {
+        // F only turns by half turns
+        let (quarter, turns): (fn(&mut Self), u8) = match (mv.face, mv.layers, mv.power) {
+            (Face::U, Layers::OUTER, power) => (Self::u, power),
+            (Face::R, Layers::OUTER, power) => (Self::r, power),
+            (Face::F, Layers::OUTER, 2) => (Self::f2, 1),
+            _ => return false,
+        };
+        for _ in 0..turns {
+            quarter(self);
+        }
+        true
+    }
+}
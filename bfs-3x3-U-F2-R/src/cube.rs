@@ -1,5 +1,10 @@
 #![allow(dead_code)]
 
+use bfs_common::{
+    notation::{CubeMove, Face, Layers, Turn},
+    puzzle::Puzzle,
+};
+
 use crate::coord_cube::CoordCube;
 
 pub const EP_SIZE: u32 = 181440;
@@ -8,7 +13,17 @@ pub const CO_SIZE: u32 = 729;
 pub const CORNERS_SIZE: u32 = CP_SIZE * CO_SIZE;
 pub const STATE_SIZE: u64 = EP_SIZE as u64 * CORNERS_SIZE as u64;
 
-#[derive(Debug, PartialEq)]
+/// Where the reflection that swaps the U and R faces, and keeps F where it is, takes each corner
+/// position. It turns U into R', R into U' and F2 into itself.
+const MIRROR_CORNERS: [u8; 7] = [4, 5, 2, 3, 0, 1, 6];
+
+/// Where the reflection takes each edge position.
+const MIRROR_EDGES: [u8; 9] = [4, 5, 6, 3, 0, 1, 2, 8, 7];
+
+/// The twist that the reflection adds to the reference facelet of each corner position.
+const MIRROR_TWISTS: [u8; 7] = [0, 2, 0, 2, 0, 2, 2];
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Cube {
     ep: [u8; 9],
     cp: [u8; 7],
@@ -73,15 +88,6 @@ impl Cube {
         self.co.swap(3, 6);
     }
 
-    pub fn do_move(&mut self, m: u8) {
-        match m {
-            0 => self.u(),
-            1 => self.r(),
-            2 => self.f2(),
-            _ => panic!("Invalid move"),
-        }
-    }
-
     pub fn up(&mut self) {
         self.u();
         self.u();
@@ -94,6 +100,36 @@ impl Cube {
         self.r();
     }
 
+    /// The cube seen in a mirror that swaps U and R, so that the mirror image of a scramble is
+    /// the scramble with every U turned into R' and every R into U'.
+    pub fn mirror(&self) -> Self {
+        let mut cube = Self::new();
+        for i in 0..7 {
+            let (to, piece) = (MIRROR_CORNERS[i] as usize, self.cp[i] as usize);
+            cube.cp[to] = MIRROR_CORNERS[piece];
+            cube.co[to] = (6 + MIRROR_TWISTS[i] - MIRROR_TWISTS[piece] - self.co[i]) % 3;
+        }
+        for i in 0..9 {
+            cube.ep[MIRROR_EDGES[i] as usize] = MIRROR_EDGES[self.ep[i] as usize];
+        }
+        cube.is_even_perm = self.is_even_perm;
+        cube
+    }
+
+    /// The cube that the inverse of a scramble of this cube leads to.
+    pub fn inverse(&self) -> Self {
+        let mut cube = Self::new();
+        for i in 0..7 {
+            cube.cp[self.cp[i] as usize] = i as u8;
+            cube.co[self.cp[i] as usize] = (3 - self.co[i]) % 3;
+        }
+        for i in 0..9 {
+            cube.ep[self.ep[i] as usize] = i as u8;
+        }
+        cube.is_even_perm = self.is_even_perm;
+        cube
+    }
+
     pub fn ep_coord(&self) -> u32 {
         combinatorics::indexing::encode_even_permutation(self.ep) as u32
     }
@@ -158,23 +194,86 @@ impl From<CoordCube<'_>> for Cube {
     }
 }
 
+impl Default for Cube {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Puzzle for Cube {
+    const ID: &'static str = "3x3-U-F2-R";
+    const STATE_SIZE: u64 = STATE_SIZE;
+    const MOVES: &'static [&'static str] = &["U", "R", "F2"];
+    const ORDERS: &'static [usize] = &[4, 4, 2];
+
+    fn solved() -> Self {
+        Self::new()
+    }
+
+    fn encode(&self) -> u64 {
+        self.encode()
+    }
+
+    fn decode(&mut self, coord: u64) {
+        self.decode(coord);
+    }
+
+    fn sub_coords(&self) -> Vec<(&'static str, u64)> {
+        vec![
+            ("ep", self.ep_coord() as u64),
+            ("corners", self.corners_coord() as u64),
+        ]
+    }
+
+    fn pieces(&self) -> Vec<(&'static str, Vec<u8>)> {
+        vec![
+            ("ep", self.ep.to_vec()),
+            ("cp", self.cp.to_vec()),
+            ("co", self.co.to_vec()),
+        ]
+    }
+
+    fn do_move(&mut self, mv: usize) {
+        match mv {
+            0 => self.u(),
+            1 => self.r(),
+            2 => self.f2(),
+            _ => panic!("Invalid move"),
+        }
+    }
+}
+
+impl Turn<CubeMove> for Cube {
+    fn turn(&mut self, mv: CubeMove) -> bool {
+        // F only turns by half turns
+        let (quarter, turns): (fn(&mut Self), u8) = match (mv.face, mv.layers, mv.power) {
+            (Face::U, Layers::OUTER, power) => (Self::u, power),
+            (Face::R, Layers::OUTER, power) => (Self::r, power),
+            (Face::F, Layers::OUTER, 2) => (Self::f2, 1),
+            _ => return false,
+        };
+        for _ in 0..turns {
+            quarter(self);
+        }
+        true
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use bfs_common::notation::{parse_moves, turn_alg, NotationError};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
     use crate::transposition_tables::TranspositionTables;
 
     use super::*;
 
     #[test]
     fn test_cube_decode_encode() {
-        let mut x = 0u64;
-        let mut cube = Cube::new();
+        let mut rng = StdRng::seed_from_u64(0);
         for _ in 0..65536 {
-            x = x
-                .wrapping_mul(450349535401847371)
-                .wrapping_add(380506838312516788);
-            let coord = x % STATE_SIZE;
-            cube.decode(coord);
-            assert_eq!(cube.encode(), coord);
+            let cube = Cube::random(&mut rng);
+            assert_eq!(Cube::from_coord(cube.encode()), cube);
             assert_eq!(combinatorics::sign::is_even(cube.cp), cube.is_even_perm);
             assert_eq!(combinatorics::sign::is_even(cube.ep), cube.is_even_perm);
         }
@@ -185,12 +284,9 @@ mod tests {
         let transposition_tables = TranspositionTables::new();
         let mut cube = CoordCube::new(&transposition_tables);
 
-        let mut x = 0u64;
+        let mut rng = StdRng::seed_from_u64(0);
         for _ in 0..65536 {
-            x = x
-                .wrapping_mul(450349535401847371)
-                .wrapping_add(380506838312516788);
-            let coord = x % STATE_SIZE;
+            let coord = Cube::random_coord(&mut rng);
             cube.decode(coord);
             assert_eq!(cube.encode(), coord);
         }
@@ -202,16 +298,14 @@ mod tests {
 
         let mut cube = Cube::new();
         let mut coord_cube = CoordCube::new(&transposition_tables);
+        let moves = parse_moves::<CubeMove>("U R F2").unwrap();
 
-        let mut x = 0u64;
+        let mut rng = StdRng::seed_from_u64(0);
         for _ in 0..65536 {
-            x = x
-                .wrapping_mul(450349535401847371)
-                .wrapping_add(380506838312516788);
-            let mv = (x % 3) as u8;
+            let mv = moves[rng.random_range(0..moves.len())];
 
-            cube.do_move(mv);
-            coord_cube.do_move(mv);
+            assert!(cube.turn(mv));
+            assert!(coord_cube.turn(mv));
 
             assert_eq!(cube.encode(), coord_cube.encode());
         }
@@ -224,12 +318,9 @@ mod tests {
         let mut cube = Cube::new();
         let mut coord_cube = CoordCube::new(&transposition_tables);
 
-        let mut x = 0u64;
+        let mut rng = StdRng::seed_from_u64(0);
         for _ in 0..65536 {
-            x = x
-                .wrapping_mul(450349535401847371)
-                .wrapping_add(380506838312516788);
-            let enc = x % STATE_SIZE;
+            let enc = Cube::random_coord(&mut rng);
 
             cube.decode(enc);
             coord_cube.decode(enc);
@@ -242,32 +333,25 @@ mod tests {
 
     #[test]
     fn test_random_scramble() {
-        // Scramble: U R U F2 R F2 F2 R U U U U U R U F2 U R U U U U U F2 R F2 U R F2 R U U F2 R R
-        // F2 F2 U U U F2 U R F2 R U R R F2 R U F2 F2 U U F2 F2 R F2 F2 R F2 U R U U U F2 U R R R U
-        // F2 U F2 R U R U R U U U R F2 F2 F2 R R U R U U U R F2 F2 U R
-        let moves = [
-            0, 1, 0, 2, 1, 2, 2, 1, 0, 0, 0, 0, 0, 1, 0, 2, 0, 1, 0, 0, 0, 0, 0, 2, 1, 2, 0, 1, 2,
-            1, 0, 0, 2, 1, 1, 2, 2, 0, 0, 0, 2, 0, 1, 2, 1, 0, 1, 1, 2, 1, 0, 2, 2, 0, 0, 2, 2, 1,
-            2, 2, 1, 2, 0, 1, 0, 0, 0, 2, 0, 1, 1, 1, 0, 2, 0, 2, 1, 0, 1, 0, 1, 0, 0, 0, 1, 2, 2,
-            2, 1, 1, 0, 1, 0, 0, 0, 1, 2, 2, 0, 1,
-        ];
+        let scramble = "U R U F2 R F2 F2 R U U U U U R U F2 U R U U U U U F2 R F2 U R F2 R U U F2 R R \
+        F2 F2 U U U F2 U R F2 R U R R F2 R U F2 F2 U U F2 F2 R F2 F2 R F2 U R U U U F2 U R R R U F2 \
+        U F2 R U R U R U U U R F2 F2 F2 R R U R U U U R F2 F2 U R";
 
         let mut cube = Cube::new();
-        for mv in moves {
-            cube.do_move(mv);
-        }
+        turn_alg(&mut cube, scramble).unwrap();
 
-        // Solution: F2 R U F2 U2 R F2 U' R2 U' F2 R2 U R2 U2 R2 U2 F2 R2
-        let solution = [
-            2, 1, 0, 2, 0, 0, 1, 2, 0, 0, 0, 1, 1, 0, 0, 0, 2, 1, 1, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2,
-            1, 1,
-        ];
-
-        for mv in solution {
-            cube.do_move(mv);
-        }
+        let solution = "F2 R U F2 U2 R F2 U' R2 U' F2 R2 U R2 U2 R2 U2 F2 R2";
+        turn_alg(&mut cube, solution).unwrap();
 
         let solved_cube = Cube::new();
         assert_eq!(cube.encode(), solved_cube.encode());
+
+        assert_eq!(
+            turn_alg(&mut cube, "U R F"),
+            Err(NotationError::Unsupported {
+                token: "F".to_owned(),
+                position: 4,
+            })
+        );
     }
 }
@@ -0,0 +1,98 @@
+//! The symmetries of the <U, R, F2> state space: the reflection that swaps U and R, inversion, and
+//! the two combined.
+
+use bfs_common::symmetry::SmallestImage;
+
+use crate::cube::Cube;
+
+const SYMMETRIES: &[fn(&Cube) -> Cube] = &[Cube::mirror];
+
+const ANTISYMMETRIES: &[fn(&Cube) -> Cube] = &[Cube::inverse, mirror_inverse];
+
+fn mirror_inverse(cube: &Cube) -> Cube {
+    cube.mirror().inverse()
+}
+
+/// The classes of states under the 4 symmetries.
+pub fn reduction() -> SmallestImage<Cube> {
+    SmallestImage::new(SYMMETRIES, ANTISYMMETRIES)
+}
+
+#[cfg(test)]
+mod tests {
+    use bfs_common::{
+        expander::layers,
+        puzzle::Puzzle,
+        symmetry::{ReducedExpander, Reduction},
+        testing::{scrambled, scrambles},
+    };
+
+    use super::*;
+
+    bfs_common::expander! {
+        struct Expander {
+            cube: Cube,
+        }
+        const EXPANSION_NODES;
+        metric = Htm;
+        generators = [u: 4, r: 4, f2: 2];
+    }
+
+    #[test]
+    fn test_mirror() {
+        // U becomes R', R becomes U', and F2 stays F2
+        let mirrored_moves = [[1, 1, 1], [0, 0, 0], [2, 2, 2]];
+        for moves in scrambles::<Cube>() {
+            let cube = scrambled::<Cube>(&moves);
+            let mirror = cube.mirror();
+            assert_eq!(mirror.mirror(), cube);
+            assert_eq!(Cube::from_coord(mirror.encode()), mirror);
+
+            let mirrored = moves
+                .iter()
+                .flat_map(|&mv| mirrored_moves[mv])
+                .collect::<Vec<_>>();
+            assert_eq!(scrambled::<Cube>(&mirrored), mirror);
+        }
+    }
+
+    #[test]
+    fn test_inverse() {
+        for moves in scrambles::<Cube>() {
+            let cube = scrambled::<Cube>(&moves);
+            let inverse = cube.inverse();
+            assert_eq!(inverse.inverse(), cube);
+            assert_eq!(Cube::from_coord(inverse.encode()), inverse);
+
+            let inverted = moves
+                .iter()
+                .rev()
+                .flat_map(|&mv| vec![mv; Cube::ORDERS[mv] - 1])
+                .collect::<Vec<_>>();
+            assert_eq!(scrambled::<Cube>(&inverted), inverse);
+        }
+    }
+
+    #[test]
+    fn test_reduced_search() {
+        let solved = Cube::new().encode();
+        let mut reduction = reduction();
+
+        let full = layers(&mut Expander { cube: Cube::new() }, &[solved], 6);
+        let reduced = layers::<_, { 2 * EXPANSION_NODES }>(
+            &mut ReducedExpander::<_, _, EXPANSION_NODES> {
+                expander: Expander { cube: Cube::new() },
+                reduction: reduction.clone(),
+            },
+            &[reduction.class(solved)],
+            6,
+        );
+
+        assert_eq!(full.len(), reduced.len());
+        for (full, reduced) in full.iter().zip(&reduced) {
+            let unfolded = reduced.iter().map(|&c| reduction.size(c)).sum::<u64>();
+            assert_eq!(unfolded, full.len() as u64);
+            assert!(reduced.len() < full.len() || full.len() == 1);
+        }
+    }
+}
@@ -1,64 +1,85 @@
+use bfs_common::{
+    puzzle::Puzzle as _,
+    tables::{par_build, Table, TableMode, TableSet},
+};
+
 use crate::cube::{Cube, CORNERS_SIZE, EP_SIZE};
 
 pub struct TranspositionTables {
-    pub u_edges: Vec<u32>,
-    pub u_corners: Vec<u32>,
-    pub r_edges: Vec<u32>,
-    pub r_corners: Vec<u32>,
+    pub u_edges: Table,
+    pub u_corners: Table,
+    pub r_edges: Table,
+    pub r_corners: Table,
     // We need two sets of tables for f2 because parity determines whether we swap pieces 7 and 8,
     // and those two pieces are on the F face but not in U or R
-    pub f2_edges_even: Vec<u32>,
-    pub f2_edges_odd: Vec<u32>,
-    pub f2_corners: Vec<u32>,
+    pub f2_edges_even: Table,
+    pub f2_edges_odd: Table,
+    pub f2_corners: Table,
 }
 
 impl TranspositionTables {
+    /// Builds the tables in memory, without saving them.
     pub fn new() -> Self {
-        let mut u_edges = vec![0; EP_SIZE as usize];
-        let mut u_corners = vec![0; CORNERS_SIZE as usize];
-        let mut r_edges = vec![0; EP_SIZE as usize];
-        let mut r_corners = vec![0; CORNERS_SIZE as usize];
-        let mut f2_edges_even = vec![0; EP_SIZE as usize];
-        let mut f2_edges_odd = vec![0; EP_SIZE as usize];
-        let mut f2_corners = vec![0; CORNERS_SIZE as usize];
+        Self::load(None, TableMode::Full)
+    }
+}
 
-        let mut cube = Cube::new();
+impl TableSet for TranspositionTables {
+    const ID: &'static str = Cube::ID;
+    const LAYOUT: &'static [(&'static str, usize)] = &[
+        ("u_edges", EP_SIZE as usize),
+        ("u_corners", CORNERS_SIZE as usize),
+        ("r_edges", EP_SIZE as usize),
+        ("r_corners", CORNERS_SIZE as usize),
+        ("f2_edges_even", EP_SIZE as usize),
+        ("f2_edges_odd", EP_SIZE as usize),
+        ("f2_corners", CORNERS_SIZE as usize),
+    ];
 
-        for i in 0..CORNERS_SIZE as usize {
-            cube.set_corners_coord(i as u32);
-            cube.u();
-            u_corners[i] = cube.corners_coord();
-            cube.up();
-            cube.r();
-            r_corners[i] = cube.corners_coord();
-            cube.rp();
-            cube.f2();
-            f2_corners[i] = cube.corners_coord();
-        }
+    fn build(_mode: TableMode) -> Vec<Vec<u32>> {
+        let [u_corners, r_corners, f2_corners] =
+            par_build(CORNERS_SIZE as usize, Cube::new, |cube, i| {
+                cube.set_corners_coord(i as u32);
+                cube.u();
+                let u = cube.corners_coord();
+                cube.up();
+                cube.r();
+                let r = cube.corners_coord();
+                cube.rp();
+                cube.f2();
+                let f2 = cube.corners_coord();
+                [u, r, f2]
+            });
 
-        cube.is_even_perm = true;
+        let with_parity = |is_even_perm| {
+            move || {
+                let mut cube = Cube::new();
+                cube.is_even_perm = is_even_perm;
+                cube
+            }
+        };
 
-        for i in 0..EP_SIZE as usize {
-            cube.set_ep_coord(i as u32);
-            cube.u();
-            u_edges[i] = cube.ep_coord();
-            cube.up();
-            cube.r();
-            r_edges[i] = cube.ep_coord();
-            cube.rp();
-            cube.f2();
-            f2_edges_even[i] = cube.ep_coord();
-        }
+        let [u_edges, r_edges, f2_edges_even] =
+            par_build(EP_SIZE as usize, with_parity(true), |cube, i| {
+                cube.set_ep_coord(i as u32);
+                cube.u();
+                let u = cube.ep_coord();
+                cube.up();
+                cube.r();
+                let r = cube.ep_coord();
+                cube.rp();
+                cube.f2();
+                let f2 = cube.ep_coord();
+                [u, r, f2]
+            });
 
-        cube.is_even_perm = false;
-
-        for i in 0..EP_SIZE as usize {
+        let [f2_edges_odd] = par_build(EP_SIZE as usize, with_parity(false), |cube, i| {
             cube.set_ep_coord(i as u32);
             cube.f2();
-            f2_edges_odd[i] = cube.ep_coord();
-        }
+            [cube.ep_coord()]
+        });
 
-        Self {
+        vec![
             u_edges,
             u_corners,
             r_edges,
@@ -66,6 +87,21 @@ impl TranspositionTables {
             f2_edges_even,
             f2_edges_odd,
             f2_corners,
+        ]
+    }
+
+    fn from_tables(tables: Vec<Option<Table>>) -> Self {
+        let mut tables = tables.into_iter();
+        let mut next = || tables.next().flatten().unwrap();
+
+        Self {
+            u_edges: next(),
+            u_corners: next(),
+            r_edges: next(),
+            r_corners: next(),
+            f2_edges_even: next(),
+            f2_edges_odd: next(),
+            f2_corners: next(),
         }
     }
 }
@@ -1,5 +1,10 @@
 #![allow(dead_code)]
 
+use bfs_common::{
+    notation::{CubeMove, Face, Layers, Turn},
+    tables::Table,
+};
+
 use crate::{cube::Cube, transposition_tables::TranspositionTables};
 
 #[derive(Clone)]
@@ -28,14 +33,27 @@ impl<'a> CoordCube<'a> {
         }
     }
 
+    /// Applies a derived move from its tables, returning `false` if they weren't built.
+    fn apply(&mut self, perm: &Option<Table>, ori: &Option<Table>) -> bool {
+        let (Some(perm), Some(ori)) = (perm, ori) else {
+            return false;
+        };
+        self.perm = perm[self.perm as usize];
+        self.ori = ori[self.ori as usize];
+        true
+    }
+
     pub fn u(&mut self) {
         self.perm = self.transposition_tables.u_perm[self.perm as usize];
         self.ori = self.transposition_tables.u_ori[self.ori as usize];
     }
 
     pub fn u2(&mut self) {
-        self.perm = self.transposition_tables.u2_perm[self.perm as usize];
-        self.ori = self.transposition_tables.u2_ori[self.ori as usize];
+        let tables = self.transposition_tables;
+        if !self.apply(&tables.u2_perm, &tables.u2_ori) {
+            self.u();
+            self.u();
+        }
     }
 
     pub fn u_inv(&mut self) {
@@ -44,8 +62,11 @@ impl<'a> CoordCube<'a> {
     }
 
     pub fn ur(&mut self) {
-        self.perm = self.transposition_tables.ur_perm[self.perm as usize];
-        self.ori = self.transposition_tables.ur_ori[self.ori as usize];
+        let tables = self.transposition_tables;
+        if !self.apply(&tables.ur_perm, &tables.ur_ori) {
+            self.u();
+            self.r();
+        }
     }
 
     pub fn r(&mut self) {
@@ -54,8 +75,11 @@ impl<'a> CoordCube<'a> {
     }
 
     pub fn r2(&mut self) {
-        self.perm = self.transposition_tables.r2_perm[self.perm as usize];
-        self.ori = self.transposition_tables.r2_ori[self.ori as usize];
+        let tables = self.transposition_tables;
+        if !self.apply(&tables.r2_perm, &tables.r2_ori) {
+            self.r();
+            self.r();
+        }
     }
 
     pub fn r_inv(&mut self) {
@@ -69,8 +93,11 @@ impl<'a> CoordCube<'a> {
     }
 
     pub fn rw2(&mut self) {
-        self.perm = self.transposition_tables.rw2_perm[self.perm as usize];
-        self.ori = self.transposition_tables.rw2_ori[self.ori as usize];
+        let tables = self.transposition_tables;
+        if !self.apply(&tables.rw2_perm, &tables.rw2_ori) {
+            self.rw();
+            self.rw();
+        }
     }
 
     pub fn rw_inv(&mut self) {
@@ -79,22 +106,24 @@ impl<'a> CoordCube<'a> {
     }
 
     pub fn m(&mut self) {
-        self.perm = self.transposition_tables.m_perm[self.perm as usize];
-        self.ori = self.transposition_tables.m_ori[self.ori as usize];
-    }
-
-    pub fn do_move(&mut self, mv: &str) {
-        match mv {
-            "U" => self.u(),
-            "U2" => self.u2(),
-            "U'" => self.u_inv(),
-            "r" => self.rw(),
-            "r2" => self.rw2(),
-            "r'" => self.rw_inv(),
-            _ => panic!("Invalid move {mv}"),
+        let tables = self.transposition_tables;
+        if !self.apply(&tables.m_perm, &tables.m_ori) {
+            // M commutes with R, so r' R = R' M R = M
+            self.rw_inv();
+            self.r();
         }
     }
 
+    pub fn m2(&mut self) {
+        self.m();
+        self.m();
+    }
+
+    pub fn m_inv(&mut self) {
+        self.m();
+        self.m2();
+    }
+
     pub fn encode(&self) -> u64 {
         self.perm as u64 * 62208 + self.ori as u64
     }
@@ -104,3 +133,17 @@ impl<'a> CoordCube<'a> {
         self.ori = (coord % 62208) as u32;
     }
 }
+
+impl Turn<CubeMove> for CoordCube<'_> {
+    fn turn(&mut self, mv: CubeMove) -> bool {
+        let powers: [fn(&mut Self); 3] = match (mv.face, mv.layers) {
+            (Face::U, Layers::OUTER) => [Self::u, Self::u2, Self::u_inv],
+            (Face::R, Layers::OUTER) => [Self::r, Self::r2, Self::r_inv],
+            (Face::R, Layers::Wide(2)) => [Self::rw, Self::rw2, Self::rw_inv],
+            (Face::L, Layers::Middle) => [Self::m, Self::m2, Self::m_inv],
+            _ => return false,
+        };
+        powers[mv.power as usize - 1](self);
+        true
+    }
+}
@@ -1,5 +1,11 @@
 #![allow(dead_code)]
 
+use bfs_common::{
+    goal::GoalSet,
+    notation::{CubeMove, Face, Layers, Turn},
+    puzzle::Puzzle,
+};
+
 use crate::coord_cube::CoordCube;
 
 static CP_ALL: [[u8; 6]; 120] = [
@@ -125,9 +131,16 @@ static CP_ALL: [[u8; 6]; 120] = [
     [5, 4, 3, 2, 1, 0],
 ];
 
+/// Where the reflection that swaps the F and B faces takes each corner position. It turns every
+/// move into its inverse.
+const MIRROR_CORNERS: [u8; 6] = [1, 0, 3, 2, 5, 4];
+
+/// Where the reflection takes each edge position.
+const MIRROR_EDGES: [u8; 9] = [2, 1, 0, 3, 5, 4, 8, 7, 6];
+
 /// Corners: UFL ULB UBR URF DFR DRB
 /// Edges: UF UL UB UR FR BR DF DR DB
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Cube {
     cp: [u8; 6],
     co: [u8; 6],
@@ -259,22 +272,39 @@ impl Cube {
         self.m();
     }
 
-    fn do_move(&mut self, mv: &str) {
-        match mv {
-            "U" => self.u(),
-            "U2" => self.u2(),
-            "U'" => self.u_inv(),
-            "R" => self.r(),
-            "R2" => self.r2(),
-            "R'" => self.r_inv(),
-            "M" => self.m(),
-            "M2" => self.m2(),
-            "M'" => self.m_inv(),
-            "r" => self.rw(),
-            "r2" => self.rw2(),
-            "r'" => self.rw_inv(),
-            _ => panic!("Invalid move {mv}"),
+    /// The cube seen in a mirror that swaps F and B, so that the mirror image of a scramble is the
+    /// scramble with every move inverted. The reference facelets of the pieces are on the U, D, R
+    /// and L faces, which the mirror keeps in place, so only the direction of corner twists
+    /// changes.
+    pub fn mirror(&self) -> Self {
+        let mut cube = Self::new();
+        for i in 0..6 {
+            let to = MIRROR_CORNERS[i] as usize;
+            cube.cp[to] = MIRROR_CORNERS[self.cp[i] as usize];
+            cube.co[to] = (3 - self.co[i]) % 3;
+        }
+        for i in 0..9 {
+            let to = MIRROR_EDGES[i] as usize;
+            cube.ep[to] = MIRROR_EDGES[self.ep[i] as usize];
+            cube.eo[to] = self.eo[i];
         }
+        cube.centers = (4 - self.centers) % 4;
+        cube
+    }
+
+    /// The cube that the inverse of a scramble of this cube leads to.
+    pub fn inverse(&self) -> Self {
+        let mut cube = Self::new();
+        for i in 0..6 {
+            cube.cp[self.cp[i] as usize] = i as u8;
+            cube.co[self.cp[i] as usize] = (3 - self.co[i]) % 3;
+        }
+        for i in 0..9 {
+            cube.ep[self.ep[i] as usize] = i as u8;
+            cube.eo[self.ep[i] as usize] = self.eo[i];
+        }
+        cube.centers = (4 - self.centers) % 4;
+        cube
     }
 
     fn cp_coord(&self) -> u32 {
@@ -409,8 +439,87 @@ impl From<CoordCube<'_>> for Cube {
     }
 }
 
+impl Default for Cube {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Puzzle for Cube {
+    const ID: &'static str = "3x3-U-r";
+    const STATE_SIZE: u64 = 5417769369600;
+    const MOVES: &'static [&'static str] = &["U", "R", "r"];
+    const ORDERS: &'static [usize] = &[4, 4, 4];
+    const GOALS: &'static [&'static str] = &["auf"];
+
+    fn solved() -> Self {
+        Self::new()
+    }
+
+    fn encode(&self) -> u64 {
+        self.encode()
+    }
+
+    fn decode(&mut self, coord: u64) {
+        self.decode(coord);
+    }
+
+    fn sub_coords(&self) -> Vec<(&'static str, u64)> {
+        vec![
+            ("perm", self.perm_coord() as u64),
+            ("ori", self.ori_coord() as u64),
+        ]
+    }
+
+    fn pieces(&self) -> Vec<(&'static str, Vec<u8>)> {
+        vec![
+            ("cp", self.cp.to_vec()),
+            ("co", self.co.to_vec()),
+            ("ep", self.ep.to_vec()),
+            ("eo", self.eo.to_vec()),
+            ("centers", vec![self.centers]),
+        ]
+    }
+
+    fn do_move(&mut self, mv: usize) {
+        match mv {
+            0 => self.u(),
+            1 => self.r(),
+            2 => self.rw(),
+            _ => panic!("Invalid move"),
+        }
+    }
+
+    fn goal(name: &str) -> Option<GoalSet> {
+        match name {
+            // Solved up to a turn of the U layer
+            "auf" => Some(GoalSet::from_scrambles::<Self>(&["", "U", "U2", "U'"]).unwrap()),
+            _ => None,
+        }
+    }
+}
+
+impl Turn<CubeMove> for Cube {
+    fn turn(&mut self, mv: CubeMove) -> bool {
+        let powers: [fn(&mut Self); 3] = match (mv.face, mv.layers) {
+            (Face::U, Layers::OUTER) => [Self::u, Self::u2, Self::u_inv],
+            (Face::R, Layers::OUTER) => [Self::r, Self::r2, Self::r_inv],
+            (Face::R, Layers::Wide(2)) => [Self::rw, Self::rw2, Self::rw_inv],
+            (Face::L, Layers::Middle) => [Self::m, Self::m2, Self::m_inv],
+            _ => return false,
+        };
+        powers[mv.power as usize - 1](self);
+        true
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use bfs_common::{
+        notation::{parse_moves, turn_alg},
+        tables::{TableMode, TableSet as _},
+    };
+
     use crate::transposition_tables::TranspositionTables;
 
     use super::*;
@@ -451,6 +560,35 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_compact_tables() {
+        let full = TranspositionTables::new();
+        let compact = TranspositionTables::load(None, TableMode::Compact);
+        assert!(compact.u2_perm.is_none() && compact.m_ori.is_none());
+
+        let moves: [fn(&mut CoordCube); 5] = [
+            |cube| cube.u2(),
+            |cube| cube.ur(),
+            |cube| cube.r2(),
+            |cube| cube.rw2(),
+            |cube| cube.m(),
+        ];
+
+        let mut cube = CoordCube::new(&full);
+        turn_alg(&mut cube, "r U' r2 U r' U2 r U").unwrap();
+
+        for mv in moves {
+            let mut expected = cube.clone();
+            mv(&mut expected);
+
+            let mut actual = CoordCube::new(&compact);
+            actual.decode(cube.encode());
+            mv(&mut actual);
+
+            assert_eq!(actual.encode(), expected.encode());
+        }
+    }
+
     #[test]
     fn test_cube_random_scramble() {
         let scramble = "r' U' r U' r U' r' U' r' U' r2 U2 r U' r U2 r U' r' U r2 U2 r2 U' r2 \
@@ -461,9 +599,13 @@ mod tests {
         let mut cube = Cube::new();
         let mut coord_cube = CoordCube::new(&transposition_tables);
 
-        for (i, mv) in scramble.split_whitespace().enumerate() {
-            cube.do_move(mv);
-            coord_cube.do_move(mv);
+        for (i, mv) in parse_moves::<CubeMove>(scramble)
+            .unwrap()
+            .into_iter()
+            .enumerate()
+        {
+            assert!(cube.turn(mv));
+            assert!(coord_cube.turn(mv));
 
             assert_eq!(
                 cube.perm_coord(),
@@ -495,9 +637,9 @@ mod tests {
 
         let solution = "U' r' U' r2 U r2 U r2 U' r' U r' U r' U2 r' U2 r U2 r2 U r U' r' U'";
 
-        for mv in solution.split_whitespace() {
-            cube.do_move(mv);
-            coord_cube.do_move(mv);
+        for mv in parse_moves::<CubeMove>(solution).unwrap() {
+            assert!(cube.turn(mv));
+            assert!(coord_cube.turn(mv));
 
             assert_eq!(cube.perm_coord(), coord_cube.perm);
             assert_eq!(cube.ori_coord(), coord_cube.ori);
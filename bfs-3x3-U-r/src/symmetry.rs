@@ -0,0 +1,110 @@
+//! The symmetries of the <U, R, r> state space: the reflection that swaps F and B, inversion, and
+//! the two combined.
+
+use bfs_common::symmetry::SmallestImage;
+
+use crate::cube::Cube;
+
+const SYMMETRIES: &[fn(&Cube) -> Cube] = &[Cube::mirror];
+
+const ANTISYMMETRIES: &[fn(&Cube) -> Cube] = &[Cube::inverse, mirror_inverse];
+
+fn mirror_inverse(cube: &Cube) -> Cube {
+    cube.mirror().inverse()
+}
+
+/// The classes of states under the 4 symmetries.
+pub fn reduction() -> SmallestImage<Cube> {
+    SmallestImage::new(SYMMETRIES, ANTISYMMETRIES)
+}
+
+#[cfg(test)]
+mod tests {
+    use bfs_common::{
+        expander::layers,
+        puzzle::Puzzle,
+        symmetry::{ReducedExpander, Reduction},
+        testing::{scrambled, scrambles},
+    };
+
+    use super::*;
+
+    bfs_common::expander! {
+        struct Expander {
+            cube: Cube,
+        }
+        const EXPANSION_NODES;
+        metric = Htm;
+        generators = [u: 4, r: 4, rw: 4];
+    }
+
+    #[test]
+    fn test_mirror() {
+        // Every move becomes its inverse
+        let mirrored_moves = [[0; 3], [1; 3], [2; 3]];
+        for moves in scrambles::<Cube>() {
+            let cube = scrambled::<Cube>(&moves);
+            let mirror = cube.mirror();
+            assert_eq!(mirror.mirror(), cube);
+            assert_eq!(Cube::from_coord(mirror.encode()), mirror);
+
+            let mirrored = moves
+                .iter()
+                .flat_map(|&mv| mirrored_moves[mv])
+                .collect::<Vec<_>>();
+            assert_eq!(scrambled::<Cube>(&mirrored), mirror);
+        }
+    }
+
+    #[test]
+    fn test_inverse() {
+        for moves in scrambles::<Cube>() {
+            let cube = scrambled::<Cube>(&moves);
+            let inverse = cube.inverse();
+            assert_eq!(inverse.inverse(), cube);
+            assert_eq!(Cube::from_coord(inverse.encode()), inverse);
+
+            let inverted = moves
+                .iter()
+                .rev()
+                .flat_map(|&mv| vec![mv; Cube::ORDERS[mv] - 1])
+                .collect::<Vec<_>>();
+            assert_eq!(scrambled::<Cube>(&inverted), inverse);
+        }
+    }
+
+    #[test]
+    fn test_reduced_search() {
+        let solved = Cube::new().encode();
+        let mut reduction = reduction();
+
+        let full = layers(&mut Expander { cube: Cube::new() }, &[solved], 6);
+        let reduced = layers::<_, { 2 * EXPANSION_NODES }>(
+            &mut ReducedExpander::<_, _, EXPANSION_NODES> {
+                expander: Expander { cube: Cube::new() },
+                reduction: reduction.clone(),
+            },
+            &[reduction.class(solved)],
+            6,
+        );
+
+        assert_eq!(full.len(), reduced.len());
+        for (full, reduced) in full.iter().zip(&reduced) {
+            let unfolded = reduced.iter().map(|&c| reduction.size(c)).sum::<u64>();
+            assert_eq!(unfolded, full.len() as u64);
+            assert!(reduced.len() < full.len() || full.len() == 1);
+        }
+    }
+
+    #[test]
+    fn test_goals() {
+        // The mirror and inverse map AUF to itself, so it can be searched by class
+        let auf = Cube::goal("auf").unwrap();
+        assert_eq!(auf.states.len(), 4);
+        for &state in &auf.states {
+            let cube = Cube::from_coord(state);
+            assert!(auf.contains(cube.mirror().encode()));
+            assert!(auf.contains(cube.inverse().encode()));
+        }
+    }
+}
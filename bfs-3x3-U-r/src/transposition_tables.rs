@@ -1,114 +1,146 @@
+use bfs_common::{
+    puzzle::Puzzle as _,
+    tables::{par_build, retain, Table, TableMode, TableSet},
+};
+
 use crate::cube::Cube;
 
 pub struct TranspositionTables {
-    pub u_perm: Vec<u32>,
-    pub u_ori: Vec<u32>,
-    pub u2_perm: Vec<u32>,
-    pub u2_ori: Vec<u32>,
-    pub ur_perm: Vec<u32>,
-    pub ur_ori: Vec<u32>,
-    pub r_perm: Vec<u32>,
-    pub r_ori: Vec<u32>,
-    pub r2_perm: Vec<u32>,
-    pub r2_ori: Vec<u32>,
-    pub rw_perm: Vec<u32>,
-    pub rw_ori: Vec<u32>,
-    pub rw2_perm: Vec<u32>,
-    pub rw2_ori: Vec<u32>,
-    pub m_perm: Vec<u32>,
-    pub m_ori: Vec<u32>,
+    pub u_perm: Table,
+    pub u_ori: Table,
+    pub u2_perm: Option<Table>,
+    pub u2_ori: Option<Table>,
+    pub ur_perm: Option<Table>,
+    pub ur_ori: Option<Table>,
+    pub r_perm: Table,
+    pub r_ori: Table,
+    pub r2_perm: Option<Table>,
+    pub r2_ori: Option<Table>,
+    pub rw_perm: Table,
+    pub rw_ori: Table,
+    pub rw2_perm: Option<Table>,
+    pub rw2_ori: Option<Table>,
+    pub m_perm: Option<Table>,
+    pub m_ori: Option<Table>,
 }
 
 impl TranspositionTables {
+    /// Builds the tables in memory, without saving them.
     pub fn new() -> Self {
-        let mut u_perm = vec![0; 87091200];
-        let mut u_ori = vec![0; 62208];
-        let mut u2_perm = vec![0; 87091200];
-        let mut u2_ori = vec![0; 62208];
-        let mut ur_perm = vec![0; 87091200];
-        let mut ur_ori = vec![0; 62208];
-        let mut r_perm = vec![0; 87091200];
-        let mut r_ori = vec![0; 62208];
-        let mut r2_perm = vec![0; 87091200];
-        let mut r2_ori = vec![0; 62208];
-        let mut rw_perm = vec![0; 87091200];
-        let mut rw_ori = vec![0; 62208];
-        let mut rw2_perm = vec![0; 87091200];
-        let mut rw2_ori = vec![0; 62208];
-        let mut m_perm = vec![0; 87091200];
-        let mut m_ori = vec![0; 62208];
+        Self::load(None, TableMode::Full)
+    }
+}
 
-        let mut cube = Cube::new();
+impl TableSet for TranspositionTables {
+    const ID: &'static str = Cube::ID;
+    const LAYOUT: &'static [(&'static str, usize)] = &[
+        ("u_perm", 87091200),
+        ("u_ori", 62208),
+        ("u2_perm", 87091200),
+        ("u2_ori", 62208),
+        ("ur_perm", 87091200),
+        ("ur_ori", 62208),
+        ("r_perm", 87091200),
+        ("r_ori", 62208),
+        ("r2_perm", 87091200),
+        ("r2_ori", 62208),
+        ("rw_perm", 87091200),
+        ("rw_ori", 62208),
+        ("rw2_perm", 87091200),
+        ("rw2_ori", 62208),
+        ("m_perm", 87091200),
+        ("m_ori", 62208),
+    ];
+    const DERIVED: &'static [&'static str] = &[
+        "u2_perm", "u2_ori", "ur_perm", "ur_ori", "r2_perm", "r2_ori", "rw2_perm", "rw2_ori",
+        "m_perm", "m_ori",
+    ];
 
-        for i in 0..87091200 {
-            cube.set_perm_coord(i);
-            let i = i as usize;
-            cube.u();
-            u_perm[i] = cube.perm_coord();
-            cube.u();
-            u2_perm[i] = cube.perm_coord();
-            cube.u_inv();
-            cube.r();
-            ur_perm[i] = cube.perm_coord();
-            cube.r_inv();
-            cube.u_inv();
-            cube.r();
-            r_perm[i] = cube.perm_coord();
-            cube.r();
-            r2_perm[i] = cube.perm_coord();
-            cube.r2();
-            cube.rw();
-            rw_perm[i] = cube.perm_coord();
-            cube.rw();
-            rw2_perm[i] = cube.perm_coord();
-            cube.rw2();
-            cube.m();
-            m_perm[i] = cube.perm_coord();
-        }
+    fn build(mode: TableMode) -> Vec<Vec<u32>> {
+        let [u_perm, u2_perm, ur_perm, r_perm, r2_perm, rw_perm, rw2_perm, m_perm] =
+            par_build(87091200, Cube::new, |cube, i| {
+                cube.set_perm_coord(i as u32);
+                cube.u();
+                let u = cube.perm_coord();
+                cube.u();
+                let u2 = cube.perm_coord();
+                cube.u_inv();
+                cube.r();
+                let ur = cube.perm_coord();
+                cube.r_inv();
+                cube.u_inv();
+                cube.r();
+                let r = cube.perm_coord();
+                cube.r();
+                let r2 = cube.perm_coord();
+                cube.r2();
+                cube.rw();
+                let rw = cube.perm_coord();
+                cube.rw();
+                let rw2 = cube.perm_coord();
+                cube.rw2();
+                cube.m();
+                let m = cube.perm_coord();
+                [u, u2, ur, r, r2, rw, rw2, m]
+            });
 
-        for i in 0..62208 {
-            cube.set_ori_coord(i);
-            let i = i as usize;
-            cube.u();
-            u_ori[i] = cube.ori_coord();
-            cube.u();
-            u2_ori[i] = cube.ori_coord();
-            cube.u_inv();
-            cube.r();
-            ur_ori[i] = cube.ori_coord();
-            cube.r_inv();
-            cube.u_inv();
-            cube.r();
-            r_ori[i] = cube.ori_coord();
-            cube.r();
-            r2_ori[i] = cube.ori_coord();
-            cube.r2();
-            cube.rw();
-            rw_ori[i] = cube.ori_coord();
-            cube.rw();
-            rw2_ori[i] = cube.ori_coord();
-            cube.rw2();
-            cube.m();
-            m_ori[i] = cube.ori_coord();
-        }
+        let [u_ori, u2_ori, ur_ori, r_ori, r2_ori, rw_ori, rw2_ori, m_ori] =
+            par_build(62208, Cube::new, |cube, i| {
+                cube.set_ori_coord(i as u32);
+                cube.u();
+                let u = cube.ori_coord();
+                cube.u();
+                let u2 = cube.ori_coord();
+                cube.u_inv();
+                cube.r();
+                let ur = cube.ori_coord();
+                cube.r_inv();
+                cube.u_inv();
+                cube.r();
+                let r = cube.ori_coord();
+                cube.r();
+                let r2 = cube.ori_coord();
+                cube.r2();
+                cube.rw();
+                let rw = cube.ori_coord();
+                cube.rw();
+                let rw2 = cube.ori_coord();
+                cube.rw2();
+                cube.m();
+                let m = cube.ori_coord();
+                [u, u2, ur, r, r2, rw, rw2, m]
+            });
+
+        retain::<Self>(
+            mode,
+            vec![
+                u_perm, u_ori, u2_perm, u2_ori, ur_perm, ur_ori, r_perm, r_ori, r2_perm, r2_ori,
+                rw_perm, rw_ori, rw2_perm, rw2_ori, m_perm, m_ori,
+            ],
+        )
+    }
+
+    fn from_tables(tables: Vec<Option<Table>>) -> Self {
+        let mut tables = tables.into_iter();
+        let mut next = || tables.next().unwrap();
 
         Self {
-            u_perm,
-            u_ori,
-            u2_perm,
-            u2_ori,
-            ur_perm,
-            ur_ori,
-            r_perm,
-            r_ori,
-            r2_perm,
-            r2_ori,
-            rw_perm,
-            rw_ori,
-            rw2_perm,
-            rw2_ori,
-            m_perm,
-            m_ori,
+            u_perm: next().unwrap(),
+            u_ori: next().unwrap(),
+            u2_perm: next(),
+            u2_ori: next(),
+            ur_perm: next(),
+            ur_ori: next(),
+            r_perm: next().unwrap(),
+            r_ori: next().unwrap(),
+            r2_perm: next(),
+            r2_ori: next(),
+            rw_perm: next().unwrap(),
+            rw_ori: next().unwrap(),
+            rw2_perm: next(),
+            rw2_ori: next(),
+            m_perm: next(),
+            m_ori: next(),
         }
     }
 }
@@ -1,9 +1,16 @@
+use bfs_common::{
+    notation::{CubeMove, Face, Layers, Turn},
+    tables::Table,
+};
+
 use crate::{cube::Cube, transposition_tables::TranspositionTables};
 
 #[derive(Clone)]
 pub struct CoordCube<'a> {
     pub ep: u32,
     transposition_tables: &'a TranspositionTables,
+    /// Used to make moves that have no table.
+    cube: Cube,
 }
 
 impl<'a> std::fmt::Debug for CoordCube<'a> {
@@ -18,31 +25,43 @@ impl<'a> CoordCube<'a> {
         Self {
             ep: cube.ep_coord(),
             transposition_tables,
+            cube,
+        }
+    }
+
+    fn apply(&mut self, table: fn(&TranspositionTables) -> &Option<Table>, mv: fn(&mut Cube)) {
+        match table(self.transposition_tables) {
+            Some(table) => self.ep = table[self.ep as usize],
+            None => {
+                self.cube.set_ep_coord(self.ep);
+                mv(&mut self.cube);
+                self.ep = self.cube.ep_coord();
+            }
         }
     }
 
     pub fn u(&mut self) {
-        self.ep = self.transposition_tables.u[self.ep as usize];
+        self.apply(|t| &t.u, Cube::u);
     }
 
     pub fn l(&mut self) {
-        self.ep = self.transposition_tables.l[self.ep as usize];
+        self.apply(|t| &t.l, Cube::l);
     }
 
     pub fn f(&mut self) {
-        self.ep = self.transposition_tables.f[self.ep as usize];
+        self.apply(|t| &t.f, Cube::f);
     }
 
     pub fn r(&mut self) {
-        self.ep = self.transposition_tables.r[self.ep as usize];
+        self.apply(|t| &t.r, Cube::r);
     }
 
     pub fn b(&mut self) {
-        self.ep = self.transposition_tables.b[self.ep as usize];
+        self.apply(|t| &t.b, Cube::b);
     }
 
     pub fn d(&mut self) {
-        self.ep = self.transposition_tables.d[self.ep as usize];
+        self.apply(|t| &t.d, Cube::d);
     }
 
     pub fn encode(&self) -> u64 {
@@ -53,3 +72,21 @@ impl<'a> CoordCube<'a> {
         self.ep = coord as u32;
     }
 }
+
+impl Turn<CubeMove> for CoordCube<'_> {
+    fn turn(&mut self, mv: CubeMove) -> bool {
+        let quarter: fn(&mut Self) = match (mv.face, mv.layers) {
+            (Face::U, Layers::OUTER) => Self::u,
+            (Face::L, Layers::OUTER) => Self::l,
+            (Face::F, Layers::OUTER) => Self::f,
+            (Face::R, Layers::OUTER) => Self::r,
+            (Face::B, Layers::OUTER) => Self::b,
+            (Face::D, Layers::OUTER) => Self::d,
+            _ => return false,
+        };
+        for _ in 0..mv.power {
+            quarter(self);
+        }
+        true
+    }
+}
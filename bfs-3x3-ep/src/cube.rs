@@ -1,6 +1,11 @@
+use bfs_common::{
+    notation::{CubeMove, Face, Layers, Turn},
+    puzzle::Puzzle,
+};
+
 pub const EP_SIZE: usize = 479001600;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Cube {
     ep: [u8; 12],
 }
@@ -46,6 +51,16 @@ impl Cube {
         cycle4!(self.ep, 8, 9, 10, 11);
     }
 
+    /// Conjugates the edge permutation by a symmetry of the cube, given as the position each edge
+    /// position is taken to.
+    pub fn conjugate(&self, symmetry: &[u8; 12]) -> Self {
+        let mut ep = [0; 12];
+        for (i, &piece) in self.ep.iter().enumerate() {
+            ep[symmetry[i] as usize] = symmetry[piece as usize];
+        }
+        Self { ep }
+    }
+
     pub fn ep_coord(&self) -> u32 {
         combinatorics::indexing::encode_permutation(self.ep) as u32
     }
@@ -54,3 +69,66 @@ impl Cube {
         self.ep = combinatorics::indexing::decode_permutation(coord as u64);
     }
 }
+
+impl Default for Cube {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Puzzle for Cube {
+    const ID: &'static str = "3x3-ep";
+    const STATE_SIZE: u64 = EP_SIZE as u64;
+    const MOVES: &'static [&'static str] = &["U", "L", "F", "R", "B", "D"];
+    const ORDERS: &'static [usize] = &[4; 6];
+
+    fn solved() -> Self {
+        Self::new()
+    }
+
+    fn encode(&self) -> u64 {
+        self.ep_coord() as u64
+    }
+
+    fn decode(&mut self, coord: u64) {
+        self.set_ep_coord(coord as u32);
+    }
+
+    fn sub_coords(&self) -> Vec<(&'static str, u64)> {
+        vec![("ep", self.ep_coord() as u64)]
+    }
+
+    fn pieces(&self) -> Vec<(&'static str, Vec<u8>)> {
+        vec![("ep", self.ep.to_vec())]
+    }
+
+    fn do_move(&mut self, mv: usize) {
+        match mv {
+            0 => self.u(),
+            1 => self.l(),
+            2 => self.f(),
+            3 => self.r(),
+            4 => self.b(),
+            5 => self.d(),
+            _ => panic!("Invalid move"),
+        }
+    }
+}
+
+impl Turn<CubeMove> for Cube {
+    fn turn(&mut self, mv: CubeMove) -> bool {
+        let quarter: fn(&mut Self) = match (mv.face, mv.layers) {
+            (Face::U, Layers::OUTER) => Self::u,
+            (Face::L, Layers::OUTER) => Self::l,
+            (Face::F, Layers::OUTER) => Self::f,
+            (Face::R, Layers::OUTER) => Self::r,
+            (Face::B, Layers::OUTER) => Self::b,
+            (Face::D, Layers::OUTER) => Self::d,
+            _ => return false,
+        };
+        for _ in 0..mv.power {
+            quarter(self);
+        }
+        true
+    }
+}
@@ -0,0 +1,184 @@
+//! The 48 symmetries of the cube acting on edge permutations, and the classes of edge
+//! permutations under them.
+
+use bfs_common::{puzzle::Puzzle as _, symmetry::SymmetryClasses};
+
+use crate::{cube::Cube, Metric};
+
+/// The number of classes under all 48 symmetries.
+pub const CLASSES: u64 = 9985968;
+
+/// The number of classes under the 24 rotations.
+pub const ROTATION_CLASSES: u64 = 19966824;
+
+/// The faces of each edge position of a [`Cube`].
+const EDGES: [&[u8; 2]; 12] = [
+    b"UF", b"UL", b"UB", b"UR", b"FL", b"BL", b"BR", b"FR", b"DB", b"DL", b"DF", b"DR",
+];
+
+const IDENTITY: [u8; 12] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+
+/// The permutation of the edge positions made by a symmetry that takes each face in `from` to the
+/// face in the same place in `to`, and leaves the other faces where they are.
+fn edge_permutation(from: &[u8], to: &[u8]) -> [u8; 12] {
+    let face = |f| from.iter().position(|&g| g == f).map_or(f, |i| to[i]);
+    EDGES.map(|&[a, b]| {
+        let (a, b) = (face(a), face(b));
+        EDGES
+            .iter()
+            .position(|&&edge| edge == [a, b] || edge == [b, a])
+            .unwrap() as u8
+    })
+}
+
+/// `a` after `b`.
+fn compose(a: &[u8; 12], b: &[u8; 12]) -> [u8; 12] {
+    b.map(|i| a[i as usize])
+}
+
+/// The symmetries as permutations of the edge positions, starting with the identity. The first 24
+/// are the rotations, and the rest are the rotations combined with a mirror.
+pub fn symmetries() -> Vec<[u8; 12]> {
+    let x = edge_permutation(b"FUBD", b"UBDF");
+    let y = edge_permutation(b"FLBR", b"LBRF");
+    let mirror = edge_permutation(b"LR", b"RL");
+
+    let mut symmetries = vec![IDENTITY];
+    let mut i = 0;
+    while i < symmetries.len() {
+        for generator in [x, y] {
+            let symmetry = compose(&generator, &symmetries[i]);
+            if !symmetries.contains(&symmetry) {
+                symmetries.push(symmetry);
+            }
+        }
+        i += 1;
+    }
+
+    let mirrored = symmetries
+        .iter()
+        .map(|symmetry| compose(symmetry, &mirror))
+        .collect::<Vec<_>>();
+    symmetries.extend(mirrored);
+    symmetries
+}
+
+/// The number of symmetries that preserve `metric`. Mirrors turn clockwise moves into
+/// anticlockwise ones, so they only preserve metrics that have both.
+pub fn symmetry_count(metric: Metric) -> usize {
+    match metric {
+        Metric::Htm | Metric::Qtm => 48,
+        Metric::Utm => 24,
+    }
+}
+
+/// The number of classes searched for `metric`.
+pub fn class_count(metric: Metric) -> u64 {
+    match symmetry_count(metric) {
+        48 => CLASSES,
+        _ => ROTATION_CLASSES,
+    }
+}
+
+/// Finds the classes of edge permutations under the symmetries that preserve `metric`.
+pub fn classes(metric: Metric) -> SymmetryClasses {
+    let symmetries = symmetries();
+    let symmetries = &symmetries[..symmetry_count(metric)];
+
+    let mut cube = Cube::new();
+    SymmetryClasses::build(Cube::STATE_SIZE, symmetries.len(), |state, images| {
+        cube.set_ep_coord(state as u32);
+        for (image, symmetry) in images.iter_mut().zip(symmetries) {
+            *image = cube.conjugate(symmetry).ep_coord() as u64;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use bfs_common::puzzle::Puzzle;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    /// The size of the centralizer of `permutation` in the symmetric group, i.e. the number of edge
+    /// permutations that it fixes by conjugation.
+    fn centralizer_size(permutation: &[u8; 12]) -> u64 {
+        let mut seen = [false; 12];
+        let mut cycles = [0; 13];
+        for start in 0..12 {
+            let mut length = 0;
+            let mut i = start;
+            while !seen[i] {
+                seen[i] = true;
+                i = permutation[i] as usize;
+                length += 1;
+            }
+            cycles[length] += 1;
+        }
+
+        (1..=12u64)
+            .map(|length| {
+                let count = cycles[length as usize];
+                length.pow(count) * (1..=count as u64).product::<u64>()
+            })
+            .product()
+    }
+
+    #[test]
+    fn test_symmetries() {
+        let symmetries = symmetries();
+        assert_eq!(symmetries.len(), 48);
+        for (i, a) in symmetries.iter().enumerate() {
+            assert!(!symmetries[..i].contains(a));
+            for b in &symmetries {
+                assert!(symmetries.contains(&compose(a, b)));
+            }
+        }
+        for rotation in &symmetries[..24] {
+            for b in &symmetries[..24] {
+                assert!(symmetries[..24].contains(&compose(rotation, b)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_conjugate_moves() {
+        let symmetries = symmetries();
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..20 {
+            let cube = Cube::random(&mut rng);
+
+            // A symmetry takes each move to a move, and rotations keep the direction of a turn
+            // while mirrors reverse it
+            for (i, symmetry) in symmetries.iter().enumerate() {
+                let conjugate = cube.conjugate(symmetry);
+                for mv in 0..Cube::MOVES.len() {
+                    let mut moved = cube.clone();
+                    moved.do_move(mv);
+                    let moved = moved.conjugate(symmetry);
+
+                    let power = if i < 24 { 1 } else { 3 };
+                    assert!((0..Cube::MOVES.len()).any(|mv| {
+                        let mut cube = conjugate.clone();
+                        for _ in 0..power {
+                            cube.do_move(mv);
+                        }
+                        cube == moved
+                    }));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_class_counts() {
+        // Burnside's lemma
+        let symmetries = symmetries();
+        let count = |symmetries: &[[u8; 12]]| {
+            symmetries.iter().map(centralizer_size).sum::<u64>() / symmetries.len() as u64
+        };
+        assert_eq!(count(&symmetries), CLASSES);
+        assert_eq!(count(&symmetries[..24]), ROTATION_CLASSES);
+    }
+}
@@ -1,3 +1,7 @@
+use bfs_common::{
+    puzzle::Puzzle as _,
+    tables::{Table, TableMode, TableSet},
+};
 use rayon::iter::{
     IndexedParallelIterator as _, IntoParallelRefMutIterator as _, ParallelIterator,
 };
@@ -6,16 +10,32 @@ use crate::cube::{Cube, EP_SIZE};
 
 #[derive(Debug, PartialEq)]
 pub struct TranspositionTables {
-    pub u: Vec<u32>,
-    pub l: Vec<u32>,
-    pub f: Vec<u32>,
-    pub r: Vec<u32>,
-    pub b: Vec<u32>,
-    pub d: Vec<u32>,
+    pub u: Option<Table>,
+    pub l: Option<Table>,
+    pub f: Option<Table>,
+    pub r: Option<Table>,
+    pub b: Option<Table>,
+    pub d: Option<Table>,
 }
 
-impl TranspositionTables {
-    pub fn new() -> Self {
+impl TableSet for TranspositionTables {
+    const ID: &'static str = Cube::ID;
+    const LAYOUT: &'static [(&'static str, usize)] = &[
+        ("u", EP_SIZE),
+        ("l", EP_SIZE),
+        ("f", EP_SIZE),
+        ("r", EP_SIZE),
+        ("b", EP_SIZE),
+        ("d", EP_SIZE),
+    ];
+    const DERIVED: &'static [&'static str] = &["u", "l", "f", "r", "b", "d"];
+
+    fn build(mode: TableMode) -> Vec<Vec<u32>> {
+        // Every table is derived, and moves are made on a `Cube` instead
+        if mode == TableMode::Compact {
+            return Vec::new();
+        }
+
         let mut u = vec![0; EP_SIZE];
         let mut l = vec![0; EP_SIZE];
         let mut f = vec![0; EP_SIZE];
@@ -41,6 +61,20 @@ impl TranspositionTables {
         par_build_table!(b);
         par_build_table!(d);
 
-        Self { u, l, f, r, b, d }
+        vec![u, l, f, r, b, d]
+    }
+
+    fn from_tables(tables: Vec<Option<Table>>) -> Self {
+        let mut tables = tables.into_iter();
+        let mut next = || tables.next().unwrap();
+
+        Self {
+            u: next(),
+            l: next(),
+            f: next(),
+            r: next(),
+            b: next(),
+            d: next(),
+        }
     }
 }
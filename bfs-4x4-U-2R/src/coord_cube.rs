@@ -1,3 +1,8 @@
+use bfs_common::{
+    notation::{CubeMove, Face, Layers, Turn},
+    tables::Table,
+};
+
 use crate::{cube::Cube, transposition_tables::TranspositionTables};
 
 #[derive(Clone)]
@@ -17,6 +22,16 @@ impl<'a> CoordCube<'a> {
         }
     }
 
+    /// Applies a derived move from its tables, returning `false` if they weren't built.
+    fn apply(&mut self, edges: &Option<Table>, centers_corners: &Option<Table>) -> bool {
+        let (Some(edges), Some(centers_corners)) = (edges, centers_corners) else {
+            return false;
+        };
+        self.edges = edges[self.edges as usize];
+        self.centers_corners = centers_corners[self.centers_corners as usize];
+        true
+    }
+
     pub fn u(&mut self) {
         self.edges = self.transposition_tables.u_edges[self.edges as usize];
         self.centers_corners =
@@ -24,27 +39,38 @@ impl<'a> CoordCube<'a> {
     }
 
     pub fn u2(&mut self) {
-        self.edges = self.transposition_tables.u2_edges[self.edges as usize];
-        self.centers_corners =
-            self.transposition_tables.u2_centers_corners[self.centers_corners as usize];
+        let tables = self.transposition_tables;
+        if !self.apply(&tables.u2_edges, &tables.u2_centers_corners) {
+            self.u();
+            self.u();
+        }
     }
 
     pub fn up(&mut self) {
-        self.edges = self.transposition_tables.up_edges[self.edges as usize];
-        self.centers_corners =
-            self.transposition_tables.up_centers_corners[self.centers_corners as usize];
+        let tables = self.transposition_tables;
+        if !self.apply(&tables.up_edges, &tables.up_centers_corners) {
+            self.u();
+            self.u();
+            self.u();
+        }
     }
 
     pub fn ur(&mut self) {
-        self.edges = self.transposition_tables.ur_edges[self.edges as usize];
-        self.centers_corners =
-            self.transposition_tables.ur_centers_corners[self.centers_corners as usize];
+        let tables = self.transposition_tables;
+        if !self.apply(&tables.ur_edges, &tables.ur_centers_corners) {
+            self.u();
+            self.r();
+        }
     }
 
     pub fn urp(&mut self) {
-        self.edges = self.transposition_tables.urp_edges[self.edges as usize];
-        self.centers_corners =
-            self.transposition_tables.urp_centers_corners[self.centers_corners as usize];
+        let tables = self.transposition_tables;
+        if !self.apply(&tables.urp_edges, &tables.urp_centers_corners) {
+            self.u();
+            self.r();
+            self.r();
+            self.r();
+        }
     }
 
     pub fn r(&mut self) {
@@ -54,9 +80,11 @@ impl<'a> CoordCube<'a> {
     }
 
     pub fn r2(&mut self) {
-        self.edges = self.transposition_tables.r2_edges[self.edges as usize];
-        self.centers_corners =
-            self.transposition_tables.r2_centers_corners[self.centers_corners as usize];
+        let tables = self.transposition_tables;
+        if !self.apply(&tables.r2_edges, &tables.r2_centers_corners) {
+            self.r();
+            self.r();
+        }
     }
 
     pub fn encode(&self) -> u64 {
@@ -68,3 +96,17 @@ impl<'a> CoordCube<'a> {
         self.centers_corners = (coord % 75600) as u32;
     }
 }
+
+impl Turn<CubeMove> for CoordCube<'_> {
+    fn turn(&mut self, mv: CubeMove) -> bool {
+        let quarter: fn(&mut Self) = match (mv.face, mv.layers) {
+            (Face::U, Layers::OUTER) => Self::u,
+            (Face::R, Layers::Slice(2)) => Self::r,
+            _ => return false,
+        };
+        for _ in 0..mv.power {
+            quarter(self);
+        }
+        true
+    }
+}
@@ -1,3 +1,20 @@
+use bfs_common::{
+    goal::GoalSet,
+    notation::{CubeMove, Face, Layers, Turn},
+    puzzle::Puzzle,
+};
+
+/// Where the reflection that swaps the F and B faces takes each edge position. It turns every move
+/// into its inverse.
+const MIRROR_EDGES: [u8; 10] = [5, 4, 3, 2, 1, 0, 7, 6, 9, 8];
+
+/// Where the reflection takes each center position.
+const MIRROR_CENTERS: [u8; 10] = [1, 0, 3, 2, 9, 8, 7, 6, 5, 4];
+
+/// The color each center color is reflected to. The F and B centers swap colors.
+const MIRROR_COLORS: [u8; 4] = [0, 3, 2, 1];
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Cube {
     corners: u8,
     edges: [u8; 10],
@@ -28,6 +45,20 @@ impl Cube {
         self.centers[2..10].rotate_left(2);
     }
 
+    /// The cube seen in a mirror that swaps F and B, so that the mirror image of a scramble is the
+    /// scramble with every move inverted.
+    ///
+    /// There is no inverse to go with it, because centers of the same color aren't told apart.
+    pub fn mirror(&self) -> Self {
+        let mut cube = Self::new();
+        cube.corners = (4 - self.corners) % 4;
+        for i in 0..10 {
+            cube.edges[MIRROR_EDGES[i] as usize] = MIRROR_EDGES[self.edges[i] as usize];
+            cube.centers[MIRROR_CENTERS[i] as usize] = MIRROR_COLORS[self.centers[i] as usize];
+        }
+        cube
+    }
+
     pub fn edge_coord(&self) -> u32 {
         combinatorics::indexing::encode_permutation(self.edges) as u32
     }
@@ -53,3 +84,84 @@ impl Cube {
         self.set_center_coord(coord / 4);
     }
 }
+
+impl Default for Cube {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Puzzle for Cube {
+    const ID: &'static str = "4x4-U-2R";
+    const STATE_SIZE: u64 = 274337280000;
+    const MOVES: &'static [&'static str] = &["U", "2R"];
+    const ORDERS: &'static [usize] = &[4, 4];
+    const GOALS: &'static [&'static str] = &["auf", "centers"];
+
+    fn solved() -> Self {
+        Self::new()
+    }
+
+    fn encode(&self) -> u64 {
+        self.edge_coord() as u64 * 75600 + self.center_corner_coord() as u64
+    }
+
+    fn decode(&mut self, coord: u64) {
+        self.set_edge_coord((coord / 75600) as u32);
+        self.set_center_corner_coord((coord % 75600) as u32);
+    }
+
+    fn sub_coords(&self) -> Vec<(&'static str, u64)> {
+        vec![
+            ("edges", self.edge_coord() as u64),
+            ("center_corner", self.center_corner_coord() as u64),
+        ]
+    }
+
+    fn pieces(&self) -> Vec<(&'static str, Vec<u8>)> {
+        vec![
+            ("edges", self.edges.to_vec()),
+            ("centers", self.centers.to_vec()),
+            ("corners", vec![self.corners]),
+        ]
+    }
+
+    fn do_move(&mut self, mv: usize) {
+        match mv {
+            0 => self.u(),
+            1 => self.r(),
+            _ => panic!("Invalid move"),
+        }
+    }
+
+    fn goal(name: &str) -> Option<GoalSet> {
+        match name {
+            // Solved up to a turn of the U layer
+            "auf" => Some(GoalSet::from_scrambles::<Self>(&["", "U", "U2", "U'"]).unwrap()),
+            // Every arrangement of the centers, with the corners and edges solved
+            "centers" => {
+                let edges = Self::new().edge_coord() as u64 * 75600;
+                Some(GoalSet::from_predicate::<Self>(
+                    "centers",
+                    edges..edges + 75600,
+                    |cube| cube.corners == 0,
+                ))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Turn<CubeMove> for Cube {
+    fn turn(&mut self, mv: CubeMove) -> bool {
+        let quarter: fn(&mut Self) = match (mv.face, mv.layers) {
+            (Face::U, Layers::OUTER) => Self::u,
+            (Face::R, Layers::Slice(2)) => Self::r,
+            _ => return false,
+        };
+        for _ in 0..mv.power {
+            quarter(self);
+        }
+        true
+    }
+}
@@ -2,24 +2,28 @@
 
 mod coord_cube;
 mod cube;
+mod symmetry;
 mod transposition_tables;
 
-use std::path::PathBuf;
-
-use disk_based_bfs::{
-    builder::BfsBuilder,
-    callback::BfsCallback,
-    expander::BfsExpander,
-    provider::{BfsSettingsProvider, ChunkFilesBehavior, UpdateFilesBehavior},
+use bfs_common::{
+    config::RunConfig,
+    manifest::Variant,
+    runner::{self, Bounds, Task, TaskError},
+    tables::TableSet as _,
 };
-use tracing_subscriber::{layer::SubscriberExt as _, util::SubscriberInitExt as _, EnvFilter};
+use disk_based_bfs::expander::BfsExpander;
 
+pub use crate::cube::Cube;
 use crate::{coord_cube::CoordCube, transposition_tables::TranspositionTables};
 
 const EXPANSION_NODES_HTM: usize = 6;
 const EXPANSION_NODES_QTM: usize = 4;
 const EXPANSION_NODES_UTM: usize = 2;
 
+const CALLBACK_BOUND_HTM: usize = 27;
+const CALLBACK_BOUND_QTM: usize = 34;
+const CALLBACK_BOUND_UTM: usize = 48;
+
 #[derive(Clone)]
 struct ExpanderHtm<'a> {
     cube: CoordCube<'a>,
@@ -77,43 +81,6 @@ impl BfsExpander<EXPANSION_NODES_UTM> for ExpanderUtm<'_> {
     }
 }
 
-macro_rules! define_callback {
-    ($name:ident, $depth:expr) => {
-        #[derive(Clone)]
-        struct $name;
-
-        impl BfsCallback for $name {
-            fn new_state(&mut self, depth: usize, state: u64) {
-                if depth > $depth {
-                    tracing::info!("depth {depth} state {state}");
-                }
-            }
-
-            fn end_of_chunk(&self, _: usize, _: usize) {}
-        }
-    };
-}
-
-define_callback!(CallbackHtm, 26);
-define_callback!(CallbackQtm, 33);
-define_callback!(CallbackUtm, 47);
-
-struct Provider;
-
-impl BfsSettingsProvider for Provider {
-    fn chunk_root_idx(&self, chunk_idx: usize) -> usize {
-        chunk_idx % 4
-    }
-
-    fn update_files_behavior(&self, _: usize) -> UpdateFilesBehavior {
-        UpdateFilesBehavior::DontMerge
-    }
-
-    fn chunk_files_behavior(&self, _: usize) -> ChunkFilesBehavior {
-        ChunkFilesBehavior::Delete
-    }
-}
-
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Metric {
     Htm,
@@ -121,60 +88,56 @@ pub enum Metric {
     Utm,
 }
 
-pub fn run(metric: Metric) {
-    tracing_subscriber::registry()
-        .with(
-            EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "disk_based_bfs=trace,bfs_4x4_U_2R=trace".into()),
-        )
-        .with(
-            tracing_subscriber::fmt::layer()
-                .compact()
-                .with_ansi(false)
-                .with_thread_names(true)
-                .with_line_number(true),
-        )
-        .init();
-
-    let transposition_tables = TranspositionTables::new();
+/// Runs `task`, for the classes of states under mirroring if `symmetry` is set. The mirror turns
+/// clockwise moves into anticlockwise ones, so it can't be used with [`Metric::Utm`].
+pub fn run(
+    metric: Metric,
+    symmetry: bool,
+    config: &RunConfig,
+    task: &Task,
+) -> Result<(), TaskError> {
+    if symmetry && metric == Metric::Utm {
+        return Err(TaskError::NoSymmetries);
+    }
+
+    let (transposition_tables, tables) = TranspositionTables::load_with_checksum(
+        config.table_directory.as_deref(),
+        config.table_mode,
+    );
+    let variant = Variant {
+        metric: Some(format!("{metric:?}")),
+        tables: Some(tables),
+        one_way: metric == Metric::Utm,
+        ..Variant::default()
+    };
 
     macro_rules! run {
-        ($expander:ident, $callback:ident) => {
-            BfsBuilder::new()
-                .threads(48)
-                // 2 * 48 chunks
-                .chunk_size_bytes(357210000)
-                .update_memory(112 * (1 << 30))
-                .num_update_blocks(2 * 2 * 48 * 48)
-                .capacity_check_frequency(256)
-                .initial_states(&[CoordCube::new(&transposition_tables).encode()])
-                .state_size(274337280000)
-                .root_directories(&[
-                    PathBuf::from("/media/ben/drive1/bfs/4x4-U-2R/"),
-                    PathBuf::from("/media/ben/drive2/bfs/4x4-U-2R/"),
-                    PathBuf::from("/media/ben/drive3/bfs/4x4-U-2R/"),
-                    PathBuf::from("/media/ben/drive4/bfs/4x4-U-2R/"),
-                ])
-                .initial_memory_limit(1 << 32)
-                .available_disk_space_limit(256 * (1 << 30))
-                .update_array_threshold(357210000)
-                .use_locked_io(false)
-                .sync_filesystem(false)
-                .compute_checksums(true)
-                .use_compression(true)
-                .expander($expander {
-                    cube: CoordCube::new(&transposition_tables),
-                })
-                .callback($callback)
-                .settings_provider(Provider)
-                .run_no_defaults()
-                .unwrap()
-        };
+        ($expander:ident, $expansion_nodes:ident, $callback_bound:expr) => {{
+            let expander = $expander {
+                cube: CoordCube::new(&transposition_tables),
+            };
+            let bounds = Bounds {
+                log: $callback_bound,
+                keep: Bounds::NEVER,
+            };
+            if symmetry {
+                runner::run_reduced::<Cube, _, _, $expansion_nodes, $expansion_nodes>(
+                    config,
+                    task,
+                    variant,
+                    expander,
+                    bounds,
+                    symmetry::reduction(),
+                )
+            } else {
+                runner::run::<Cube, _, $expansion_nodes>(config, task, variant, expander, bounds)
+            }
+        }};
     }
 
     match metric {
-        Metric::Htm => run!(ExpanderHtm, CallbackHtm),
-        Metric::Qtm => run!(ExpanderQtm, CallbackQtm),
-        Metric::Utm => run!(ExpanderUtm, CallbackUtm),
+        Metric::Htm => run!(ExpanderHtm, EXPANSION_NODES_HTM, CALLBACK_BOUND_HTM),
+        Metric::Qtm => run!(ExpanderQtm, EXPANSION_NODES_QTM, CALLBACK_BOUND_QTM),
+        Metric::Utm => run!(ExpanderUtm, EXPANSION_NODES_UTM, CALLBACK_BOUND_UTM),
     }
 }
@@ -0,0 +1,87 @@
+//! The symmetry of the <U, 2R> state space: the reflection that swaps F and B.
+
+use bfs_common::symmetry::SmallestImage;
+
+use crate::cube::Cube;
+
+const SYMMETRIES: &[fn(&Cube) -> Cube] = &[Cube::mirror];
+
+/// The classes of states under mirroring.
+pub fn reduction() -> SmallestImage<Cube> {
+    SmallestImage::new(SYMMETRIES, &[])
+}
+
+#[cfg(test)]
+mod tests {
+    use bfs_common::{
+        expander::layers,
+        puzzle::Puzzle,
+        symmetry::{ReducedExpander, Reduction},
+        testing::{scrambled, scrambles},
+    };
+
+    use super::*;
+
+    bfs_common::expander! {
+        struct Expander {
+            cube: Cube,
+        }
+        const EXPANSION_NODES;
+        metric = Htm;
+        generators = [u: 4, r: 4];
+    }
+
+    #[test]
+    fn test_mirror() {
+        // Every move becomes its inverse
+        let mirrored_moves = [[0; 3], [1; 3]];
+        for moves in scrambles::<Cube>() {
+            let cube = scrambled::<Cube>(&moves);
+            let mirror = cube.mirror();
+            assert_eq!(mirror.mirror(), cube);
+            assert_eq!(Cube::from_coord(mirror.encode()), mirror);
+
+            let mirrored = moves
+                .iter()
+                .flat_map(|&mv| mirrored_moves[mv])
+                .collect::<Vec<_>>();
+            assert_eq!(scrambled::<Cube>(&mirrored), mirror);
+        }
+    }
+
+    #[test]
+    fn test_reduced_search() {
+        let solved = Cube::new().encode();
+        let mut reduction = reduction();
+
+        let full = layers(&mut Expander { cube: Cube::new() }, &[solved], 7);
+        let reduced = layers::<_, EXPANSION_NODES>(
+            &mut ReducedExpander::<_, _, EXPANSION_NODES> {
+                expander: Expander { cube: Cube::new() },
+                reduction: reduction.clone(),
+            },
+            &[reduction.class(solved)],
+            7,
+        );
+
+        assert_eq!(full.len(), reduced.len());
+        for (full, reduced) in full.iter().zip(&reduced) {
+            let unfolded = reduced.iter().map(|&c| reduction.size(c)).sum::<u64>();
+            assert_eq!(unfolded, full.len() as u64);
+            assert!(reduced.len() < full.len() || full.len() == 1);
+        }
+    }
+
+    #[test]
+    fn test_goals() {
+        // The mirror maps each goal set to itself, so it can be searched by class
+        for (name, size) in [("auf", 4), ("centers", 18900)] {
+            let goal = Cube::goal(name).unwrap();
+            assert_eq!(goal.states.len(), size, "{name}");
+            for &state in &goal.states {
+                let mirror = Cube::from_coord(state).mirror();
+                assert!(goal.contains(mirror.encode()), "{name}");
+            }
+        }
+    }
+}
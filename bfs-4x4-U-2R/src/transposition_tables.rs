@@ -1,100 +1,132 @@
+use bfs_common::{
+    puzzle::Puzzle as _,
+    tables::{par_build, retain, Table, TableMode, TableSet},
+};
+
 use crate::cube::Cube;
 
 pub struct TranspositionTables {
-    pub u_edges: Vec<u32>,
-    pub u_centers_corners: Vec<u32>,
-    pub u2_edges: Vec<u32>,
-    pub u2_centers_corners: Vec<u32>,
-    pub up_edges: Vec<u32>,
-    pub up_centers_corners: Vec<u32>,
-    pub ur_edges: Vec<u32>,
-    pub ur_centers_corners: Vec<u32>,
-    pub urp_edges: Vec<u32>,
-    pub urp_centers_corners: Vec<u32>,
-    pub r_edges: Vec<u32>,
-    pub r_centers_corners: Vec<u32>,
-    pub r2_edges: Vec<u32>,
-    pub r2_centers_corners: Vec<u32>,
+    pub u_edges: Table,
+    pub u_centers_corners: Table,
+    pub u2_edges: Option<Table>,
+    pub u2_centers_corners: Option<Table>,
+    pub up_edges: Option<Table>,
+    pub up_centers_corners: Option<Table>,
+    pub ur_edges: Option<Table>,
+    pub ur_centers_corners: Option<Table>,
+    pub urp_edges: Option<Table>,
+    pub urp_centers_corners: Option<Table>,
+    pub r_edges: Table,
+    pub r_centers_corners: Table,
+    pub r2_edges: Option<Table>,
+    pub r2_centers_corners: Option<Table>,
 }
 
-impl TranspositionTables {
-    pub fn new() -> Self {
-        let mut u_edges = vec![0; 3628800];
-        let mut u_centers_corners = vec![0; 75600];
-        let mut u2_edges = vec![0; 3628800];
-        let mut u2_centers_corners = vec![0; 75600];
-        let mut up_edges = vec![0; 3628800];
-        let mut up_centers_corners = vec![0; 75600];
-        let mut ur_edges = vec![0; 3628800];
-        let mut ur_centers_corners = vec![0; 75600];
-        let mut urp_edges = vec![0; 3628800];
-        let mut urp_centers_corners = vec![0; 75600];
-        let mut r_edges = vec![0; 3628800];
-        let mut r_centers_corners = vec![0; 75600];
-        let mut r2_edges = vec![0; 3628800];
-        let mut r2_centers_corners = vec![0; 75600];
-
-        let mut cube = Cube::new();
+impl TableSet for TranspositionTables {
+    const ID: &'static str = Cube::ID;
+    const LAYOUT: &'static [(&'static str, usize)] = &[
+        ("u_edges", 3628800),
+        ("u_centers_corners", 75600),
+        ("u2_edges", 3628800),
+        ("u2_centers_corners", 75600),
+        ("up_edges", 3628800),
+        ("up_centers_corners", 75600),
+        ("ur_edges", 3628800),
+        ("ur_centers_corners", 75600),
+        ("urp_edges", 3628800),
+        ("urp_centers_corners", 75600),
+        ("r_edges", 3628800),
+        ("r_centers_corners", 75600),
+        ("r2_edges", 3628800),
+        ("r2_centers_corners", 75600),
+    ];
+    const DERIVED: &'static [&'static str] = &[
+        "u2_edges",
+        "u2_centers_corners",
+        "up_edges",
+        "up_centers_corners",
+        "ur_edges",
+        "ur_centers_corners",
+        "urp_edges",
+        "urp_centers_corners",
+        "r2_edges",
+        "r2_centers_corners",
+    ];
 
-        for i in 0..3628800 {
-            cube.set_edge_coord(i);
-            let i = i as usize;
+    fn build(mode: TableMode) -> Vec<Vec<u32>> {
+        let edges = par_build(3628800, Cube::new, |cube, i| {
+            cube.set_edge_coord(i as u32);
             cube.u();
-            u_edges[i] = cube.edge_coord();
+            let u = cube.edge_coord();
             cube.r();
-            ur_edges[i] = cube.edge_coord();
+            let ur = cube.edge_coord();
             cube.r();
             cube.r();
-            urp_edges[i] = cube.edge_coord();
+            let urp = cube.edge_coord();
             cube.r();
             cube.u();
-            u2_edges[i] = cube.edge_coord();
+            let u2 = cube.edge_coord();
             cube.u();
-            up_edges[i] = cube.edge_coord();
+            let up = cube.edge_coord();
             cube.u();
             cube.r();
-            r_edges[i] = cube.edge_coord();
+            let r = cube.edge_coord();
             cube.r();
-            r2_edges[i] = cube.edge_coord();
-        }
+            let r2 = cube.edge_coord();
+            [u, u2, up, ur, urp, r, r2]
+        });
 
-        for i in 0..75600 {
-            cube.set_center_corner_coord(i);
-            let i = i as usize;
+        let centers_corners = par_build(75600, Cube::new, |cube, i| {
+            cube.set_center_corner_coord(i as u32);
             cube.u();
-            u_centers_corners[i] = cube.center_corner_coord();
+            let u = cube.center_corner_coord();
             cube.r();
-            ur_centers_corners[i] = cube.center_corner_coord();
+            let ur = cube.center_corner_coord();
             cube.r();
             cube.r();
-            urp_centers_corners[i] = cube.center_corner_coord();
+            let urp = cube.center_corner_coord();
             cube.r();
             cube.u();
-            u2_centers_corners[i] = cube.center_corner_coord();
+            let u2 = cube.center_corner_coord();
             cube.u();
-            up_centers_corners[i] = cube.center_corner_coord();
+            let up = cube.center_corner_coord();
             cube.u();
             cube.r();
-            r_centers_corners[i] = cube.center_corner_coord();
+            let r = cube.center_corner_coord();
             cube.r();
-            r2_centers_corners[i] = cube.center_corner_coord();
-        }
+            let r2 = cube.center_corner_coord();
+            [u, u2, up, ur, urp, r, r2]
+        });
+
+        // Both are in move order, so interleaving them gives the layout order
+        let tables = edges
+            .into_iter()
+            .zip(centers_corners)
+            .flat_map(|(edges, centers_corners)| [edges, centers_corners])
+            .collect();
+
+        retain::<Self>(mode, tables)
+    }
+
+    fn from_tables(tables: Vec<Option<Table>>) -> Self {
+        let mut tables = tables.into_iter();
+        let mut next = || tables.next().unwrap();
 
         Self {
-            u_edges,
-            u_centers_corners,
-            u2_edges,
-            u2_centers_corners,
-            up_edges,
-            up_centers_corners,
-            ur_edges,
-            ur_centers_corners,
-            urp_edges,
-            urp_centers_corners,
-            r_edges,
-            r_centers_corners,
-            r2_edges,
-            r2_centers_corners,
+            u_edges: next().unwrap(),
+            u_centers_corners: next().unwrap(),
+            u2_edges: next(),
+            u2_centers_corners: next(),
+            up_edges: next(),
+            up_centers_corners: next(),
+            ur_edges: next(),
+            ur_centers_corners: next(),
+            urp_edges: next(),
+            urp_centers_corners: next(),
+            r_edges: next().unwrap(),
+            r_centers_corners: next().unwrap(),
+            r2_edges: next(),
+            r2_centers_corners: next(),
         }
     }
 }
@@ -0,0 +1,20 @@
+//! Records the revision of the code that is being built, so that it can be written to the
+//! manifests of searches. Outside of a git checkout, the package version is used instead.
+
+use std::{env, process::Command};
+
+fn main() {
+    let revision = Command::new("git")
+        .args(["describe", "--always", "--tags"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|revision| revision.trim().to_owned())
+        .filter(|revision| !revision.is_empty())
+        .unwrap_or_else(|| env::var("CARGO_PKG_VERSION").unwrap());
+
+    println!("cargo:rustc-env=BFS_REVISION={revision}");
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+    println!("cargo:rerun-if-changed=../.git/refs");
+}
@@ -0,0 +1,133 @@
+//! Collects the states in the deepest layer of a search, and writes them out in a form that can be
+//! read without decoding the coordinates by hand.
+
+use std::{
+    fs,
+    io::{self, BufWriter, Write as _},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::puzzle::Puzzle;
+
+/// The deepest states seen by an [`AntipodeCollector`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Antipodes {
+    pub depth: usize,
+    /// Sorted coordinates of the states at `depth`.
+    pub states: Vec<u64>,
+}
+
+impl Antipodes {
+    fn add(&mut self, depth: usize, state: u64) {
+        if depth > self.depth || self.states.is_empty() {
+            self.depth = depth;
+            self.states.clear();
+        }
+        if depth == self.depth {
+            self.states.push(state);
+        }
+    }
+
+    /// Writes one line per state to `path`, with the coordinate, a scramble if `scramble` gives
+    /// one, and the `Debug` output of the decoded state.
+    pub fn write<P: Puzzle>(
+        &self,
+        path: &Path,
+        run: &str,
+        mut scramble: impl FnMut(u64) -> Option<String>,
+    ) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut writer = BufWriter::new(fs::File::create(path)?);
+        writeln!(
+            writer,
+            "# {} ({run}): {} states at depth {}",
+            P::ID,
+            self.states.len(),
+            self.depth,
+        )?;
+
+        let mut puzzle = P::solved();
+        for &state in &self.states {
+            puzzle.decode(state);
+            match scramble(state) {
+                Some(scramble) => writeln!(writer, "{state}\t{scramble}\t{puzzle:?}")?,
+                None => writeln!(writer, "{state}\t{puzzle:?}")?,
+            }
+        }
+
+        writer.flush()
+    }
+}
+
+/// Keeps the deepest states it is given, ignoring any shallower than a minimum depth. Unlike
+/// [`DepthCounter`](crate::report::DepthCounter), all clones add their states straight to the
+/// same collection, since states that deep are rare enough to be logged one by one anyway.
+#[derive(Clone)]
+pub struct AntipodeCollector {
+    min_depth: usize,
+    shared: Arc<Mutex<Antipodes>>,
+}
+
+impl AntipodeCollector {
+    pub fn new(min_depth: usize) -> Self {
+        Self {
+            min_depth,
+            shared: Arc::new(Mutex::new(Antipodes::default())),
+        }
+    }
+
+    pub fn add(&self, depth: usize, state: u64) {
+        if depth >= self.min_depth {
+            self.shared.lock().unwrap().add(depth, state);
+        }
+    }
+
+    /// The deepest states given to any clone of this collector, or `None` if none were at least
+    /// `min_depth` deep.
+    pub fn antipodes(&self) -> Option<Antipodes> {
+        let mut antipodes = self.shared.lock().unwrap().clone();
+        if antipodes.states.is_empty() {
+            return None;
+        }
+        antipodes.states.sort_unstable();
+        Some(antipodes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collector() {
+        let collector = AntipodeCollector::new(2);
+        let a = collector.clone();
+        a.add(1, 10);
+        assert_eq!(collector.antipodes(), None);
+
+        let b = collector.clone();
+        for (depth, state) in [(2, 20), (3, 31), (3, 30)] {
+            a.add(depth, state);
+        }
+        for (depth, state) in [(2, 21), (3, 32), (1, 11)] {
+            b.add(depth, state);
+        }
+        drop(a);
+
+        let c = collector.clone();
+        c.add(2, 22);
+        assert_eq!(
+            collector.antipodes(),
+            Some(Antipodes {
+                depth: 3,
+                states: vec![30, 31, 32],
+            })
+        );
+    }
+}
@@ -0,0 +1,224 @@
+//! Checkpoints of a search, so that an interrupted run can carry on from the last depth it
+//! finished instead of starting over.
+//!
+//! disk-based-bfs keeps the state of a search in its root directories, but the counts, antipodes
+//! and depth table that the runner collects along the way only live in memory, apart from the
+//! depth table's temporary file. Each time a depth is finished, a [`Checkpoint`] of them is written
+//! next to the search's files, along with what is being searched. A later run over the same
+//! directories restores it if it is the same search, and refuses to start if it isn't. The
+//! checkpoint is removed once the search has reached every state it can, but not when it was
+//! stopped early by a limit, so that it can be carried on later.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::antipodes::Antipodes;
+
+#[derive(Debug, Error)]
+pub enum CheckpointError {
+    #[error("{path}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+
+    #[error("{path}: {source}")]
+    Json {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+
+    #[error(
+        "{path} belongs to a different search: its {what} is {found}, not {expected}. Resume \
+         that search, or remove its files from the root directories, before starting a new one"
+    )]
+    Mismatch {
+        path: PathBuf,
+        what: &'static str,
+        found: String,
+        expected: String,
+    },
+}
+
+/// The path of the checkpoint of a search whose first root directory is `directory`. There is
+/// only one per directory, whatever the run, so that two searches can't share directories.
+pub fn path(directory: &Path) -> PathBuf {
+    directory.join("checkpoint.json")
+}
+
+/// Everything a search has found up to and including the last depth it finished.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub puzzle: String,
+    pub run: String,
+    /// The number of states searched, which is the number of classes when reducing by symmetry.
+    pub state_size: u64,
+    /// The temporary file the depth table is being written to, if there is one.
+    pub depth_table: Option<PathBuf>,
+    /// `counts[d]` is the number of states found at depth `d`, for each finished depth.
+    pub counts: Vec<u64>,
+    /// The number of states in the classes found at each finished depth, when reducing by
+    /// symmetry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub class_sizes: Option<Vec<u64>>,
+    /// The deepest states collected so far, if any depth deep enough to collect them was finished.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub antipodes: Option<Antipodes>,
+}
+
+impl Checkpoint {
+    /// A checkpoint of a search that hasn't finished any depths yet.
+    pub fn new(puzzle: &str, run: &str, state_size: u64, depth_table: Option<PathBuf>) -> Self {
+        Self {
+            puzzle: puzzle.to_owned(),
+            run: run.to_owned(),
+            state_size,
+            depth_table,
+            counts: Vec::new(),
+            class_sizes: None,
+            antipodes: None,
+        }
+    }
+
+    /// The last finished depth, or `None` if no depths were finished.
+    pub fn depth(&self) -> Option<usize> {
+        self.counts.len().checked_sub(1)
+    }
+
+    /// Reads the checkpoint at `path`, or returns `None` if there isn't one.
+    pub fn read(path: &Path) -> Result<Option<Self>, CheckpointError> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(source) => {
+                return Err(CheckpointError::Io {
+                    path: path.to_owned(),
+                    source,
+                })
+            }
+        };
+        serde_json::from_str(&contents)
+            .map(Some)
+            .map_err(|source| CheckpointError::Json {
+                path: path.to_owned(),
+                source,
+            })
+    }
+
+    /// Checks that the checkpoint at `path` was made by the same search as `expected`, i.e. with
+    /// the same puzzle, run (which names the metric and generators), state size and depth table.
+    pub fn check(&self, path: &Path, expected: &Self) -> Result<(), CheckpointError> {
+        let mismatch = |what, found: String, expected: String| {
+            if found == expected {
+                Ok(())
+            } else {
+                Err(CheckpointError::Mismatch {
+                    path: path.to_owned(),
+                    what,
+                    found,
+                    expected,
+                })
+            }
+        };
+        let table = |table: &Option<PathBuf>| {
+            table
+                .as_ref()
+                .map_or("none".to_owned(), |path| path.display().to_string())
+        };
+
+        mismatch("puzzle", self.puzzle.clone(), expected.puzzle.clone())?;
+        mismatch("run", self.run.clone(), expected.run.clone())?;
+        mismatch(
+            "state size",
+            self.state_size.to_string(),
+            expected.state_size.to_string(),
+        )?;
+        mismatch(
+            "depth table",
+            table(&self.depth_table),
+            table(&expected.depth_table),
+        )
+    }
+
+    /// Writes the checkpoint to `path`, replacing any earlier one only once it has been written
+    /// in full.
+    pub fn write(&self, path: &Path) -> Result<(), CheckpointError> {
+        let tmp_path = path.with_extension("json.tmp");
+        let io_err = |path: &Path| {
+            let path = path.to_owned();
+            move |source| CheckpointError::Io { path, source }
+        };
+
+        let json = serde_json::to_string_pretty(self).map_err(|source| CheckpointError::Json {
+            path: path.to_owned(),
+            source,
+        })?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(io_err(parent))?;
+        }
+        fs::write(&tmp_path, json).map_err(io_err(&tmp_path))?;
+        fs::rename(&tmp_path, path).map_err(io_err(path))
+    }
+
+    /// Removes the checkpoint at `path` once its search has finished.
+    pub fn remove(path: &Path) -> Result<(), CheckpointError> {
+        match fs::remove_file(path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(CheckpointError::Io {
+                path: path.to_owned(),
+                source: err,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    #[test]
+    fn test_write_and_check() {
+        let dir = temp_dir("checkpoint-check");
+        let path = path(&dir);
+        assert_eq!(Checkpoint::read(&path).unwrap(), None);
+
+        let expected = Checkpoint::new("puzzle", "puzzle.htm", 100, None);
+        let checkpoint = Checkpoint {
+            counts: vec![1, 4, 10],
+            antipodes: Some(Antipodes {
+                depth: 2,
+                states: vec![3, 5],
+            }),
+            ..expected.clone()
+        };
+        checkpoint.write(&path).unwrap();
+
+        let read = Checkpoint::read(&path).unwrap().unwrap();
+        assert_eq!(read, checkpoint);
+        assert_eq!(read.depth(), Some(2));
+        read.check(&path, &expected).unwrap();
+
+        let qtm = Checkpoint::new("puzzle", "puzzle.qtm", 100, None);
+        assert!(matches!(
+            read.check(&path, &qtm),
+            Err(CheckpointError::Mismatch { what: "run", .. })
+        ));
+        let table = Checkpoint::new("puzzle", "puzzle.htm", 100, Some(dir.join("depths")));
+        assert!(matches!(
+            read.check(&path, &table),
+            Err(CheckpointError::Mismatch {
+                what: "depth table",
+                ..
+            })
+        ));
+
+        Checkpoint::remove(&path).unwrap();
+        Checkpoint::remove(&path).unwrap();
+        assert_eq!(Checkpoint::read(&path).unwrap(), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,597 @@
+//! Run settings loaded from a TOML file.
+//!
+//! A config file is a tree of tables. Keys at the top level apply to every run, and each nested
+//! table (e.g. `[3x3-U-r]`, then `[3x3-U-r.ur.htm]`) overrides them for a more specific run. A run
+//! picks a path through the tree with [`ConfigFile::resolve`], and the most specific value of each
+//! key wins.
+//!
+//! Sizes in bytes can be written either as integers or as strings with a binary suffix, e.g.
+//! `update_memory = "112 GiB"`.
+//!
+//! `chunk_size_bytes`, `num_update_blocks` and `update_array_threshold` may be left out if
+//! `memory_budget` is given, in which case they are derived from the state size by a [`Planner`].
+//!
+//! `table_directory` is optional, and turns on saving transposition tables to disk (see
+//! [`crate::tables`]). `table_mode = "compact"` keeps fewer tables, at some cost in speed.
+//!
+//! Each run keeps its files in a subdirectory of each of the `root_directories`, named after the
+//! run, so runs of different metrics or goal sets can share the directories in the file.
+//!
+//! `report_directory` is where the distance distribution is written, and defaults to `reports`.
+//! `depth_table_directory` is optional, and turns on writing a depth table (see [`crate::depths`])
+//! that optimal solutions can be found from.
+//!
+//! The goal set of a run comes from the command line rather than the file, but it is part of the
+//! [`RunConfig`] so that every task of the run uses the same one.
+
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{
+    goal::Goal,
+    plan::{PlanError, Planner},
+    tables::TableMode,
+};
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("failed to read {path}: {source}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("failed to parse config: {0}")]
+    Parse(#[from] toml::de::Error),
+
+    #[error("[{section}]: {source}")]
+    Section {
+        section: String,
+        source: toml::de::Error,
+    },
+
+    #[error("[{section}]: `{key}` is not a table")]
+    NotATable { section: String, key: String },
+
+    #[error("[{section}]: missing `{key}`")]
+    Missing { section: String, key: &'static str },
+
+    #[error("[{section}]: missing `{key}`, and no `memory_budget` to derive it from")]
+    Unplanned { section: String, key: &'static str },
+
+    #[error("[{section}]: {source}")]
+    Plan { section: String, source: PlanError },
+
+    #[error("[{section}]: invalid `{key}`: {reason}")]
+    Invalid {
+        section: String,
+        key: &'static str,
+        reason: String,
+    },
+}
+
+/// Settings for one run, with every value present and checked for consistency.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunConfig {
+    /// The section the settings were resolved from, e.g. `3x3-U-r.ur.htm`. Output files of the run
+    /// are named after it.
+    pub name: String,
+    pub threads: usize,
+    pub chunk_size_bytes: usize,
+    pub update_memory: usize,
+    pub num_update_blocks: usize,
+    pub capacity_check_frequency: usize,
+    /// A subdirectory named after the run in each of the root directories given in the file.
+    pub root_directories: Vec<PathBuf>,
+    /// Root directory index of each chunk, repeated cyclically over the chunk indices.
+    pub chunk_root_pattern: Vec<usize>,
+    pub initial_memory_limit: usize,
+    pub available_disk_space_limit: u64,
+    pub update_array_threshold: u64,
+    pub use_locked_io: bool,
+    pub sync_filesystem: bool,
+    pub compute_checksums: bool,
+    pub use_compression: bool,
+    /// Where transposition tables are saved and loaded from. If unset, tables are rebuilt in
+    /// memory on every run.
+    pub table_directory: Option<PathBuf>,
+    pub table_mode: TableMode,
+    /// Where the distance distribution is written at the end of the run.
+    pub report_directory: PathBuf,
+    /// Where the depth table of the run is written, and read from to find solutions. If unset, no
+    /// depth table is written.
+    pub depth_table_directory: Option<PathBuf>,
+    /// The states the search starts from, which are the solved state unless set otherwise.
+    pub goal: Goal,
+}
+
+impl RunConfig {
+    pub fn chunk_root_idx(&self, chunk_idx: usize) -> usize {
+        self.chunk_root_pattern[chunk_idx % self.chunk_root_pattern.len()]
+    }
+
+    /// The number of chunks that a search of `state_size` states is split into.
+    pub fn num_chunks(&self, state_size: u64) -> usize {
+        state_size
+            .div_ceil(8)
+            .div_ceil(self.chunk_size_bytes as u64) as usize
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConfigFile {
+    table: toml::Table,
+}
+
+impl ConfigFile {
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let contents = fs::read_to_string(path).map_err(|source| ConfigError::Io {
+            path: path.to_owned(),
+            source,
+        })?;
+        contents.parse()
+    }
+
+    /// Merges the tables along `section`, starting from the top level, and checks the result for a
+    /// state space of `state_size` states. Tables that are missing from the file are skipped.
+    pub fn resolve(&self, section: &[&str], state_size: u64) -> Result<RunConfig, ConfigError> {
+        let mut table = &self.table;
+        let mut settings = PartialRunConfig::from_table(table, "")?;
+
+        for (i, &key) in section.iter().enumerate() {
+            let name = section[..=i].join(".");
+            match table.get(key) {
+                Some(toml::Value::Table(t)) => {
+                    table = t;
+                    settings = settings.merge(PartialRunConfig::from_table(table, &name)?);
+                }
+                Some(_) => {
+                    return Err(ConfigError::NotATable {
+                        section: section[..i].join("."),
+                        key: key.to_owned(),
+                    });
+                }
+                None => break,
+            }
+        }
+
+        settings.validate(&section.join("."), state_size)
+    }
+}
+
+impl FromStr for ConfigFile {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self { table: s.parse()? })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum ByteSize {
+    Bytes(u64),
+    Text(String),
+}
+
+impl ByteSize {
+    fn bytes(&self) -> Result<u64, String> {
+        let s = match self {
+            Self::Bytes(b) => return Ok(*b),
+            Self::Text(s) => s.trim(),
+        };
+
+        let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (value, unit) = s.split_at(split);
+        let value = value
+            .parse::<u64>()
+            .map_err(|_| format!("expected a size such as \"64 GiB\", got {s:?}"))?;
+        let multiplier = match unit.trim() {
+            "" | "B" => 1,
+            "KiB" => 1 << 10,
+            "MiB" => 1 << 20,
+            "GiB" => 1 << 30,
+            "TiB" => 1 << 40,
+            unit => return Err(format!("unknown unit {unit:?}")),
+        };
+
+        value
+            .checked_mul(multiplier)
+            .ok_or_else(|| format!("{s:?} is too large"))
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PartialRunConfig {
+    threads: Option<usize>,
+    chunk_size_bytes: Option<ByteSize>,
+    update_memory: Option<ByteSize>,
+    num_update_blocks: Option<usize>,
+    capacity_check_frequency: Option<usize>,
+    root_directories: Option<Vec<PathBuf>>,
+    chunk_root_pattern: Option<Vec<usize>>,
+    initial_memory_limit: Option<ByteSize>,
+    available_disk_space_limit: Option<ByteSize>,
+    update_array_threshold: Option<u64>,
+    memory_budget: Option<ByteSize>,
+    use_locked_io: Option<bool>,
+    sync_filesystem: Option<bool>,
+    compute_checksums: Option<bool>,
+    use_compression: Option<bool>,
+    table_directory: Option<PathBuf>,
+    table_mode: Option<TableMode>,
+    report_directory: Option<PathBuf>,
+    depth_table_directory: Option<PathBuf>,
+}
+
+macro_rules! merge_fields {
+    ($base:expr, $other:expr, $($field:ident),* $(,)?) => {
+        PartialRunConfig {
+            $($field: $other.$field.or($base.$field),)*
+        }
+    };
+}
+
+impl PartialRunConfig {
+    /// Reads the settings stored directly in `table`, ignoring nested tables.
+    fn from_table(table: &toml::Table, section: &str) -> Result<Self, ConfigError> {
+        let settings = table
+            .iter()
+            .filter(|(_, v)| !v.is_table())
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect::<toml::Table>();
+
+        settings.try_into().map_err(|source| ConfigError::Section {
+            section: section.to_owned(),
+            source,
+        })
+    }
+
+    /// Returns `self` with any values set in `other` replaced.
+    fn merge(self, other: Self) -> Self {
+        merge_fields!(
+            self,
+            other,
+            threads,
+            chunk_size_bytes,
+            update_memory,
+            num_update_blocks,
+            capacity_check_frequency,
+            root_directories,
+            chunk_root_pattern,
+            initial_memory_limit,
+            available_disk_space_limit,
+            update_array_threshold,
+            memory_budget,
+            use_locked_io,
+            sync_filesystem,
+            compute_checksums,
+            use_compression,
+            table_directory,
+            table_mode,
+            report_directory,
+            depth_table_directory,
+        )
+    }
+
+    fn validate(self, section: &str, state_size: u64) -> Result<RunConfig, ConfigError> {
+        let missing = |key| ConfigError::Missing {
+            section: section.to_owned(),
+            key,
+        };
+        let invalid = |key, reason: String| ConfigError::Invalid {
+            section: section.to_owned(),
+            key,
+            reason,
+        };
+        let bytes = |key, size: Option<ByteSize>| {
+            size.ok_or_else(|| missing(key))?
+                .bytes()
+                .map_err(|reason| invalid(key, reason))
+        };
+
+        let threads = self.threads.ok_or_else(|| missing("threads"))?;
+        let update_memory = bytes("update_memory", self.update_memory)? as usize;
+        let capacity_check_frequency = self
+            .capacity_check_frequency
+            .ok_or_else(|| missing("capacity_check_frequency"))?;
+        let root_directories = self
+            .root_directories
+            .ok_or_else(|| missing("root_directories"))?;
+        let initial_memory_limit =
+            bytes("initial_memory_limit", self.initial_memory_limit)? as usize;
+        let available_disk_space_limit = bytes(
+            "available_disk_space_limit",
+            self.available_disk_space_limit,
+        )?;
+        let use_locked_io = self.use_locked_io.ok_or_else(|| missing("use_locked_io"))?;
+        let sync_filesystem = self
+            .sync_filesystem
+            .ok_or_else(|| missing("sync_filesystem"))?;
+        let compute_checksums = self
+            .compute_checksums
+            .ok_or_else(|| missing("compute_checksums"))?;
+        let use_compression = self
+            .use_compression
+            .ok_or_else(|| missing("use_compression"))?;
+
+        if threads == 0 {
+            return Err(invalid("threads", "must be at least 1".to_owned()));
+        }
+        if root_directories.is_empty() {
+            return Err(invalid("root_directories", "must not be empty".to_owned()));
+        }
+        if root_directories.iter().collect::<HashSet<_>>().len() != root_directories.len() {
+            return Err(invalid(
+                "root_directories",
+                "contains duplicate directories".to_owned(),
+            ));
+        }
+
+        let root_directories = root_directories
+            .into_iter()
+            .map(|directory| directory.join(section))
+            .collect::<Vec<_>>();
+
+        let chunk_root_pattern = self
+            .chunk_root_pattern
+            .unwrap_or_else(|| (0..root_directories.len()).collect());
+        if chunk_root_pattern.is_empty() {
+            return Err(invalid(
+                "chunk_root_pattern",
+                "must not be empty".to_owned(),
+            ));
+        }
+        if let Some(&idx) = chunk_root_pattern
+            .iter()
+            .find(|&&idx| idx >= root_directories.len())
+        {
+            return Err(invalid(
+                "chunk_root_pattern",
+                format!(
+                    "index {idx} is out of range for {} root directories",
+                    root_directories.len()
+                ),
+            ));
+        }
+
+        let chunk_size_bytes = self
+            .chunk_size_bytes
+            .map(|size| {
+                size.bytes()
+                    .map_err(|reason| invalid("chunk_size_bytes", reason))
+            })
+            .transpose()?
+            .map(|size| size as usize);
+        let plan = if chunk_size_bytes.is_none()
+            || self.num_update_blocks.is_none()
+            || self.update_array_threshold.is_none()
+        {
+            let unplanned = |key| ConfigError::Unplanned {
+                section: section.to_owned(),
+                key,
+            };
+            let memory_budget = self.memory_budget.ok_or_else(|| {
+                unplanned(if chunk_size_bytes.is_none() {
+                    "chunk_size_bytes"
+                } else if self.num_update_blocks.is_none() {
+                    "num_update_blocks"
+                } else {
+                    "update_array_threshold"
+                })
+            })?;
+            let memory_budget = memory_budget
+                .bytes()
+                .map_err(|reason| invalid("memory_budget", reason))?;
+
+            let planner = Planner {
+                state_size,
+                threads,
+                memory_budget: memory_budget as usize,
+                update_memory,
+                disks: chunk_root_pattern.len(),
+            };
+            let plan = planner.plan().map_err(|source| ConfigError::Plan {
+                section: section.to_owned(),
+                source,
+            })?;
+            Some(plan)
+        } else {
+            None
+        };
+
+        let chunk_size_bytes = chunk_size_bytes.unwrap_or_else(|| plan.unwrap().chunk_size_bytes);
+        let num_update_blocks = self
+            .num_update_blocks
+            .unwrap_or_else(|| plan.unwrap().num_update_blocks);
+        let update_array_threshold = self
+            .update_array_threshold
+            .unwrap_or_else(|| plan.unwrap().update_array_threshold);
+
+        if chunk_size_bytes == 0 {
+            return Err(invalid("chunk_size_bytes", "must be non-zero".to_owned()));
+        }
+        if num_update_blocks == 0 || num_update_blocks > update_memory {
+            return Err(invalid(
+                "num_update_blocks",
+                format!("must be between 1 and update_memory ({update_memory})"),
+            ));
+        }
+
+        Ok(RunConfig {
+            name: section.to_owned(),
+            threads,
+            chunk_size_bytes,
+            update_memory,
+            num_update_blocks,
+            capacity_check_frequency,
+            root_directories,
+            chunk_root_pattern,
+            initial_memory_limit,
+            available_disk_space_limit,
+            update_array_threshold,
+            use_locked_io,
+            sync_filesystem,
+            compute_checksums,
+            use_compression,
+            table_directory: self.table_directory,
+            table_mode: self.table_mode.unwrap_or_default(),
+            report_directory: self
+                .report_directory
+                .unwrap_or_else(|| PathBuf::from("reports")),
+            depth_table_directory: self.depth_table_directory,
+            goal: Goal::Solved,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATE_SIZE: u64 = 5417769369600;
+
+    const CONFIG: &str = r#"
+        threads = 48
+        update_memory = "112 GiB"
+        num_update_blocks = 122880
+        capacity_check_frequency = 256
+        initial_memory_limit = 17179869184
+        available_disk_space_limit = "256 GiB"
+        use_locked_io = false
+        sync_filesystem = true
+        compute_checksums = true
+        use_compression = true
+
+        [3x3-U-r]
+        root_directories = ["/a", "/b", "/c", "/d"]
+        chunk_root_pattern = [0, 1, 2, 3, 1, 2, 3]
+        chunk_size_bytes = 529079040
+        update_array_threshold = 529079040
+
+        [3x3-U-r.ur.htm]
+        threads = 16
+        table_mode = "compact"
+    "#;
+
+    #[test]
+    fn test_resolve_layers() {
+        let file = CONFIG.parse::<ConfigFile>().unwrap();
+
+        let config = file.resolve(&["3x3-U-r", "ur", "qtm"], STATE_SIZE).unwrap();
+        assert_eq!(config.threads, 48);
+        assert_eq!(config.update_memory, 112 << 30);
+        assert_eq!(config.available_disk_space_limit, 256 << 30);
+        assert_eq!(
+            config.root_directories[1],
+            PathBuf::from("/b/3x3-U-r.ur.qtm")
+        );
+        assert_eq!(config.root_directories.len(), 4);
+        assert_eq!(config.chunk_root_idx(4), 1);
+        assert_eq!(config.chunk_root_idx(7), 0);
+
+        let config = file.resolve(&["3x3-U-r", "ur", "htm"], STATE_SIZE).unwrap();
+        assert_eq!(config.name, "3x3-U-r.ur.htm");
+        assert_eq!(config.threads, 16);
+        assert_eq!(config.table_mode, TableMode::Compact);
+        assert_eq!(
+            file.resolve(&["3x3-U-r", "ur", "qtm"], STATE_SIZE)
+                .unwrap()
+                .table_mode,
+            TableMode::Full
+        );
+    }
+
+    #[test]
+    fn test_missing_key() {
+        let file = CONFIG.parse::<ConfigFile>().unwrap();
+
+        assert!(matches!(
+            file.resolve(&["3x3-ep", "htm"], 479001600),
+            Err(ConfigError::Missing {
+                key: "root_directories",
+                ..
+            })
+        ));
+
+        let file = format!("{CONFIG}\n[3x3-ep]\nroot_directories = [\"/e\"]\n")
+            .parse::<ConfigFile>()
+            .unwrap();
+        assert!(matches!(
+            file.resolve(&["3x3-ep", "htm"], 479001600),
+            Err(ConfigError::Unplanned {
+                key: "chunk_size_bytes",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_inconsistent_values() {
+        let file = format!("{CONFIG}\n[3x3-U-r.urrw]\nchunk_root_pattern = [0, 4]\n")
+            .parse::<ConfigFile>()
+            .unwrap();
+        assert!(matches!(
+            file.resolve(&["3x3-U-r", "urrw", "htm"], STATE_SIZE),
+            Err(ConfigError::Invalid {
+                key: "chunk_root_pattern",
+                ..
+            })
+        ));
+
+        let file = format!("{CONFIG}\n[3x3-U-r.urrw]\nthreads = 0\n")
+            .parse::<ConfigFile>()
+            .unwrap();
+        assert!(matches!(
+            file.resolve(&["3x3-U-r", "urrw", "htm"], STATE_SIZE),
+            Err(ConfigError::Invalid { key: "threads", .. })
+        ));
+    }
+
+    #[test]
+    fn test_unknown_key() {
+        let file = format!("{CONFIG}\n[3x3-U-r.urrw]\nthread = 8\n")
+            .parse::<ConfigFile>()
+            .unwrap();
+        assert!(matches!(
+            file.resolve(&["3x3-U-r", "urrw", "htm"], STATE_SIZE),
+            Err(ConfigError::Section { .. })
+        ));
+    }
+
+    #[test]
+    fn test_planned_values() {
+        let file = format!(
+            "{CONFIG}\n[3x3-ep]\nroot_directories = [\"/e\"]\nmemory_budget = \"113 GiB\"\n"
+        )
+        .parse::<ConfigFile>()
+        .unwrap();
+
+        // 1 GiB for 48 chunks of at most 22369621 bytes, and `num_update_blocks` is still taken
+        // from the top level
+        let config = file.resolve(&["3x3-ep", "htm"], 479001600).unwrap();
+        assert_eq!(config.chunk_size_bytes, 59875200 / 48);
+        assert_eq!(config.num_update_blocks, 122880);
+        assert_eq!(
+            config.update_array_threshold,
+            config.chunk_size_bytes as u64
+        );
+    }
+
+    #[test]
+    fn test_byte_size() {
+        assert_eq!(ByteSize::Bytes(12).bytes(), Ok(12));
+        assert_eq!(ByteSize::Text("3 MiB".to_owned()).bytes(), Ok(3 << 20));
+        assert_eq!(ByteSize::Text("2TiB".to_owned()).bytes(), Ok(2 << 40));
+        assert!(ByteSize::Text("2 TB".to_owned()).bytes().is_err());
+        assert!(ByteSize::Text("GiB".to_owned()).bytes().is_err());
+    }
+}
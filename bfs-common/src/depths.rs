@@ -0,0 +1,341 @@
+//! A table of the depth of every state modulo 3, written during a search and memory-mapped
+//! afterwards to find optimal solutions.
+//!
+//! Two bits per state are enough to walk back to the initial states, as long as the inverse of
+//! every move is also a move: the neighbours of a state at depth `d` are then at depths `d - 1`,
+//! `d` and `d + 1`, which are all different modulo 3. When the moves only turn one way, as in UTM,
+//! a state's neighbours can be at any smaller depth, and there may be no move back towards the
+//! initial states at all, so such a table can be written but not walked.
+//!
+//! Each entry stores `depth % 3 + 1`, so that an entry of 0 means the state wasn't reached, and a
+//! table can be filled in with bitwise ORs starting from an all-zero (sparse) file.
+
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use disk_based_bfs::expander::BfsExpander;
+use memmap2::{Mmap, MmapMut};
+
+use crate::tables::TableFileError;
+
+const MAGIC: [u8; 8] = *b"BFSDEPTH";
+
+/// Version of the file layout. Bump this whenever the layout changes.
+const VERSION: u32 = 1;
+
+/// Magic, version, padding and state size.
+const HEADER_LEN: usize = 24;
+
+/// The path of the depth table of the run `name` in `directory`.
+pub fn path(directory: &Path, name: &str) -> PathBuf {
+    directory.join(format!("{name}.depths"))
+}
+
+fn header(state_size: u64) -> [u8; HEADER_LEN] {
+    let mut header = [0; HEADER_LEN];
+    header[..8].copy_from_slice(&MAGIC);
+    header[8..12].copy_from_slice(&VERSION.to_le_bytes());
+    header[16..24].copy_from_slice(&state_size.to_le_bytes());
+    header
+}
+
+fn data_len(state_size: u64) -> usize {
+    state_size.div_ceil(4) as usize
+}
+
+/// A depth table that is being filled in by a search. It is written to a temporary file, which
+/// only replaces the table at `path` once [`DepthTableWriter::finish`] is called.
+pub struct DepthTableWriter {
+    mmap: MmapMut,
+    data: *mut u8,
+    state_size: u64,
+    path: PathBuf,
+    tmp_path: PathBuf,
+}
+
+// SAFETY: `data` points into `mmap`, which is owned by the writer, and is only written to with
+// atomic operations.
+unsafe impl Send for DepthTableWriter {}
+unsafe impl Sync for DepthTableWriter {}
+
+impl DepthTableWriter {
+    /// The temporary file that the depth table at `path` is written to.
+    pub fn tmp_path(path: &Path) -> PathBuf {
+        path.with_extension("depths.tmp")
+    }
+
+    pub fn create(path: &Path, state_size: u64) -> Result<Self, TableFileError> {
+        Self::open(path, state_size, false)
+    }
+
+    /// Carries on writing the temporary file of an interrupted search, keeping the states it
+    /// already has. A search that was stopped early finished its table, which is moved back to the
+    /// temporary file until the search is finished again.
+    pub fn resume(path: &Path, state_size: u64) -> Result<Self, TableFileError> {
+        let tmp_path = Self::tmp_path(path);
+        if !tmp_path.exists() && path.exists() {
+            fs::rename(path, &tmp_path).map_err(|source| TableFileError::Io {
+                path: path.to_owned(),
+                source,
+            })?;
+        }
+        Self::open(path, state_size, true)
+    }
+
+    fn open(path: &Path, state_size: u64, resume: bool) -> Result<Self, TableFileError> {
+        let tmp_path = Self::tmp_path(path);
+        let io_err = |source| TableFileError::Io {
+            path: tmp_path.clone(),
+            source,
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|source| TableFileError::Io {
+                path: parent.to_owned(),
+                source,
+            })?;
+        }
+
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create(!resume)
+            .truncate(!resume)
+            .open(&tmp_path)
+            .map_err(io_err)?;
+        if resume {
+            // Checks the header and length
+            DepthTable::open(&tmp_path, state_size)?;
+        } else {
+            file.set_len((HEADER_LEN + data_len(state_size)) as u64)
+                .map_err(io_err)?;
+        }
+
+        // SAFETY: the temporary file is only used by this writer.
+        let mut mmap = unsafe { MmapMut::map_mut(&file) }.map_err(io_err)?;
+        mmap[..HEADER_LEN].copy_from_slice(&header(state_size));
+        let data = mmap[HEADER_LEN..].as_mut_ptr();
+
+        Ok(Self {
+            mmap,
+            data,
+            state_size,
+            path: path.to_owned(),
+            tmp_path,
+        })
+    }
+
+    /// Flushes the states recorded so far to the temporary file.
+    pub fn flush(&self) -> Result<(), TableFileError> {
+        self.mmap.flush().map_err(|source| TableFileError::Io {
+            path: self.tmp_path.clone(),
+            source,
+        })
+    }
+
+    /// Records that `state` is at `depth`. Each state should only be recorded once.
+    pub fn set(&self, state: u64, depth: usize) {
+        assert!(state < self.state_size);
+        let value = (depth % 3 + 1) as u8;
+        let shift = (state % 4) * 2;
+        // SAFETY: the byte is in bounds, and is only accessed atomically while the writer exists.
+        let byte = unsafe { AtomicU8::from_ptr(self.data.add((state / 4) as usize)) };
+        byte.fetch_or(value << shift, Ordering::Relaxed);
+    }
+
+    /// Flushes the table to disk and moves it into place.
+    pub fn finish(self) -> Result<DepthTable, TableFileError> {
+        self.flush()?;
+        drop(self.mmap);
+
+        fs::rename(&self.tmp_path, &self.path).map_err(|source| TableFileError::Io {
+            path: self.path.clone(),
+            source,
+        })?;
+        DepthTable::open(&self.path, self.state_size)
+    }
+}
+
+/// A finished depth table, mapped from a file.
+pub struct DepthTable {
+    mmap: Mmap,
+    state_size: u64,
+}
+
+impl DepthTable {
+    pub fn open(path: &Path, state_size: u64) -> Result<Self, TableFileError> {
+        let io_err = |source| TableFileError::Io {
+            path: path.to_owned(),
+            source,
+        };
+
+        let file = File::open(path).map_err(io_err)?;
+        // SAFETY: depth tables are only ever replaced by renaming a new file over them, never
+        // modified in place.
+        let mmap = unsafe { Mmap::map(&file) }.map_err(io_err)?;
+
+        let header = mmap.get(..HEADER_LEN).ok_or(TableFileError::Truncated)?;
+        if header[..8] != MAGIC {
+            return Err(TableFileError::Magic);
+        }
+        let version = u32::from_le_bytes(header[8..12].try_into().unwrap());
+        if version != VERSION {
+            return Err(TableFileError::Version { found: version });
+        }
+        let found_size = u64::from_le_bytes(header[16..24].try_into().unwrap());
+        if found_size != state_size {
+            return Err(TableFileError::Mismatch {
+                what: "state size",
+                found: found_size.to_string(),
+                expected: state_size.to_string(),
+            });
+        }
+        if mmap.len() < HEADER_LEN + data_len(state_size) {
+            return Err(TableFileError::Truncated);
+        }
+
+        Ok(Self { mmap, state_size })
+    }
+
+    pub fn state_size(&self) -> u64 {
+        self.state_size
+    }
+
+    /// The packed entries, four states to a byte.
+    pub(crate) fn data(&self) -> &[u8] {
+        &self.mmap[HEADER_LEN..HEADER_LEN + data_len(self.state_size)]
+    }
+
+    /// The depth of `state` modulo 3, or `None` if it wasn't reached.
+    pub fn get(&self, state: u64) -> Option<usize> {
+        if state >= self.state_size {
+            return None;
+        }
+        let byte = self.mmap[HEADER_LEN + (state / 4) as usize];
+        let value = (byte >> ((state % 4) * 2)) & 0b11;
+        (value != 0).then(|| value as usize - 1)
+    }
+
+    /// The states on a shortest path from `state` to a state where `is_goal` is true, not
+    /// including `state` itself. Neighbours are generated by `expander`, which must be the one
+    /// used for the search, and have the inverse of every move among its moves. Returns `None` if
+    /// `state` wasn't reached.
+    pub fn path<E, const N: usize>(
+        &self,
+        expander: &mut E,
+        mut state: u64,
+        is_goal: impl Fn(u64) -> bool,
+    ) -> Option<Vec<u64>>
+    where
+        E: BfsExpander<N>,
+    {
+        let mut depth = self.get(state)?;
+        let mut path = Vec::new();
+        let mut neighbours = [0; N];
+
+        while !is_goal(state) {
+            expander.expand(state, &mut neighbours);
+            let closer = (depth + 2) % 3;
+            state = neighbours
+                .into_iter()
+                .find(|&neighbour| self.get(neighbour) == Some(closer))?;
+            depth = closer;
+            path.push(state);
+        }
+
+        Some(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    /// Adding or subtracting 1 modulo 10.
+    struct Expander;
+
+    impl BfsExpander<2> for Expander {
+        fn expand(&mut self, node: u64, expanded_nodes: &mut [u64; 2]) {
+            *expanded_nodes = [(node + 1) % 10, (node + 9) % 10];
+        }
+    }
+
+    #[test]
+    fn test_write_and_walk() {
+        let dir = temp_dir("depths-walk");
+        let path = path(&dir, "cycle");
+
+        // States 10 and 11 are never reached
+        let writer = DepthTableWriter::create(&path, 12).unwrap();
+        for state in 0..10 {
+            writer.set(state, state.min(10 - state) as usize);
+        }
+        assert!(!path.exists());
+        let table = writer.finish().unwrap();
+
+        assert_eq!(table.get(4), Some(1));
+        assert_eq!(table.get(6), Some(1));
+        assert_eq!(table.get(10), None);
+        assert_eq!(table.get(12), None);
+
+        assert_eq!(
+            table.path(&mut Expander, 6, |state| state == 0),
+            Some(vec![7, 8, 9, 0])
+        );
+        assert_eq!(
+            table.path(&mut Expander, 0, |state| state == 0),
+            Some(vec![])
+        );
+        assert_eq!(table.path(&mut Expander, 11, |state| state == 0), None);
+
+        assert!(matches!(
+            DepthTable::open(&path, 11),
+            Err(TableFileError::Mismatch { .. })
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_resume() {
+        let dir = temp_dir("depths-resume");
+        let path = path(&dir, "cycle");
+        assert!(DepthTableWriter::resume(&path, 10).is_err());
+
+        let writer = DepthTableWriter::create(&path, 10).unwrap();
+        for state in 0..3 {
+            writer.set(state, state as usize);
+        }
+        writer.flush().unwrap();
+        drop(writer);
+
+        assert!(matches!(
+            DepthTableWriter::resume(&path, 11),
+            Err(TableFileError::Mismatch { .. })
+        ));
+        let writer = DepthTableWriter::resume(&path, 10).unwrap();
+        for state in 3..10 {
+            writer.set(state, state.min(10 - state) as usize);
+        }
+        let table = writer.finish().unwrap();
+        assert!(!DepthTableWriter::tmp_path(&path).exists());
+
+        assert_eq!(
+            table.path(&mut Expander, 4, |state| state == 0),
+            Some(vec![3, 2, 1, 0])
+        );
+        drop(table);
+
+        // A finished table is carried on with too
+        let writer = DepthTableWriter::resume(&path, 10).unwrap();
+        assert!(!path.exists());
+        let table = writer.finish().unwrap();
+        assert_eq!(table.get(4), Some(1));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
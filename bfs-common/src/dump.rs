@@ -0,0 +1,430 @@
+//! Writes every state that a search finds at some depths to files that other programs can read,
+//! one row per state, with the coordinate, the coordinates it is made of and the pieces of the
+//! decoded state.
+//!
+//! Each depth goes to a file of its own, e.g. `3x3-U-r.ur.htm.depth-12.csv`, so a search that is
+//! resumed from a checkpoint only rewrites the depths it hasn't finished. The callback of each
+//! thread collects rows in a [`DumpWriter`] of its own, and only takes the lock on the files to
+//! hand over a full buffer.
+
+use std::{
+    collections::{btree_map::Entry, BTreeMap},
+    fmt,
+    fs::{self, File},
+    io::{self, BufWriter, Write as _},
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use crate::puzzle::Puzzle;
+
+/// The size a [`DumpWriter`]'s buffer grows to before it is written to the file.
+const BUFFER_LEN: usize = 1 << 20;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DumpFormat {
+    /// Comma-separated values with a header row. Pieces are written as space-separated numbers.
+    #[default]
+    Csv,
+
+    /// One JSON object per line.
+    Jsonl,
+}
+
+impl FromStr for DumpFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Self::Csv),
+            "jsonl" => Ok(Self::Jsonl),
+            _ => Err(format!("expected `csv` or `jsonl`, got {s:?}")),
+        }
+    }
+}
+
+impl fmt::Display for DumpFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Csv => write!(f, "csv"),
+            Self::Jsonl => write!(f, "jsonl"),
+        }
+    }
+}
+
+/// The depths whose states are written out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DumpDepths(pub RangeInclusive<usize>);
+
+impl FromStr for DumpDepths {
+    type Err = String;
+
+    /// Parses a single depth, e.g. `12`, or a range of depths, e.g. `10-12`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let depth = |s: &str| {
+            s.trim()
+                .parse::<usize>()
+                .map_err(|_| format!("expected a depth or a range such as `10-12`, got {s:?}"))
+        };
+        let (first, last) = match s.split_once('-') {
+            Some((first, last)) => (depth(first)?, depth(last)?),
+            None => (depth(s)?, depth(s)?),
+        };
+        if first > last {
+            return Err(format!("the range {s:?} is empty"));
+        }
+        Ok(Self(first..=last))
+    }
+}
+
+/// Which states a search writes out, and how.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dump {
+    pub depths: DumpDepths,
+    pub format: DumpFormat,
+}
+
+/// The path of the file of the states at `depth` of the run `name` in `directory`.
+pub fn path(directory: &Path, name: &str, depth: usize, format: DumpFormat) -> PathBuf {
+    directory.join(format!("{name}.depth-{depth}.{format}"))
+}
+
+/// The files of the states at each depth, which every [`DumpWriter`] of a search writes to.
+pub struct DumpFiles {
+    directory: PathBuf,
+    name: String,
+    format: DumpFormat,
+    /// The first line of each CSV file.
+    header: String,
+    /// The open files, and the number of rows written to each.
+    files: Mutex<BTreeMap<usize, (BufWriter<File>, u64)>>,
+    /// The first error while writing, after which nothing more is written.
+    error: Mutex<Option<io::Error>>,
+}
+
+impl DumpFiles {
+    /// Files for the run `name` in `directory`, which are created when the first state of their
+    /// depth is written. `reduced` adds a column with the state that each class is decoded from.
+    pub fn new<P: Puzzle>(directory: &Path, name: &str, format: DumpFormat, reduced: bool) -> Self {
+        let solved = P::solved();
+        let columns = ["depth", "coord"]
+            .into_iter()
+            .chain(reduced.then_some("state"))
+            .chain(solved.sub_coords().into_iter().map(|(name, _)| name))
+            .chain(solved.pieces().into_iter().map(|(name, _)| name))
+            .collect::<Vec<_>>();
+        Self {
+            directory: directory.to_owned(),
+            name: name.to_owned(),
+            format,
+            header: columns.join(","),
+            files: Mutex::new(BTreeMap::new()),
+            error: Mutex::new(None),
+        }
+    }
+
+    /// Appends `rows` rows at `depth`, creating the file of the depth if this is the first of them.
+    fn write(&self, depth: usize, rows: u64, bytes: &[u8]) {
+        let mut error = self.error.lock().unwrap();
+        if error.is_some() {
+            return;
+        }
+
+        let mut files = self.files.lock().unwrap();
+        let result = (|| {
+            let (file, count) = match files.entry(depth) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    fs::create_dir_all(&self.directory)?;
+                    let path = path(&self.directory, &self.name, depth, self.format);
+                    tracing::info!("writing the states at depth {depth} to {}", path.display());
+                    let mut file = BufWriter::new(File::create(path)?);
+                    if self.format == DumpFormat::Csv {
+                        writeln!(file, "{}", self.header)?;
+                    }
+                    entry.insert((file, 0))
+                }
+            };
+            file.write_all(bytes)?;
+            *count += rows;
+            Ok(())
+        })();
+
+        if let Err(err) = result {
+            tracing::error!("failed to write the states at depth {depth}: {err}");
+            *error = Some(err);
+        }
+    }
+
+    /// Flushes every file, once the writers are done with them, and returns the path and the
+    /// number of rows of each.
+    pub fn finish(self) -> io::Result<Vec<(PathBuf, u64)>> {
+        if let Some(err) = self.error.into_inner().unwrap() {
+            return Err(err);
+        }
+
+        self.files
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|(depth, (mut file, rows))| {
+                file.flush()?;
+                Ok((path(&self.directory, &self.name, depth, self.format), rows))
+            })
+            .collect()
+    }
+}
+
+/// Collects rows for one thread of a search. Each clone has a buffer of its own, which starts
+/// empty, and the buffer is written to the files when it is full, when the depth changes, and
+/// when the writer is dropped.
+pub struct DumpWriter {
+    files: Arc<DumpFiles>,
+    depths: RangeInclusive<usize>,
+    reduced: bool,
+    row: fn(&mut Vec<u8>, DumpFormat, bool, usize, u64, u64),
+    buffer: Vec<u8>,
+    /// The depth of the rows in the buffer, and how many there are.
+    buffered: Option<(usize, u64)>,
+}
+
+impl DumpWriter {
+    pub fn new<P: Puzzle>(
+        files: Arc<DumpFiles>,
+        depths: RangeInclusive<usize>,
+        reduced: bool,
+    ) -> Self {
+        Self {
+            files,
+            depths,
+            reduced,
+            row: row::<P>,
+            buffer: Vec::new(),
+            buffered: None,
+        }
+    }
+
+    /// Whether the states at `depth` are written out.
+    pub fn wants(&self, depth: usize) -> bool {
+        self.depths.contains(&depth)
+    }
+
+    /// Writes a row for `coord`, decoded from `state`. That is `coord` itself, unless the search
+    /// is reduced by symmetry and `coord` is a class, when it is a state in the class.
+    pub fn add(&mut self, depth: usize, coord: u64, state: u64) {
+        if self.buffered.is_some_and(|(buffered, _)| buffered != depth) {
+            self.flush();
+        }
+
+        (self.row)(
+            &mut self.buffer,
+            self.files.format,
+            self.reduced,
+            depth,
+            coord,
+            state,
+        );
+        let (_, rows) = self.buffered.get_or_insert((depth, 0));
+        *rows += 1;
+
+        if self.buffer.len() >= BUFFER_LEN {
+            self.flush();
+        }
+    }
+
+    fn flush(&mut self) {
+        if let Some((depth, rows)) = self.buffered.take() {
+            self.files.write(depth, rows, &self.buffer);
+            self.buffer.clear();
+        }
+    }
+}
+
+impl Clone for DumpWriter {
+    fn clone(&self) -> Self {
+        Self {
+            files: Arc::clone(&self.files),
+            depths: self.depths.clone(),
+            reduced: self.reduced,
+            row: self.row,
+            buffer: Vec::new(),
+            buffered: None,
+        }
+    }
+}
+
+impl Drop for DumpWriter {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// Appends the row of `coord` to `buffer`.
+fn row<P: Puzzle>(
+    buffer: &mut Vec<u8>,
+    format: DumpFormat,
+    reduced: bool,
+    depth: usize,
+    coord: u64,
+    state: u64,
+) {
+    let puzzle = P::from_coord(state);
+    let sub_coords = puzzle.sub_coords();
+    let pieces = puzzle.pieces();
+
+    // Writing to a `Vec` can't fail
+    match format {
+        DumpFormat::Csv => {
+            write!(buffer, "{depth},{coord}").unwrap();
+            if reduced {
+                write!(buffer, ",{state}").unwrap();
+            }
+            for (_, value) in &sub_coords {
+                write!(buffer, ",{value}").unwrap();
+            }
+            for (_, pieces) in &pieces {
+                let pieces = pieces.iter().map(u8::to_string).collect::<Vec<_>>();
+                write!(buffer, ",{}", pieces.join(" ")).unwrap();
+            }
+        }
+        DumpFormat::Jsonl => {
+            write!(buffer, r#"{{"depth":{depth},"coord":{coord}"#).unwrap();
+            if reduced {
+                write!(buffer, r#","state":{state}"#).unwrap();
+            }
+            for (name, value) in &sub_coords {
+                write!(buffer, r#","{name}":{value}"#).unwrap();
+            }
+            for (name, pieces) in &pieces {
+                let pieces = pieces.iter().map(u8::to_string).collect::<Vec<_>>();
+                write!(buffer, r#","{name}":[{}]"#, pieces.join(",")).unwrap();
+            }
+            write!(buffer, "}}").unwrap();
+        }
+    }
+    buffer.push(b'\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    /// A cycle of length 10, with its coordinate split into tens and units.
+    #[derive(Debug)]
+    struct Cycle(u8);
+
+    impl Puzzle for Cycle {
+        const ID: &'static str = "cycle";
+        const STATE_SIZE: u64 = 10;
+        const MOVES: &'static [&'static str] = &["A"];
+        const ORDERS: &'static [usize] = &[10];
+
+        fn solved() -> Self {
+            Self(0)
+        }
+
+        fn encode(&self) -> u64 {
+            self.0 as u64
+        }
+
+        fn decode(&mut self, coord: u64) {
+            self.0 = coord as u8;
+        }
+
+        fn do_move(&mut self, _: usize) {
+            self.0 = (self.0 + 1) % 10;
+        }
+
+        fn sub_coords(&self) -> Vec<(&'static str, u64)> {
+            vec![("half", self.0 as u64 / 5), ("rest", self.0 as u64 % 5)]
+        }
+
+        fn pieces(&self) -> Vec<(&'static str, Vec<u8>)> {
+            vec![("pieces", (0..3).map(|i| (self.0 + i) % 10).collect())]
+        }
+    }
+
+    #[test]
+    fn test_depths() {
+        assert_eq!("12".parse(), Ok(DumpDepths(12..=12)));
+        assert_eq!("10-12".parse(), Ok(DumpDepths(10..=12)));
+        assert!("12-10".parse::<DumpDepths>().is_err());
+        assert!("ten".parse::<DumpDepths>().is_err());
+        assert_eq!("jsonl".parse(), Ok(DumpFormat::Jsonl));
+    }
+
+    #[test]
+    fn test_csv() {
+        let dir = temp_dir("dump-csv");
+        let files = Arc::new(DumpFiles::new::<Cycle>(
+            &dir,
+            "cycle",
+            DumpFormat::Csv,
+            false,
+        ));
+        let writer = DumpWriter::new::<Cycle>(Arc::clone(&files), 1..=2, false);
+        assert!(!writer.wants(0) && writer.wants(2));
+
+        // Two threads, one of which is done with depth 1 before the other
+        let mut first = writer.clone();
+        let mut second = writer.clone();
+        first.add(1, 1, 1);
+        second.add(1, 9, 9);
+        first.add(2, 8, 8);
+        second.add(2, 2, 2);
+        drop((writer, first, second));
+
+        let written = Arc::into_inner(files).unwrap().finish().unwrap();
+        assert_eq!(
+            written,
+            [
+                (path(&dir, "cycle", 1, DumpFormat::Csv), 2),
+                (path(&dir, "cycle", 2, DumpFormat::Csv), 2),
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(&written[0].0).unwrap(),
+            "depth,coord,half,rest,pieces\n1,1,0,1,1 2 3\n1,9,1,4,9 0 1\n"
+        );
+        let depth_2 = fs::read_to_string(&written[1].0).unwrap();
+        let mut rows = depth_2.lines().skip(1).collect::<Vec<_>>();
+        rows.sort();
+        assert_eq!(rows, ["2,2,0,2,2 3 4", "2,8,1,3,8 9 0"]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_jsonl() {
+        let dir = temp_dir("dump-jsonl");
+        let files = Arc::new(DumpFiles::new::<Cycle>(
+            &dir,
+            "cycle",
+            DumpFormat::Jsonl,
+            true,
+        ));
+        let mut writer = DumpWriter::new::<Cycle>(Arc::clone(&files), 3..=3, true);
+        writer.add(3, 4, 7);
+        drop(writer);
+
+        let written = Arc::into_inner(files).unwrap().finish().unwrap();
+        let json = fs::read_to_string(&written[0].0).unwrap();
+        let row = serde_json::from_str::<serde_json::Value>(&json).unwrap();
+        assert_eq!(
+            row,
+            serde_json::json!({
+                "depth": 3,
+                "coord": 4,
+                "state": 7,
+                "half": 1,
+                "rest": 2,
+                "pieces": [7, 8, 9],
+            })
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,441 @@
+//! Every optimal and near-optimal move sequence that solves a case, found with the depth table.
+//!
+//! The depth table only stores depths modulo 3, but the exact depth of a neighbour follows from
+//! the exact depth of a state, so once the depth of the case is known, the depth of every state
+//! along a sequence is too. A depth-first search through the moves of the expander (and so its
+//! transposition tables) then only follows moves that can still reach a goal state in time. Like
+//! [`DepthTable::path`], this needs the inverse of every move to be a move.
+//!
+//! Only sequences that can't obviously be shortened are listed: they don't pass through a goal
+//! state on the way, and don't turn the same generator in a row more times than it takes to make
+//! that power of it, e.g. `U U` in HTM, where `U2` is a single move.
+
+use std::str::FromStr;
+
+use disk_based_bfs::expander::BfsExpander;
+
+use crate::{
+    depths::DepthTable,
+    notation::{self, Move, NotationError},
+    puzzle::Puzzle,
+};
+
+/// Which end of a sequence a [`MoveFilter`] looks at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum End {
+    Start,
+    End,
+}
+
+/// A condition on the first or last move of a sequence, parsed from e.g. `end=U`, which keeps
+/// sequences that end with `U`, or `start!=U2,U2'`, which drops sequences that start with either.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MoveFilter {
+    pub end: End,
+    /// Whether to keep the sequences whose move is one of `moves`, or drop them.
+    pub keep: bool,
+    /// Names of the moves, in the notation of the puzzle, which is only known once the filter is
+    /// used.
+    pub moves: Vec<String>,
+}
+
+impl FromStr for MoveFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (end, keep, moves) = if let Some((end, moves)) = s.split_once("!=") {
+            (end, false, moves)
+        } else if let Some((end, moves)) = s.split_once('=') {
+            (end, true, moves)
+        } else {
+            return Err(format!(
+                "expected `start=<moves>`, `end=<moves>`, `start!=<moves>` or `end!=<moves>`, \
+                 got {s:?}"
+            ));
+        };
+        let end = match end.trim() {
+            "start" => End::Start,
+            "end" => End::End,
+            end => return Err(format!("expected `start` or `end`, got {end:?}")),
+        };
+        let moves = moves
+            .split(',')
+            .map(|mv| mv.trim().to_owned())
+            .filter(|mv| !mv.is_empty())
+            .collect::<Vec<_>>();
+        if moves.is_empty() {
+            return Err(format!("no moves in the filter {s:?}"));
+        }
+        Ok(Self { end, keep, moves })
+    }
+}
+
+/// A [`MoveFilter`] with its moves parsed for a puzzle.
+struct Filter {
+    end: End,
+    keep: bool,
+    moves: Vec<Move>,
+}
+
+impl Filter {
+    fn new<P: Puzzle>(filter: &MoveFilter) -> Result<Self, NotationError> {
+        let moves = filter
+            .moves
+            .iter()
+            .map(|mv| match notation::parse::<P>(mv)?.as_slice() {
+                &[mv] => Ok(mv),
+                _ => Err(NotationError::UnknownMove {
+                    token: mv.clone(),
+                    position: 0,
+                }),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            end: filter.end,
+            keep: filter.keep,
+            moves,
+        })
+    }
+
+    fn allows(&self, mv: Move) -> bool {
+        self.moves.contains(&mv) == self.keep
+    }
+}
+
+/// The sequences that solve one case.
+pub struct Enumerator<'a, E, const N: usize> {
+    depths: &'a DepthTable,
+    expander: &'a mut E,
+    is_goal: &'a dyn Fn(u64) -> bool,
+    /// The move that makes each neighbour of the expander, which is the same for every state.
+    moves: [Move; N],
+    /// `cost[generator][power]` is the fewest moves that make `power` turns of `generator`, or
+    /// `usize::MAX` if the moves can't make it.
+    cost: Vec<Vec<usize>>,
+    filters: Vec<Filter>,
+}
+
+impl<'a, E, const N: usize> Enumerator<'a, E, N>
+where
+    E: BfsExpander<N>,
+{
+    /// An enumerator for the search that `depths` came from, whose expander is `expander` and
+    /// whose goal states are the ones where `is_goal` is true. Only the sequences that pass every
+    /// filter are listed.
+    pub fn new<P: Puzzle>(
+        depths: &'a DepthTable,
+        expander: &'a mut E,
+        is_goal: &'a dyn Fn(u64) -> bool,
+        filters: &[MoveFilter],
+    ) -> Result<Self, NotationError> {
+        let filters = filters
+            .iter()
+            .map(Filter::new::<P>)
+            .collect::<Result<_, _>>()?;
+
+        // Any state will do to find out which move makes each neighbour
+        let solved = P::solved().encode();
+        let mut neighbours = [0; N];
+        expander.expand(solved, &mut neighbours);
+        let moves = neighbours.map(|neighbour| {
+            notation::find_move::<P>(solved, neighbour)
+                .expect("the expander's neighbours are one move away on the puzzle")
+        });
+
+        let mut cost = P::ORDERS
+            .iter()
+            .map(|&order| {
+                let mut cost = vec![usize::MAX; order];
+                cost[0] = 0;
+                cost
+            })
+            .collect::<Vec<_>>();
+        // The powers of a generator are a cycle, so going round it once is enough to find the
+        // cheapest way to each of them
+        for (generator, cost) in cost.iter_mut().enumerate() {
+            let order = cost.len();
+            for _ in 0..order {
+                for mv in moves.iter().filter(|mv| mv.generator == generator) {
+                    for power in 0..order {
+                        if cost[power] != usize::MAX {
+                            let next = (power + mv.power) % order;
+                            cost[next] = cost[next].min(cost[power] + 1);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            depths,
+            expander,
+            is_goal,
+            moves,
+            cost,
+            filters,
+        })
+    }
+
+    /// The length of an optimal solution of `state`, or `None` if it wasn't reached.
+    pub fn depth(&mut self, state: u64) -> Option<usize> {
+        Some(self.depths.path(self.expander, state, self.is_goal)?.len())
+    }
+
+    /// Calls `found` with every sequence of `length` moves that solves `state`, which is `depth`
+    /// moves from a goal state.
+    pub fn enumerate(
+        &mut self,
+        state: u64,
+        depth: usize,
+        length: usize,
+        found: &mut impl FnMut(&[Move]),
+    ) {
+        let mut path = Vec::with_capacity(length);
+        self.search(state, depth, length, &mut path, found);
+    }
+
+    fn search(
+        &mut self,
+        state: u64,
+        depth: usize,
+        length: usize,
+        path: &mut Vec<Move>,
+        found: &mut impl FnMut(&[Move]),
+    ) {
+        if (self.is_goal)(state) {
+            let last = path.last().copied();
+            let allowed = |filter: &Filter| match (filter.end, last) {
+                (End::End, Some(mv)) => filter.allows(mv),
+                _ => true,
+            };
+            if path.len() == length && self.filters.iter().all(allowed) {
+                found(path);
+            }
+            return;
+        }
+        // A state at depth 0 that isn't a goal state means the goal set isn't the one the depth
+        // table was made from
+        if depth == 0 || path.len() + depth > length {
+            return;
+        }
+
+        let mut neighbours = [0; N];
+        self.expander.expand(state, &mut neighbours);
+        let moves = self.moves;
+        for (&neighbour, &mv) in neighbours.iter().zip(&moves) {
+            if path.is_empty()
+                && !self
+                    .filters
+                    .iter()
+                    .all(|filter| filter.end == End::End || filter.allows(mv))
+            {
+                continue;
+            }
+            if !self.is_canonical(path, mv) {
+                continue;
+            }
+
+            // Neighbours are one deeper, as deep, or one shallower, which differ modulo 3
+            let next = match self.depths.get(neighbour) {
+                Some(value) if value == (depth + 2) % 3 => depth - 1,
+                Some(value) if value == depth % 3 => depth,
+                Some(_) => depth + 1,
+                None => continue,
+            };
+            path.push(mv);
+            self.search(neighbour, next, length, path, found);
+            path.pop();
+        }
+    }
+
+    /// Whether `path` followed by `mv` doesn't end in a run of turns of one generator that fewer
+    /// moves would make.
+    fn is_canonical(&self, path: &[Move], mv: Move) -> bool {
+        let order = self.cost[mv.generator].len();
+        let run = path
+            .iter()
+            .rev()
+            .take_while(|previous| previous.generator == mv.generator);
+        let (count, power) = run.fold((1, mv.power), |(count, power), previous| {
+            (count + 1, (power + previous.power) % order)
+        });
+        power != 0 && self.cost[mv.generator][power] >= count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeSet, fs};
+
+    use super::*;
+    use crate::{
+        depths::{self, DepthTableWriter},
+        test_util::temp_dir,
+    };
+
+    /// A cycle of length 6 turned by `A`, and one of length 4 turned by `B`.
+    #[derive(Debug)]
+    struct Cycles(u64, u64);
+
+    impl Puzzle for Cycles {
+        const ID: &'static str = "cycles";
+        const STATE_SIZE: u64 = 24;
+        const MOVES: &'static [&'static str] = &["A", "B"];
+        const ORDERS: &'static [usize] = &[6, 4];
+
+        fn solved() -> Self {
+            Self(0, 0)
+        }
+
+        fn encode(&self) -> u64 {
+            self.0 * 4 + self.1
+        }
+
+        fn decode(&mut self, coord: u64) {
+            *self = Self(coord / 4, coord % 4);
+        }
+
+        fn do_move(&mut self, mv: usize) {
+            match mv {
+                0 => self.0 = (self.0 + 1) % 6,
+                _ => self.1 = (self.1 + 1) % 4,
+            }
+        }
+    }
+
+    /// `A`, `A'` and every power of `B`.
+    struct Expander;
+
+    impl BfsExpander<5> for Expander {
+        fn expand(&mut self, node: u64, expanded_nodes: &mut [u64; 5]) {
+            let (a, b) = (node / 4, node % 4);
+            *expanded_nodes = [
+                (a + 1) % 6 * 4 + b,
+                (a + 5) % 6 * 4 + b,
+                a * 4 + (b + 1) % 4,
+                a * 4 + (b + 2) % 4,
+                a * 4 + (b + 3) % 4,
+            ];
+        }
+    }
+
+    fn depth(state: u64) -> usize {
+        let (a, b) = (state / 4, state % 4);
+        (a.min(6 - a) + b.min(1)) as usize
+    }
+
+    /// Every sequence of `length` moves from `state` to the solved state that doesn't pass
+    /// through it, and doesn't have a run of turns of `A` or `B` that fewer moves would make,
+    /// found without the depth table.
+    fn brute_force(state: u64, length: usize) -> BTreeSet<String> {
+        let moves = notation::parse::<Cycles>("A A' B B2 B'").unwrap();
+        let cost = |mv: Move, power: usize| match mv.generator {
+            0 => power.min(6 - power),
+            _ => power.min(1),
+        };
+        let mut sequences = BTreeSet::new();
+        let mut stack = vec![(Cycles::from_coord(state), vec![])];
+        while let Some((puzzle, path)) = stack.pop() {
+            if puzzle.encode() == 0 || path.len() == length {
+                if puzzle.encode() == 0 && path.len() == length {
+                    sequences.insert(notation::format::<Cycles>(&path));
+                }
+                continue;
+            }
+            for &mv in &moves {
+                let run = path
+                    .iter()
+                    .rev()
+                    .take_while(|previous: &&Move| previous.generator == mv.generator)
+                    .chain([&mv]);
+                let (count, power) = run.fold((0, 0), |(count, power), mv| {
+                    (count + 1, (power + mv.power) % Cycles::ORDERS[mv.generator])
+                });
+                if power != 0 && cost(mv, power) >= count {
+                    let mut puzzle = Cycles::from_coord(puzzle.encode());
+                    mv.apply(&mut puzzle);
+                    let mut path = path.clone();
+                    path.push(mv);
+                    stack.push((puzzle, path));
+                }
+            }
+        }
+        sequences
+    }
+
+    fn enumerate(
+        enumerator: &mut Enumerator<Expander, 5>,
+        state: u64,
+        length: usize,
+    ) -> BTreeSet<String> {
+        let mut found = BTreeSet::new();
+        let depth = enumerator.depth(state).unwrap();
+        enumerator.enumerate(state, depth, length, &mut |path| {
+            assert!(found.insert(notation::format::<Cycles>(path)));
+        });
+        found
+    }
+
+    #[test]
+    fn test_enumerate() {
+        let dir = temp_dir("enumerate-cycles");
+        let writer = DepthTableWriter::create(&depths::path(&dir, "cycles"), 24).unwrap();
+        for state in 0..24 {
+            writer.set(state, depth(state));
+        }
+        let table = writer.finish().unwrap();
+
+        let is_goal = |state| state == 0;
+        let mut expander = Expander;
+        let mut enumerator =
+            Enumerator::new::<Cycles>(&table, &mut expander, &is_goal, &[]).unwrap();
+        for state in 0..24 {
+            assert_eq!(enumerator.depth(state), Some(depth(state)));
+            for length in depth(state)..depth(state) + 4 {
+                assert_eq!(
+                    enumerate(&mut enumerator, state, length),
+                    brute_force(state, length),
+                    "{state} {length}"
+                );
+            }
+        }
+
+        // From A B, the optimal solutions are A' B' and B' A'
+        let state = 5;
+        let filters = ["end=A'".parse().unwrap()];
+        let mut enumerator =
+            Enumerator::new::<Cycles>(&table, &mut expander, &is_goal, &filters).unwrap();
+        assert_eq!(
+            enumerate(&mut enumerator, state, 2),
+            ["B' A'".to_owned()].into()
+        );
+        let filters = ["start!=B,B'".parse().unwrap()];
+        let mut enumerator =
+            Enumerator::new::<Cycles>(&table, &mut expander, &is_goal, &filters).unwrap();
+        assert_eq!(
+            enumerate(&mut enumerator, state, 2),
+            ["A' B'".to_owned()].into()
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_move_filter() {
+        assert_eq!(
+            "start!=U2, U2'".parse(),
+            Ok(MoveFilter {
+                end: End::Start,
+                keep: false,
+                moves: vec!["U2".to_owned(), "U2'".to_owned()],
+            })
+        );
+        assert!("middle=U".parse::<MoveFilter>().is_err());
+        assert!("end=".parse::<MoveFilter>().is_err());
+        assert!("U".parse::<MoveFilter>().is_err());
+
+        assert!(Filter::new::<Cycles>(&"end=B2".parse().unwrap()).is_ok());
+        assert!(Filter::new::<Cycles>(&"end=C".parse().unwrap()).is_err());
+        assert!(Filter::new::<Cycles>(&"end=A B".parse().unwrap()).is_err());
+    }
+}
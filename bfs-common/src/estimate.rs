@@ -0,0 +1,548 @@
+//! Estimates the distance distribution of a state space from a sample of random states, before
+//! searching all of it, to help choose the bounds of a search and see how large its layers get.
+//!
+//! Each sampled state is solved with a breadth-first search from both ends at once, from the state
+//! and from the goal states, using the same expander as the full search. This needs nothing but
+//! the expander, so it works before there is a depth table to solve with. Both searches use the
+//! moves of the expander forwards, so they only meet at the right depth in metrics that aren't
+//! [one-way](crate::manifest::Variant::one_way), and the runner doesn't estimate the others.
+//!
+//! The searches of the deepest states are the largest, so each is limited to a number of states.
+//! A sample whose search reaches the limit only gives a lower bound on its depth, and is counted
+//! separately.
+
+use std::{
+    collections::HashSet,
+    fmt, fs, io,
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use disk_based_bfs::expander::BfsExpander;
+use rand::{rngs::StdRng, SeedableRng};
+use rayon::iter::{IntoParallelIterator as _, ParallelIterator as _};
+use serde::Serialize;
+
+use crate::puzzle::Puzzle;
+
+/// The two-sided normal quantile of a 95% confidence interval.
+const Z_95: f64 = 1.959964;
+
+/// How far a sampled state is from the goal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleDepth {
+    Exact(usize),
+    /// The search reached its limit, having shown that the state is at least this deep.
+    AtLeast(usize),
+    /// The state can't reach the goal.
+    Unreachable,
+}
+
+/// One side of a bidirectional search: the states found so far, and the ones at its depth.
+struct Side {
+    seen: HashSet<u64>,
+    frontier: Vec<u64>,
+    depth: usize,
+}
+
+impl Side {
+    fn new(states: &[u64]) -> Self {
+        Self {
+            seen: states.iter().copied().collect(),
+            frontier: states.to_vec(),
+            depth: 0,
+        }
+    }
+}
+
+/// The distance from `state` to the nearest of `goals`, found by searching from both ends until
+/// the searches meet or have found `max_states` states between them.
+pub fn sample_depth<E, const N: usize>(
+    expander: &mut E,
+    state: u64,
+    goals: &[u64],
+    max_states: usize,
+) -> SampleDepth
+where
+    E: BfsExpander<N>,
+{
+    if goals.contains(&state) {
+        return SampleDepth::Exact(0);
+    }
+
+    let mut from_state = Side::new(&[state]);
+    let mut from_goal = Side::new(goals);
+    let mut neighbours = [0; N];
+    loop {
+        // The two sides have found no state in common, so the state is further away than both
+        // depths put together
+        let searched = from_state.depth + from_goal.depth;
+
+        let (side, other) = if from_state.frontier.len() <= from_goal.frontier.len() {
+            (&mut from_state, &from_goal)
+        } else {
+            (&mut from_goal, &from_state)
+        };
+
+        let mut next = Vec::new();
+        for &s in &side.frontier {
+            expander.expand(s, &mut neighbours);
+            for &neighbour in &neighbours {
+                if other.seen.contains(&neighbour) {
+                    return SampleDepth::Exact(searched + 1);
+                }
+                if side.seen.insert(neighbour) {
+                    next.push(neighbour);
+                }
+            }
+            if side.seen.len() + other.seen.len() > max_states {
+                return SampleDepth::AtLeast(searched + 1);
+            }
+        }
+
+        if next.is_empty() {
+            return SampleDepth::Unreachable;
+        }
+        side.frontier = next;
+        side.depth += 1;
+    }
+}
+
+/// The number of sampled states at each depth.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Estimate {
+    /// `exact[d]` is the number of samples at depth `d`.
+    pub exact: Vec<u64>,
+    /// `at_least[d]` is the number of samples whose search reached its limit, and that are at
+    /// least `d` deep.
+    pub at_least: Vec<u64>,
+    pub unreachable: u64,
+}
+
+fn add_at(counts: &mut Vec<u64>, depth: usize, count: u64) {
+    if counts.len() <= depth {
+        counts.resize(depth + 1, 0);
+    }
+    counts[depth] += count;
+}
+
+impl Estimate {
+    pub fn add(&mut self, depth: SampleDepth) {
+        match depth {
+            SampleDepth::Exact(depth) => add_at(&mut self.exact, depth, 1),
+            SampleDepth::AtLeast(depth) => add_at(&mut self.at_least, depth, 1),
+            SampleDepth::Unreachable => self.unreachable += 1,
+        }
+    }
+
+    fn merge(mut self, other: Self) -> Self {
+        for (depth, &count) in other.exact.iter().enumerate() {
+            add_at(&mut self.exact, depth, count);
+        }
+        for (depth, &count) in other.at_least.iter().enumerate() {
+            add_at(&mut self.at_least, depth, count);
+        }
+        self.unreachable += other.unreachable;
+        self
+    }
+
+    /// The number of states sampled.
+    pub fn samples(&self) -> u64 {
+        self.exact.iter().sum::<u64>() + self.at_least.iter().sum::<u64>() + self.unreachable
+    }
+
+    /// The number of samples whose search reached its limit.
+    pub fn limited(&self) -> u64 {
+        self.at_least.iter().sum()
+    }
+
+    /// The deepest sample whose depth is known, or `None` if there is none.
+    pub fn deepest(&self) -> Option<usize> {
+        self.exact.iter().rposition(|&count| count > 0)
+    }
+
+    /// The mean depth of the samples that can reach the goal, counting the ones whose search
+    /// reached its limit at their lower bound.
+    pub fn average(&self) -> f64 {
+        let weighted = |counts: &[u64]| {
+            counts
+                .iter()
+                .enumerate()
+                .map(|(depth, &count)| depth as f64 * count as f64)
+                .sum::<f64>()
+        };
+        let reachable = self.samples() - self.unreachable;
+        (weighted(&self.exact) + weighted(&self.at_least)) / reachable as f64
+    }
+
+    /// The fraction of states at `depth`, and a 95% confidence interval for it.
+    pub fn fraction(&self, depth: usize) -> (f64, f64, f64) {
+        let count = self.exact.get(depth).copied().unwrap_or(0);
+        wilson_interval(count, self.samples())
+    }
+
+    pub fn report<'a>(
+        &'a self,
+        puzzle: &'a str,
+        run: &'a str,
+        state_size: u64,
+        max_states: usize,
+        seed: u64,
+    ) -> EstimateReport<'a> {
+        EstimateReport {
+            puzzle,
+            run,
+            estimate: self,
+            state_size,
+            max_states,
+            seed,
+        }
+    }
+}
+
+/// The fraction `count / samples`, with the Wilson score interval around it, which stays sensible
+/// for the depths that only a few samples (or none) are at.
+fn wilson_interval(count: u64, samples: u64) -> (f64, f64, f64) {
+    if samples == 0 {
+        return (0.0, 0.0, 1.0);
+    }
+    let n = samples as f64;
+    let p = count as f64 / n;
+    let z2 = Z_95 * Z_95;
+    let centre = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+    let half_width = Z_95 / (1.0 + z2 / n) * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt();
+    (
+        p,
+        (centre - half_width).max(0.0),
+        (centre + half_width).min(1.0),
+    )
+}
+
+/// Samples `samples` uniformly random states of `P` on `threads` threads, and finds the distance
+/// from each to `goals` with [`sample_depth`]. The samples only depend on `seed`, not on the number of threads.
+pub fn estimate<P, E, const N: usize>(
+    expander: &E,
+    goals: &[u64],
+    samples: u64,
+    max_states: usize,
+    seed: u64,
+    threads: usize,
+) -> Estimate
+where
+    P: Puzzle,
+    E: BfsExpander<N> + Clone + Sync + Send,
+{
+    let done = AtomicU64::new(0);
+    let log_every = (samples / 20).max(1);
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .expect("failed to start the sampling threads");
+    pool.install(|| {
+        (0..samples)
+            .into_par_iter()
+            .map_init(
+                || expander.clone(),
+                |expander, i| {
+                    let mut rng = StdRng::seed_from_u64(seed.wrapping_add(i));
+                    let state = P::random_coord(&mut rng);
+                    let depth = sample_depth(expander, state, goals, max_states);
+
+                    let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+                    if done.is_multiple_of(log_every) {
+                        tracing::info!("sampled {done} of {samples} states");
+                    }
+                    depth
+                },
+            )
+            .fold(Estimate::default, |mut estimate, depth| {
+                estimate.add(depth);
+                estimate
+            })
+            .reduce(Estimate::default, Estimate::merge)
+    })
+}
+
+/// An [`Estimate`] with the details of the run it is for. It displays as a plain text table, and
+/// serializes to JSON.
+pub struct EstimateReport<'a> {
+    puzzle: &'a str,
+    run: &'a str,
+    estimate: &'a Estimate,
+    state_size: u64,
+    max_states: usize,
+    /// The seed the samples were drawn with, so that they can be drawn again.
+    seed: u64,
+}
+
+#[derive(Serialize)]
+struct JsonEstimate<'a> {
+    puzzle: &'a str,
+    run: &'a str,
+    state_size: u64,
+    samples: u64,
+    max_states: usize,
+    seed: u64,
+    limited: u64,
+    unreachable: u64,
+    deepest: Option<usize>,
+    average_distance: f64,
+    depths: Vec<JsonDepth>,
+}
+
+#[derive(Serialize)]
+struct JsonDepth {
+    depth: usize,
+    samples: u64,
+    at_least: u64,
+    fraction: f64,
+    estimate: u64,
+    low: u64,
+    high: u64,
+}
+
+impl EstimateReport<'_> {
+    /// The estimated number of states at each depth, with a 95% confidence interval, for every
+    /// depth up to the deepest sample.
+    fn depths(&self) -> impl Iterator<Item = JsonDepth> + '_ {
+        let estimate = self.estimate;
+        let depths = estimate.exact.len().max(estimate.at_least.len());
+        let states = |fraction: f64| (fraction * self.state_size as f64).round() as u64;
+        (0..depths).map(move |depth| {
+            let (fraction, low, high) = estimate.fraction(depth);
+            JsonDepth {
+                depth,
+                samples: estimate.exact.get(depth).copied().unwrap_or(0),
+                at_least: estimate.at_least.get(depth).copied().unwrap_or(0),
+                fraction,
+                estimate: states(fraction),
+                low: states(low),
+                high: states(high),
+            }
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        let report = JsonEstimate {
+            puzzle: self.puzzle,
+            run: self.run,
+            state_size: self.state_size,
+            samples: self.estimate.samples(),
+            max_states: self.max_states,
+            seed: self.seed,
+            limited: self.estimate.limited(),
+            unreachable: self.estimate.unreachable,
+            deepest: self.estimate.deepest(),
+            average_distance: self.estimate.average(),
+            depths: self.depths().collect(),
+        };
+        serde_json::to_string_pretty(&report).unwrap()
+    }
+
+    /// Writes the report to `<name>.estimate.txt` and `<name>.estimate.json` in `directory`,
+    /// creating it if needed.
+    pub fn write(&self, directory: &Path, name: &str) -> io::Result<()> {
+        fs::create_dir_all(directory)?;
+        fs::write(
+            directory.join(format!("{name}.estimate.txt")),
+            self.to_string() + "\n",
+        )?;
+        fs::write(
+            directory.join(format!("{name}.estimate.json")),
+            self.to_json() + "\n",
+        )
+    }
+}
+
+impl fmt::Display for EstimateReport<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let estimate = self.estimate;
+        let samples = estimate.samples();
+        let sample_width = samples.to_string().len().max("samples".len());
+        let width = self.state_size.to_string().len().max("estimate".len());
+
+        writeln!(f, "{} ({})", self.puzzle, self.run)?;
+        writeln!(
+            f,
+            "estimated from {samples} random states (seed {}), searching at most {} states for each",
+            self.seed, self.max_states
+        )?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "depth  {:>sample_width$}  fraction  {:>width$}  {:>width$}  {:>width$}",
+            "samples", "estimate", "low", "high"
+        )?;
+        for depth in self.depths().filter(|depth| depth.samples > 0) {
+            writeln!(
+                f,
+                "{:>5}  {:>sample_width$}  {:>8.6}  {:>width$}  {:>width$}  {:>width$}",
+                depth.depth, depth.samples, depth.fraction, depth.estimate, depth.low, depth.high
+            )?;
+        }
+        writeln!(f)?;
+
+        for depth in self.depths().filter(|depth| depth.at_least > 0) {
+            writeln!(
+                f,
+                "at least {}: {} samples reached the limit",
+                depth.depth, depth.at_least
+            )?;
+        }
+        if estimate.unreachable > 0 {
+            writeln!(f, "unreachable: {} samples", estimate.unreachable)?;
+        }
+        match estimate.deepest() {
+            Some(depth) => writeln!(f, "deepest sample: {depth}")?,
+            None => writeln!(f, "deepest sample: none")?,
+        }
+        if estimate.limited() > 0 {
+            write!(f, "average distance: at least {:.6}", estimate.average())
+        } else {
+            write!(f, "average distance: {:.6}", estimate.average())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Adding 1, 3 or their inverses modulo 20. State 20 has no moves to the others.
+    #[derive(Clone)]
+    struct Expander;
+
+    impl BfsExpander<4> for Expander {
+        fn expand(&mut self, node: u64, expanded_nodes: &mut [u64; 4]) {
+            if node == 20 {
+                *expanded_nodes = [node; 4];
+                return;
+            }
+            *expanded_nodes = [
+                (node + 1) % 20,
+                (node + 19) % 20,
+                (node + 3) % 20,
+                (node + 17) % 20,
+            ];
+        }
+    }
+
+    #[derive(Debug)]
+    struct Cycle(u64);
+
+    impl Puzzle for Cycle {
+        const ID: &'static str = "cycle";
+        const STATE_SIZE: u64 = 20;
+        const MOVES: &'static [&'static str] = &["A", "B"];
+        const ORDERS: &'static [usize] = &[20, 20];
+
+        fn solved() -> Self {
+            Self(0)
+        }
+
+        fn encode(&self) -> u64 {
+            self.0
+        }
+
+        fn decode(&mut self, coord: u64) {
+            self.0 = coord;
+        }
+
+        fn do_move(&mut self, mv: usize) {
+            self.0 = (self.0 + [1, 3][mv]) % 20;
+        }
+    }
+
+    fn distance(state: u64) -> usize {
+        let d = state.min(20 - state);
+        (d / 3 + d % 3) as usize
+    }
+
+    #[test]
+    fn test_sample_depth() {
+        for state in 0..20 {
+            assert_eq!(
+                sample_depth(&mut Expander, state, &[0], usize::MAX),
+                SampleDepth::Exact(distance(state)),
+                "{state}"
+            );
+        }
+
+        // The depth of state 10 is 4, but the limit is reached before the searches meet
+        assert!(matches!(
+            sample_depth(&mut Expander, 10, &[0], 6),
+            SampleDepth::AtLeast(1..=3)
+        ));
+        assert_eq!(
+            sample_depth(&mut Expander, 20, &[0], usize::MAX),
+            SampleDepth::Unreachable
+        );
+
+        // The distance to a set of goals is the distance to the nearest of them
+        assert_eq!(
+            sample_depth(&mut Expander, 8, &[0, 10], usize::MAX),
+            SampleDepth::Exact(2)
+        );
+        assert_eq!(
+            sample_depth(&mut Expander, 10, &[0, 10], usize::MAX),
+            SampleDepth::Exact(0)
+        );
+    }
+
+    #[test]
+    fn test_estimate() {
+        let sampled = estimate::<Cycle, _, 4>(&Expander, &[0], 2000, usize::MAX, 1, 4);
+        assert_eq!(sampled.samples(), 2000);
+        assert_eq!(sampled.limited(), 0);
+        assert_eq!(sampled.deepest(), Some(4));
+        assert_eq!(
+            sampled,
+            estimate::<Cycle, _, 4>(&Expander, &[0], 2000, usize::MAX, 1, 1)
+        );
+
+        // The number of states at each depth is inside its interval
+        let report = sampled.report("cycle", "cycle.htm", 20, usize::MAX, 1);
+        for depth in report.depths() {
+            let count = (0..20).filter(|&s| distance(s) == depth.depth).count() as u64;
+            assert!(depth.low <= count && count <= depth.high, "{}", depth.depth);
+        }
+        assert!((sampled.average() - 2.3).abs() < 0.1);
+
+        let text = report.to_string();
+        assert!(text.starts_with("cycle (cycle.htm)\nestimated from 2000 random states (seed 1)"));
+        assert!(text.contains("\ndeepest sample: 4\naverage distance: 2."));
+        let json = serde_json::from_str::<serde_json::Value>(&report.to_json()).unwrap();
+        assert_eq!(json["depths"][0]["depth"], 0);
+        assert_eq!(json["depths"].as_array().unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_limited_estimate() {
+        let mut estimate = Estimate::default();
+        estimate.add(SampleDepth::Exact(2));
+        estimate.add(SampleDepth::AtLeast(3));
+        estimate.add(SampleDepth::AtLeast(3));
+        estimate.add(SampleDepth::Unreachable);
+        assert_eq!(estimate.samples(), 4);
+        assert_eq!(estimate.limited(), 2);
+        assert_eq!(estimate.average(), 8.0 / 3.0);
+
+        let text = estimate.report("cycle", "cycle.htm", 20, 6, 1).to_string();
+        assert!(text.contains("\nat least 3: 2 samples reached the limit\n"));
+        assert!(text.contains("\nunreachable: 1 samples\n"));
+        assert!(text.ends_with("average distance: at least 2.666667"));
+    }
+
+    #[test]
+    fn test_wilson_interval() {
+        let (p, low, high) = wilson_interval(0, 100);
+        assert_eq!((p, low), (0.0, 0.0));
+        assert!(0.03 < high && high < 0.04);
+
+        let (p, low, high) = wilson_interval(50, 100);
+        assert_eq!(p, 0.5);
+        assert!((0.5 - low - (high - 0.5)).abs() < 1e-9);
+        assert!(0.40 < low && low < 0.41);
+    }
+}
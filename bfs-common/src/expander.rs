@@ -0,0 +1,254 @@
+//! Expanders generated from a list of generators, instead of unrolled by hand.
+//!
+//! [`expander!`](crate::expander!) defines an expander struct holding a puzzle, and implements
+//! `BfsExpander<N>` for it by applying each generator repeatedly and encoding the powers that the
+//! metric counts as a single move. `N` is computed from the generator orders at compile time, so
+//! the array length can't disagree with the number of moves.
+//!
+//! Expanders that use fused transposition tables (e.g. a `ur` table for `U` followed by `R`) to
+//! save table lookups are still written by hand.
+
+use std::collections::HashSet;
+
+pub use disk_based_bfs::expander::BfsExpander;
+
+/// Which powers of a generator count as a single move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    /// Every power except the identity.
+    Htm,
+
+    /// The generator and its inverse.
+    Qtm,
+
+    /// Only the inverse of the generator, i.e. turns in one direction.
+    Utm,
+}
+
+impl Metric {
+    /// Whether `generator^power` is a single move, for a generator of order `order`.
+    pub const fn includes(self, power: usize, order: usize) -> bool {
+        if power == 0 || power >= order {
+            return false;
+        }
+
+        match self {
+            Self::Htm => true,
+            Self::Qtm => power == 1 || power == order - 1,
+            Self::Utm => power == order - 1,
+        }
+    }
+
+    /// The number of moves generated by generators of the given orders.
+    pub const fn expansion_nodes(self, orders: &[usize]) -> usize {
+        let mut nodes = 0;
+        let mut i = 0;
+        while i < orders.len() {
+            let mut power = 1;
+            while power < orders[i] {
+                if self.includes(power, orders[i]) {
+                    nodes += 1;
+                }
+                power += 1;
+            }
+            i += 1;
+        }
+        nodes
+    }
+}
+
+/// The states at each depth up to `max_depth` of a breadth-first search from `initial_states`,
+/// done in memory. This is for checking expanders against each other on shallow searches, e.g.
+/// that a search reduced by symmetry finds the same states as the full one.
+pub fn layers<E, const N: usize>(
+    expander: &mut E,
+    initial_states: &[u64],
+    max_depth: usize,
+) -> Vec<Vec<u64>>
+where
+    E: BfsExpander<N>,
+{
+    let mut seen = initial_states.iter().copied().collect::<HashSet<_>>();
+    let mut layers = vec![seen.iter().copied().collect::<Vec<_>>()];
+    let mut neighbours = [0; N];
+
+    while layers.len() <= max_depth {
+        let mut layer = Vec::new();
+        for &state in layers.last().unwrap() {
+            expander.expand(state, &mut neighbours);
+            layer.extend(neighbours.into_iter().filter(|&n| seen.insert(n)));
+        }
+        if layer.is_empty() {
+            break;
+        }
+        layers.push(layer);
+    }
+
+    layers
+}
+
+/// Defines an expander and the constant holding its number of expansion nodes.
+///
+/// The field type must have `decode(&mut self, u64)`, `encode(&self) -> u64`, and a method for
+/// each generator. The moves are generated in the order given, each generator's powers in
+/// increasing order.
+///
+/// ```ignore
+/// bfs_common::expander! {
+///     struct ExpanderHtm<'a> {
+///         cube: CoordCube<'a>,
+///     }
+///     const EXPANSION_NODES_HTM;
+///     metric = Htm;
+///     generators = [u: 4, r: 4, f2: 2];
+/// }
+/// ```
+#[macro_export]
+macro_rules! expander {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident $(<$lt:lifetime>)? {
+            $field:ident: $ty:ty $(,)?
+        }
+        const $nodes:ident;
+        metric = $metric:ident;
+        generators = [$($generator:ident: $order:literal),+ $(,)?];
+    ) => {
+        $vis const $nodes: usize =
+            $crate::expander::Metric::$metric.expansion_nodes(&[$($order),+]);
+
+        $(#[$attr])*
+        #[derive(Clone)]
+        $vis struct $name $(<$lt>)? {
+            $field: $ty,
+        }
+
+        impl $(<$lt>)? $crate::expander::BfsExpander<$nodes> for $name $(<$lt>)? {
+            fn expand(&mut self, node: u64, expanded_nodes: &mut [u64; $nodes]) {
+                const ORDERS: &[usize] = &[$($order),+];
+
+                self.$field.decode(node);
+
+                let mut idx = 0;
+                let mut generator = 0;
+                $(
+                    for power in 1..$order {
+                        self.$field.$generator();
+                        if $crate::expander::Metric::$metric.includes(power, $order) {
+                            expanded_nodes[idx] = self.$field.encode();
+                            idx += 1;
+                        }
+                    }
+                    // Undo the generator, unless nothing comes after it
+                    generator += 1;
+                    if generator < ORDERS.len() {
+                        self.$field.$generator();
+                    }
+                )+
+
+                debug_assert_eq!(idx, $nodes);
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two independent cycles, of lengths 5 and 2.
+    #[derive(Clone)]
+    struct Cycles {
+        a: u64,
+        b: u64,
+    }
+
+    impl Cycles {
+        fn a(&mut self) {
+            self.a = (self.a + 1) % 5;
+        }
+
+        fn b(&mut self) {
+            self.b = (self.b + 1) % 2;
+        }
+
+        fn encode(&self) -> u64 {
+            self.a * 2 + self.b
+        }
+
+        fn decode(&mut self, coord: u64) {
+            self.a = coord / 2;
+            self.b = coord % 2;
+        }
+    }
+
+    crate::expander! {
+        struct ExpanderHtm {
+            cycles: Cycles,
+        }
+        const EXPANSION_NODES_HTM;
+        metric = Htm;
+        generators = [a: 5, b: 2];
+    }
+
+    crate::expander! {
+        struct ExpanderQtm {
+            cycles: Cycles,
+        }
+        const EXPANSION_NODES_QTM;
+        metric = Qtm;
+        generators = [a: 5, b: 2];
+    }
+
+    crate::expander! {
+        struct ExpanderUtm {
+            cycles: Cycles,
+        }
+        const EXPANSION_NODES_UTM;
+        metric = Utm;
+        generators = [a: 5, b: 2];
+    }
+
+    fn cycles() -> Cycles {
+        Cycles { a: 0, b: 0 }
+    }
+
+    #[test]
+    fn test_expansion_nodes() {
+        assert_eq!(EXPANSION_NODES_HTM, 5);
+        assert_eq!(EXPANSION_NODES_QTM, 3);
+        assert_eq!(EXPANSION_NODES_UTM, 2);
+        assert_eq!(Metric::Htm.expansion_nodes(&[4; 6]), 18);
+        assert_eq!(Metric::Qtm.expansion_nodes(&[4, 4, 2]), 5);
+    }
+
+    #[test]
+    fn test_expand() {
+        // a = 3, b = 1
+        let node = 7;
+
+        let mut expanded = [0; EXPANSION_NODES_HTM];
+        ExpanderHtm { cycles: cycles() }.expand(node, &mut expanded);
+        assert_eq!(expanded, [9, 1, 3, 5, 6]);
+
+        let mut expanded = [0; EXPANSION_NODES_QTM];
+        ExpanderQtm { cycles: cycles() }.expand(node, &mut expanded);
+        assert_eq!(expanded, [9, 5, 6]);
+
+        let mut expanded = [0; EXPANSION_NODES_UTM];
+        ExpanderUtm { cycles: cycles() }.expand(node, &mut expanded);
+        assert_eq!(expanded, [5, 6]);
+    }
+
+    #[test]
+    fn test_layers() {
+        let sizes = |max_depth| {
+            layers(&mut ExpanderQtm { cycles: cycles() }, &[0], max_depth)
+                .iter()
+                .map(Vec::len)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(sizes(2), [1, 3, 4]);
+        assert_eq!(sizes(10), [1, 3, 4, 2]);
+    }
+}
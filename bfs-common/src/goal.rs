@@ -0,0 +1,213 @@
+//! The states a search starts from. By default this is the solved state alone, but a search can
+//! instead find the distance to any of a set of states, e.g. to solved up to a turn of the U layer.
+//!
+//! A [`GoalSet`] is made from an explicit list of scrambles, or from a predicate on the states of a
+//! puzzle, checked for each of a list of candidates. Puzzles give names to the goal sets that are
+//! worth searching with [`Puzzle::goal`], and any other set can be given as scrambles.
+
+use thiserror::Error;
+
+use crate::{
+    notation::{self, NotationError},
+    puzzle::Puzzle,
+};
+
+/// Which goal set to search from, before it is made for a puzzle with [`Goal::resolve`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Goal {
+    #[default]
+    Solved,
+
+    /// One of the puzzle's [named goal sets](Puzzle::GOALS).
+    Named(String),
+
+    /// The states that these scrambles lead to from the solved state.
+    Scrambles(Vec<String>),
+}
+
+#[derive(Debug, Error)]
+pub enum GoalError {
+    #[error("{puzzle} has no goal set called {name:?}, only {}", available.join(", "))]
+    Unknown {
+        puzzle: &'static str,
+        name: String,
+        available: Vec<&'static str>,
+    },
+
+    #[error("invalid goal scramble: {0}")]
+    Notation(#[from] NotationError),
+}
+
+impl Goal {
+    /// The name that runs with this goal set are told apart by, i.e. the config section and output
+    /// files of the run, or `None` for the solved state.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Self::Solved => None,
+            Self::Named(name) => Some(name),
+            Self::Scrambles(_) => Some("scrambles"),
+        }
+    }
+
+    pub fn resolve<P: Puzzle>(&self) -> Result<GoalSet, GoalError> {
+        match self {
+            Self::Solved => Ok(GoalSet::solved::<P>()),
+            Self::Named(name) => {
+                let set = P::goal(name).ok_or_else(|| GoalError::Unknown {
+                    puzzle: P::ID,
+                    name: name.clone(),
+                    available: std::iter::once("solved")
+                        .chain(P::GOALS.iter().copied())
+                        .collect(),
+                })?;
+                Ok(GoalSet {
+                    description: name.clone(),
+                    ..set
+                })
+            }
+            Self::Scrambles(scrambles) => Ok(GoalSet::from_scrambles::<P>(scrambles)?),
+        }
+    }
+}
+
+/// A set of states of a puzzle, and how it was defined.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GoalSet {
+    /// e.g. `solved`, the name of a goal set, or the list of scrambles.
+    pub description: String,
+    /// Sorted coordinates of the states, without duplicates.
+    pub states: Vec<u64>,
+}
+
+impl GoalSet {
+    fn new(description: String, mut states: Vec<u64>) -> Self {
+        states.sort_unstable();
+        states.dedup();
+        Self {
+            description,
+            states,
+        }
+    }
+
+    pub fn solved<P: Puzzle>() -> Self {
+        Self::new("solved".to_owned(), vec![P::solved().encode()])
+    }
+
+    /// The states that `scrambles` lead to from the solved state. An empty scramble is the solved
+    /// state itself.
+    pub fn from_scrambles<P: Puzzle>(scrambles: &[impl AsRef<str>]) -> Result<Self, NotationError> {
+        let states = scrambles
+            .iter()
+            .map(|scramble| {
+                let moves = notation::parse::<P>(scramble.as_ref())?;
+                Ok(notation::apply::<P>(&moves).encode())
+            })
+            .collect::<Result<_, NotationError>>()?;
+        let description = scrambles
+            .iter()
+            .map(|scramble| format!("{:?}", scramble.as_ref()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        Ok(Self::new(description, states))
+    }
+
+    /// The states among `candidates` that `predicate` holds for. The candidates only have to
+    /// include every state in the set, so that a small part of a large state space can be checked
+    /// instead of all of it.
+    pub fn from_predicate<P: Puzzle>(
+        description: &str,
+        candidates: impl IntoIterator<Item = u64>,
+        mut predicate: impl FnMut(&P) -> bool,
+    ) -> Self {
+        let mut puzzle = P::solved();
+        let states = candidates
+            .into_iter()
+            .filter(|&state| {
+                puzzle.decode(state);
+                predicate(&puzzle)
+            })
+            .collect();
+        Self::new(description.to_owned(), states)
+    }
+
+    pub fn contains(&self, state: u64) -> bool {
+        self.states.binary_search(&state).is_ok()
+    }
+
+    /// Whether this is the solved state alone.
+    pub fn is_solved<P: Puzzle>(&self) -> bool {
+        self.states == [P::solved().encode()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A cycle of length 6 and a flip, which are both solved at 0.
+    #[derive(Debug)]
+    struct Cycles(u64, u64);
+
+    impl Puzzle for Cycles {
+        const ID: &'static str = "cycles";
+        const STATE_SIZE: u64 = 12;
+        const MOVES: &'static [&'static str] = &["A", "B"];
+        const ORDERS: &'static [usize] = &[6, 2];
+        const GOALS: &'static [&'static str] = &["a"];
+
+        fn solved() -> Self {
+            Self(0, 0)
+        }
+
+        fn encode(&self) -> u64 {
+            self.0 * 2 + self.1
+        }
+
+        fn decode(&mut self, coord: u64) {
+            *self = Self(coord / 2, coord % 2);
+        }
+
+        fn do_move(&mut self, mv: usize) {
+            match mv {
+                0 => self.0 = (self.0 + 1) % 6,
+                _ => self.1 = (self.1 + 1) % 2,
+            }
+        }
+
+        fn goal(name: &str) -> Option<GoalSet> {
+            // Solved up to a turn of the cycle
+            (name == "a")
+                .then(|| GoalSet::from_predicate::<Self>("turns of A", 0..12, |c| c.1 == 0))
+        }
+    }
+
+    #[test]
+    fn test_goal_sets() {
+        let solved = Goal::Solved.resolve::<Cycles>().unwrap();
+        assert_eq!(solved.states, [0]);
+        assert!(solved.is_solved::<Cycles>());
+
+        let a = Goal::Named("a".to_owned()).resolve::<Cycles>().unwrap();
+        assert_eq!(a.states, [0, 2, 4, 6, 8, 10]);
+        assert_eq!(a.description, "a");
+        assert!(a.contains(4) && !a.contains(5));
+        assert!(!a.is_solved::<Cycles>());
+
+        let scrambles = ["B", "A2 B", "", "A3 A3"].map(str::to_owned).to_vec();
+        let goal = Goal::Scrambles(scrambles);
+        assert_eq!(goal.name(), Some("scrambles"));
+        let set = goal.resolve::<Cycles>().unwrap();
+        assert_eq!(set.states, [0, 1, 5]);
+        assert_eq!(set.description, r#""B", "A2 B", "", "A3 A3""#);
+
+        let err = Goal::Named("b".to_owned()).resolve::<Cycles>().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            r#"cycles has no goal set called "b", only solved, a"#
+        );
+        assert!(matches!(
+            Goal::Scrambles(vec!["C".to_owned()]).resolve::<Cycles>(),
+            Err(GoalError::Notation(_))
+        ));
+    }
+}
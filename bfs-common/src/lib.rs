@@ -0,0 +1,25 @@
+pub mod antipodes;
+pub mod checkpoint;
+pub mod config;
+pub mod depths;
+pub mod dump;
+pub mod enumerate;
+pub mod estimate;
+pub mod expander;
+pub mod goal;
+pub mod limits;
+pub mod manifest;
+pub mod notation;
+pub mod plan;
+pub mod pruning;
+pub mod puzzle;
+pub mod report;
+pub mod runner;
+pub mod solutions;
+pub mod symmetry;
+pub mod tables;
+#[cfg(feature = "test-support")]
+pub mod testing;
+
+#[cfg(test)]
+mod test_util;
@@ -0,0 +1,161 @@
+//! Stops a search early, once it has finished a given depth or visited a given number of states.
+//!
+//! The search can't be interrupted from the callback, so it is stopped through its expander
+//! instead: once a [`Stop`] is set, a [`LimitedExpander`] expands every state to itself. Those
+//! states have all been visited, so the next depth is empty and the search ends the way it would
+//! at the end of the state space, with every depth up to the last finished one complete.
+
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use disk_based_bfs::expander::BfsExpander;
+
+/// When to stop a search. Both limits are checked each time a depth is finished, so a search
+/// never ends part of the way through a depth, and may go past `max_states` by most of a depth.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// The deepest depth to search.
+    pub max_depth: Option<usize>,
+
+    /// The number of states after which to stop, counting the classes instead when reducing by
+    /// symmetry.
+    pub max_states: Option<u64>,
+}
+
+impl Limits {
+    /// No limits, to search the whole state space.
+    pub const NONE: Self = Self {
+        max_depth: None,
+        max_states: None,
+    };
+
+    /// Why a search that has finished `depth` and visited `states` states should stop, if it
+    /// should.
+    pub fn reached(&self, depth: usize, states: u64) -> Option<StopReason> {
+        if self.max_depth.is_some_and(|max_depth| depth >= max_depth) {
+            Some(StopReason::MaxDepth(depth))
+        } else if self
+            .max_states
+            .is_some_and(|max_states| states >= max_states)
+        {
+            Some(StopReason::MaxStates(states))
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// The search finished the maximum depth.
+    MaxDepth(usize),
+
+    /// The search visited this many states, which is at least the maximum.
+    MaxStates(u64),
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MaxDepth(depth) => write!(f, "reached the maximum depth of {depth}"),
+            Self::MaxStates(states) => write!(f, "visited {states} states"),
+        }
+    }
+}
+
+/// A flag shared by every clone of a [`LimitedExpander`] and whatever decides to stop the search.
+#[derive(Debug, Clone, Default)]
+pub struct Stop(Arc<AtomicBool>);
+
+impl Stop {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Expands states with `expander` until `stop` is set, and to themselves after that.
+#[derive(Clone)]
+pub struct LimitedExpander<E> {
+    pub expander: E,
+    pub stop: Stop,
+}
+
+impl<E, const N: usize> BfsExpander<N> for LimitedExpander<E>
+where
+    E: BfsExpander<N>,
+{
+    fn expand(&mut self, node: u64, expanded_nodes: &mut [u64; N]) {
+        if self.stop.is_set() {
+            *expanded_nodes = [node; N];
+        } else {
+            self.expander.expand(node, expanded_nodes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Adding or subtracting 1 modulo 10.
+    #[derive(Clone)]
+    struct Expander;
+
+    impl BfsExpander<2> for Expander {
+        fn expand(&mut self, node: u64, expanded_nodes: &mut [u64; 2]) {
+            *expanded_nodes = [(node + 1) % 10, (node + 9) % 10];
+        }
+    }
+
+    #[test]
+    fn test_reached() {
+        assert_eq!(Limits::NONE.reached(100, u64::MAX), None);
+
+        let limits = Limits {
+            max_depth: Some(3),
+            max_states: Some(50),
+        };
+        assert_eq!(limits.reached(2, 49), None);
+        assert_eq!(limits.reached(3, 10), Some(StopReason::MaxDepth(3)));
+        assert_eq!(limits.reached(2, 60), Some(StopReason::MaxStates(60)));
+        assert_eq!(limits.reached(4, 60), Some(StopReason::MaxDepth(4)));
+        assert_eq!(
+            StopReason::MaxDepth(3).to_string(),
+            "reached the maximum depth of 3"
+        );
+    }
+
+    #[test]
+    fn test_limited_expander() {
+        let stop = Stop::new();
+        let mut expander = LimitedExpander {
+            expander: Expander,
+            stop: stop.clone(),
+        };
+        let mut clone = expander.clone();
+
+        let mut nodes = [0; 2];
+        expander.expand(0, &mut nodes);
+        assert_eq!(nodes, [1, 9]);
+
+        // Setting the flag stops every clone
+        stop.set();
+        expander.expand(0, &mut nodes);
+        assert_eq!(nodes, [0, 0]);
+        clone.expand(5, &mut nodes);
+        assert_eq!(nodes, [5, 5]);
+    }
+}
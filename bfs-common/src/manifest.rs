@@ -0,0 +1,392 @@
+//! A record of what produced the files of a search, written to each of its root directories.
+//!
+//! Nothing in the chunk and update files says which puzzle, metric or settings they belong to, so
+//! a search writes a `manifest.json` next to them when it starts, and again with the counts at
+//! each depth when it finishes, along with the revision of the code that ran it. A search refuses
+//! to start in a directory whose manifest belongs to a different one, so that two searches can't
+//! mix up each other's files.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{config::RunConfig, goal::GoalSet};
+
+#[derive(Debug, Error)]
+pub enum ManifestError {
+    #[error("{path}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+
+    #[error("{path}: {source}")]
+    Json {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+
+    #[error(
+        "{path} belongs to a different search: its {what} is {found}, not {expected}. Use other \
+         root directories, or remove its files, before starting a new search"
+    )]
+    Conflict {
+        path: PathBuf,
+        what: &'static str,
+        found: String,
+        expected: String,
+    },
+}
+
+/// The revision of the code, from `git describe`, or the package version if it wasn't built from
+/// a git checkout.
+pub const REVISION: &str = env!("BFS_REVISION");
+
+/// The path of the manifest in a root directory.
+pub fn path(directory: &Path) -> PathBuf {
+    directory.join("manifest.json")
+}
+
+/// The goal of manifests written before there were goal sets.
+fn solved() -> String {
+    "solved".to_owned()
+}
+
+/// What a puzzle's `run` searches, beyond the puzzle itself.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Variant {
+    /// The metric, for puzzles that can be searched in more than one.
+    pub metric: Option<String>,
+    /// The generators, for puzzles that can be searched with more than one set of them.
+    pub generators: Option<String>,
+    /// The checksum of the transposition tables used by the expander, if it uses any.
+    pub tables: Option<u32>,
+    /// Whether the moves only turn one way, as in UTM, so that the inverse of a move isn't always
+    /// a move. The depths of a search are then distances from the goal states but not to them.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub one_way: bool,
+}
+
+/// The settings a search passes to `BfsBuilder`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuilderSettings {
+    pub threads: usize,
+    pub chunk_size_bytes: usize,
+    pub update_memory: usize,
+    pub num_update_blocks: usize,
+    pub capacity_check_frequency: usize,
+    pub root_directories: Vec<PathBuf>,
+    pub chunk_root_pattern: Vec<usize>,
+    pub initial_memory_limit: usize,
+    pub available_disk_space_limit: u64,
+    pub update_array_threshold: u64,
+    pub use_locked_io: bool,
+    pub sync_filesystem: bool,
+    pub compute_checksums: bool,
+    pub use_compression: bool,
+}
+
+impl From<&RunConfig> for BuilderSettings {
+    fn from(config: &RunConfig) -> Self {
+        Self {
+            threads: config.threads,
+            chunk_size_bytes: config.chunk_size_bytes,
+            update_memory: config.update_memory,
+            num_update_blocks: config.num_update_blocks,
+            capacity_check_frequency: config.capacity_check_frequency,
+            root_directories: config.root_directories.clone(),
+            chunk_root_pattern: config.chunk_root_pattern.clone(),
+            initial_memory_limit: config.initial_memory_limit,
+            available_disk_space_limit: config.available_disk_space_limit,
+            update_array_threshold: config.update_array_threshold,
+            use_locked_io: config.use_locked_io,
+            sync_filesystem: config.sync_filesystem,
+            compute_checksums: config.compute_checksums,
+            use_compression: config.use_compression,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub puzzle: String,
+    pub run: String,
+    /// The [`REVISION`] of the code that last ran the search, which is empty for manifests written
+    /// before it was recorded.
+    #[serde(default)]
+    pub revision: String,
+    #[serde(flatten)]
+    pub variant: Variant,
+    /// The states the search starts from, as described by [`GoalSet::description`].
+    #[serde(default = "solved")]
+    pub goal: String,
+    /// Whether the search was reduced by symmetry.
+    pub symmetry: bool,
+    /// The number of states searched, which is the number of classes when reducing by symmetry.
+    pub state_size: u64,
+    pub settings: BuilderSettings,
+    pub started: DateTime<Utc>,
+    pub finished: Option<DateTime<Utc>>,
+    /// `counts[d]` is the number of states at depth `d`, once the search has finished.
+    #[serde(default)]
+    pub counts: Vec<u64>,
+    /// The number of classes at each depth, once a search reduced by symmetry has finished.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub classes: Option<Vec<u64>>,
+    /// Why the search was stopped before it reached every state, if it was.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stopped: Option<String>,
+}
+
+impl Manifest {
+    /// The manifest of a search that is starting now.
+    pub fn new(
+        puzzle: &str,
+        config: &RunConfig,
+        variant: Variant,
+        goal: &GoalSet,
+        symmetry: bool,
+        state_size: u64,
+    ) -> Self {
+        Self {
+            puzzle: puzzle.to_owned(),
+            run: config.name.clone(),
+            revision: REVISION.to_owned(),
+            variant,
+            goal: goal.description.clone(),
+            symmetry,
+            state_size,
+            settings: config.into(),
+            started: Utc::now(),
+            finished: None,
+            counts: Vec::new(),
+            classes: None,
+            stopped: None,
+        }
+    }
+
+    /// Reads the manifest at `path`, or returns `None` if there isn't one.
+    pub fn read(path: &Path) -> Result<Option<Self>, ManifestError> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(source) => {
+                return Err(ManifestError::Io {
+                    path: path.to_owned(),
+                    source,
+                })
+            }
+        };
+        serde_json::from_str(&contents)
+            .map(Some)
+            .map_err(|source| ManifestError::Json {
+                path: path.to_owned(),
+                source,
+            })
+    }
+
+    /// Checks that the manifest at `path` belongs to the same search as `new`. The search must be
+    /// of the same puzzle, metric, generators, goal set and state size. If it hasn't finished, it must also
+    /// lay out its files the same way, since `new` will carry on with them.
+    pub fn check(&self, path: &Path, new: &Self) -> Result<(), ManifestError> {
+        let conflict = |what, found: String, expected: String| {
+            if found == expected {
+                Ok(())
+            } else {
+                Err(ManifestError::Conflict {
+                    path: path.to_owned(),
+                    what,
+                    found,
+                    expected,
+                })
+            }
+        };
+        let optional = |value: &Option<String>| value.clone().unwrap_or_else(|| "none".to_owned());
+
+        conflict("puzzle", self.puzzle.clone(), new.puzzle.clone())?;
+        conflict("run", self.run.clone(), new.run.clone())?;
+        conflict(
+            "metric",
+            optional(&self.variant.metric),
+            optional(&new.variant.metric),
+        )?;
+        conflict(
+            "generators",
+            optional(&self.variant.generators),
+            optional(&new.variant.generators),
+        )?;
+        conflict("goal", self.goal.clone(), new.goal.clone())?;
+        conflict(
+            "symmetry",
+            self.symmetry.to_string(),
+            new.symmetry.to_string(),
+        )?;
+        conflict(
+            "state size",
+            self.state_size.to_string(),
+            new.state_size.to_string(),
+        )?;
+
+        if self.finished.is_none() {
+            let (found, expected) = (&self.settings, &new.settings);
+            conflict(
+                "chunk size",
+                found.chunk_size_bytes.to_string(),
+                expected.chunk_size_bytes.to_string(),
+            )?;
+            conflict(
+                "root directories",
+                format!("{:?}", found.root_directories),
+                format!("{:?}", expected.root_directories),
+            )?;
+            conflict(
+                "chunk root pattern",
+                format!("{:?}", found.chunk_root_pattern),
+                format!("{:?}", expected.chunk_root_pattern),
+            )?;
+            conflict(
+                "compression",
+                found.use_compression.to_string(),
+                expected.use_compression.to_string(),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the manifest to every root directory of the search, creating them if needed.
+    pub fn write(&self) -> Result<(), ManifestError> {
+        for directory in &self.settings.root_directories {
+            let path = path(directory);
+            let json =
+                serde_json::to_string_pretty(self).map_err(|source| ManifestError::Json {
+                    path: path.clone(),
+                    source,
+                })? + "\n";
+            let tmp_path = path.with_extension("json.tmp");
+            let io_err = |path: &Path| {
+                let path = path.to_owned();
+                move |source| ManifestError::Io { path, source }
+            };
+
+            fs::create_dir_all(directory).map_err(io_err(directory))?;
+            fs::write(&tmp_path, &json).map_err(io_err(&tmp_path))?;
+            fs::rename(&tmp_path, &path).map_err(io_err(&path))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    fn settings(root_directories: Vec<PathBuf>) -> BuilderSettings {
+        BuilderSettings {
+            threads: 4,
+            chunk_size_bytes: 1 << 20,
+            update_memory: 1 << 30,
+            num_update_blocks: 64,
+            capacity_check_frequency: 256,
+            chunk_root_pattern: (0..root_directories.len()).collect(),
+            root_directories,
+            initial_memory_limit: 1 << 20,
+            available_disk_space_limit: 1 << 30,
+            update_array_threshold: 1 << 20,
+            use_locked_io: false,
+            sync_filesystem: true,
+            compute_checksums: true,
+            use_compression: true,
+        }
+    }
+
+    #[test]
+    fn test_write_and_check() {
+        let dir = temp_dir("manifest-check");
+        let roots = vec![dir.join("a"), dir.join("b")];
+
+        let manifest = Manifest {
+            puzzle: "megaminx-U-R".to_owned(),
+            run: "megaminx-U-R".to_owned(),
+            revision: REVISION.to_owned(),
+            variant: Variant {
+                metric: Some("Htm".to_owned()),
+                generators: None,
+                tables: Some(0x1234_5678),
+                one_way: false,
+            },
+            goal: "solved".to_owned(),
+            symmetry: false,
+            state_size: 100,
+            settings: settings(roots.clone()),
+            started: Utc::now(),
+            finished: None,
+            counts: Vec::new(),
+            classes: None,
+            stopped: None,
+        };
+        manifest.write().unwrap();
+        for root in &roots {
+            let read = Manifest::read(&path(root)).unwrap().unwrap();
+            assert_eq!(read, manifest);
+            read.check(&path(root), &manifest).unwrap();
+        }
+        assert_eq!(Manifest::read(&path(&dir)).unwrap(), None);
+
+        // The same directories searched in another metric, or by another puzzle
+        let qtm = Manifest {
+            variant: Variant {
+                metric: Some("Qtm".to_owned()),
+                ..manifest.variant.clone()
+            },
+            ..manifest.clone()
+        };
+        assert!(matches!(
+            manifest.check(&path(&roots[0]), &qtm),
+            Err(ManifestError::Conflict { what: "metric", .. })
+        ));
+        let kilominx = Manifest {
+            puzzle: "kilominx-6gen-permutation-only".to_owned(),
+            ..manifest.clone()
+        };
+        assert!(matches!(
+            manifest.check(&path(&roots[0]), &kilominx),
+            Err(ManifestError::Conflict { what: "puzzle", .. })
+        ));
+        let auf = Manifest {
+            goal: "auf".to_owned(),
+            ..manifest.clone()
+        };
+        assert!(matches!(
+            manifest.check(&path(&roots[0]), &auf),
+            Err(ManifestError::Conflict { what: "goal", .. })
+        ));
+
+        // The chunk size only matters while there are files to carry on with
+        let rechunked = Manifest {
+            settings: BuilderSettings {
+                chunk_size_bytes: 1 << 21,
+                ..manifest.settings.clone()
+            },
+            ..manifest.clone()
+        };
+        assert!(matches!(
+            manifest.check(&path(&roots[0]), &rechunked),
+            Err(ManifestError::Conflict {
+                what: "chunk size",
+                ..
+            })
+        ));
+        let finished = Manifest {
+            finished: Some(Utc::now()),
+            counts: vec![1, 4, 10],
+            ..manifest.clone()
+        };
+        finished.check(&path(&roots[0]), &rechunked).unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,643 @@
+//! Move sequences in the usual notation.
+//!
+//! [`Move`] is a power of one of the moves a [`Puzzle`] searches with: the name of a move from
+//! [`Puzzle::MOVES`], followed by an optional power, e.g. `U`, `U2` and `U'`, or `U2'` for a move
+//! of order 5. [`CubeMove`] and [`MinxMove`] are any move of a cube or a megaminx in WCA-style
+//! notation, which piece-level and coordinate-level puzzles make through [`Turn`].
+
+use std::{fmt, str::FromStr};
+
+use thiserror::Error;
+
+use crate::puzzle::Puzzle;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum NotationError {
+    #[error("unknown move {token:?} at position {position}")]
+    UnknownMove { token: String, position: usize },
+
+    #[error("move {token:?} at position {position} can't be made on this puzzle")]
+    Unsupported { token: String, position: usize },
+}
+
+/// The whitespace-separated tokens of `s`, with their byte offsets.
+fn tokens(s: &str) -> impl Iterator<Item = (usize, &str)> {
+    // Each token is a subslice of `s`
+    s.split_whitespace()
+        .map(move |token| (token.as_ptr() as usize - s.as_ptr() as usize, token))
+}
+
+/// A power of one of the moves in [`Puzzle::MOVES`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Move {
+    /// Index into [`Puzzle::MOVES`].
+    pub generator: usize,
+    /// Between 1 and one less than the order of the generator.
+    pub power: usize,
+}
+
+impl Move {
+    pub fn apply<P: Puzzle>(self, puzzle: &mut P) {
+        for _ in 0..self.power {
+            puzzle.do_move(self.generator);
+        }
+    }
+
+    pub fn inverse<P: Puzzle>(self) -> Self {
+        Self {
+            generator: self.generator,
+            power: P::ORDERS[self.generator] - self.power,
+        }
+    }
+
+    pub fn name<P: Puzzle>(self) -> String {
+        let name = P::MOVES[self.generator];
+        let order = P::ORDERS[self.generator];
+        if order == 2 || self.power == 1 {
+            name.to_owned()
+        } else if self.power == order - 1 {
+            format!("{name}'")
+        } else if 2 * self.power <= order {
+            format!("{name}{}", self.power)
+        } else {
+            format!("{name}{}'", order - self.power)
+        }
+    }
+
+    /// Parses a move named as in [`Puzzle::MOVES`], or spelled another way that means the same
+    /// cube move, e.g. `Rw` for `r`.
+    fn parse<P: Puzzle>(token: &str) -> Option<Self> {
+        Self::parse_exact::<P>(token).or_else(|| {
+            let canonical = token.parse::<CubeMove>().ok()?.to_string();
+            (canonical != token)
+                .then(|| Self::parse_exact::<P>(&canonical))
+                .flatten()
+        })
+    }
+
+    fn parse_exact<P: Puzzle>(token: &str) -> Option<Self> {
+        (0..P::MOVES.len()).find_map(|generator| {
+            let suffix = token.strip_prefix(P::MOVES[generator])?;
+            let order = P::ORDERS[generator];
+            let (amount, inverse) = match suffix.strip_suffix('\'') {
+                Some(amount) => (amount, true),
+                None => (suffix, false),
+            };
+            let amount = match amount {
+                "" => 1,
+                amount if !amount.starts_with('0') => amount.parse::<usize>().ok()?,
+                _ => return None,
+            };
+            if amount >= order {
+                return None;
+            }
+
+            let power = if inverse { order - amount } else { amount };
+            Some(Self { generator, power })
+        })
+    }
+}
+
+/// Parses a whitespace-separated sequence of moves.
+pub fn parse<P: Puzzle>(s: &str) -> Result<Vec<Move>, NotationError> {
+    tokens(s)
+        .map(|(position, token)| {
+            Move::parse::<P>(token).ok_or_else(|| NotationError::UnknownMove {
+                token: token.to_owned(),
+                position,
+            })
+        })
+        .collect()
+}
+
+pub fn format<P: Puzzle>(moves: &[Move]) -> String {
+    moves
+        .iter()
+        .map(|mv| mv.name::<P>())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The sequence that undoes `moves`.
+pub fn invert<P: Puzzle>(moves: &[Move]) -> Vec<Move> {
+    moves.iter().rev().map(|mv| mv.inverse::<P>()).collect()
+}
+
+/// The state reached by applying `moves` to the solved state.
+pub fn apply<P: Puzzle>(moves: &[Move]) -> P {
+    let mut puzzle = P::solved();
+    for mv in moves {
+        mv.apply(&mut puzzle);
+    }
+    puzzle
+}
+
+/// A single move that takes the state `from` to the state `to`, if there is one.
+pub fn find_move<P: Puzzle>(from: u64, to: u64) -> Option<Move> {
+    let mut puzzle = P::from_coord(from);
+    (0..P::MOVES.len()).find_map(|generator| {
+        let mut found = None;
+        // Apply the generator a whole cycle, so `puzzle` ends up back at `from`
+        for power in 1..=P::ORDERS[generator] {
+            puzzle.do_move(generator);
+            if found.is_none() && power < P::ORDERS[generator] && puzzle.encode() == to {
+                found = Some(Move { generator, power });
+            }
+        }
+        found
+    })
+}
+
+/// A token that isn't a move in the notation it was parsed as.
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("invalid move {0:?}")]
+pub struct InvalidMove(pub String);
+
+/// Parses one power of a move, `""`, `"2"`, `"'"` or `"2'"`, into a number of clockwise turns of
+/// a move of order `order`.
+fn parse_power(suffix: &str, order: u8) -> Option<u8> {
+    match suffix {
+        "" => Some(1),
+        "'" => Some(order - 1),
+        "2" => Some(2),
+        "2'" => Some(order - 2),
+        _ => None,
+    }
+}
+
+fn format_power(f: &mut fmt::Formatter<'_>, power: u8, order: u8) -> fmt::Result {
+    match power {
+        1 => Ok(()),
+        2 => write!(f, "2"),
+        _ if power == order - 1 => write!(f, "'"),
+        _ => write!(f, "2'"),
+    }
+}
+
+/// A face of a cube.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Face {
+    U,
+    L,
+    F,
+    R,
+    B,
+    D,
+}
+
+impl Face {
+    const ALL: [Self; 6] = [Self::U, Self::L, Self::F, Self::R, Self::B, Self::D];
+
+    fn letter(self) -> char {
+        b"ULFRBD"[self as usize] as char
+    }
+}
+
+/// The layers of a cube turned by a move, counted from the face it turns with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Layers {
+    /// The outer `n` layers: `R` for 1, `Rw` or `r` for 2, and `3Rw` for 3.
+    Wide(u8),
+    /// Only layer `n`, which is at least 2, e.g. `2R`.
+    Slice(u8),
+    /// The middle layer of an odd cube, turning with `L` (`M`), `D` (`E`) or `F` (`S`).
+    Middle,
+}
+
+impl Layers {
+    pub const OUTER: Self = Self::Wide(1);
+}
+
+/// A move of an NxN cube in WCA notation, with the lowercase (`r`), SiGN slice (`2R`) and middle
+/// slice (`M`, `E`, `S`) moves that are usual for the subgroups searched here.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CubeMove {
+    pub face: Face,
+    pub layers: Layers,
+    /// Clockwise quarter turns, between 1 and 3.
+    pub power: u8,
+}
+
+impl FromStr for CubeMove {
+    type Err = InvalidMove;
+
+    fn from_str(token: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidMove(token.to_owned());
+
+        let digits = token.len() - token.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let (count, rest) = token.split_at(digits);
+        let count = match count {
+            "" => None,
+            count if !count.starts_with('0') => Some(count.parse::<u8>().map_err(|_| invalid())?),
+            _ => return Err(invalid()),
+        };
+
+        let mut chars = rest.chars();
+        let letter = chars.next().ok_or_else(invalid)?;
+        let rest = chars.as_str();
+        let (face, layers, suffix) = match letter {
+            'M' | 'E' | 'S' if count.is_none() => {
+                let face = match letter {
+                    'M' => Face::L,
+                    'E' => Face::D,
+                    _ => Face::F,
+                };
+                (face, Layers::Middle, rest)
+            }
+            'u' | 'l' | 'f' | 'r' | 'b' | 'd' if count.is_none() => {
+                let face = Face::ALL
+                    .into_iter()
+                    .find(|face| face.letter() == letter.to_ascii_uppercase())
+                    .unwrap();
+                (face, Layers::Wide(2), rest)
+            }
+            _ => {
+                let face = Face::ALL
+                    .into_iter()
+                    .find(|face| face.letter() == letter)
+                    .ok_or_else(invalid)?;
+                match (rest.strip_prefix('w'), count) {
+                    (Some(rest), Some(count)) if count >= 2 => (face, Layers::Wide(count), rest),
+                    (Some(rest), None) => (face, Layers::Wide(2), rest),
+                    (None, Some(count)) if count >= 2 => (face, Layers::Slice(count), rest),
+                    (None, None) => (face, Layers::OUTER, rest),
+                    _ => return Err(invalid()),
+                }
+            }
+        };
+
+        let power = parse_power(suffix, 4).ok_or_else(invalid)?;
+        Ok(Self {
+            face,
+            layers,
+            power,
+        })
+    }
+}
+
+impl fmt::Display for CubeMove {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let letter = self.face.letter();
+        match self.layers {
+            Layers::Wide(1) => write!(f, "{letter}")?,
+            Layers::Wide(2) => write!(f, "{}", letter.to_ascii_lowercase())?,
+            Layers::Wide(n) => write!(f, "{n}{letter}w")?,
+            Layers::Slice(n) => write!(f, "{n}{letter}")?,
+            Layers::Middle => write!(
+                f,
+                "{}",
+                match self.face {
+                    Face::L => 'M',
+                    Face::D => 'E',
+                    _ => 'S',
+                }
+            )?,
+        }
+        format_power(f, self.power, 4)
+    }
+}
+
+/// A face of a megaminx, named as in the usual notation for kilominx and megaminx moves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MinxFace {
+    U,
+    F,
+    R,
+    L,
+    BR,
+    BL,
+    DR,
+    DL,
+    DBR,
+    DBL,
+    B,
+    D,
+}
+
+impl MinxFace {
+    const ALL: [Self; 12] = [
+        Self::U,
+        Self::F,
+        Self::R,
+        Self::L,
+        Self::BR,
+        Self::BL,
+        Self::DR,
+        Self::DL,
+        Self::DBR,
+        Self::DBL,
+        Self::B,
+        Self::D,
+    ];
+
+    fn name(self) -> &'static str {
+        [
+            "U", "F", "R", "L", "BR", "BL", "DR", "DL", "DBR", "DBL", "B", "D",
+        ][self as usize]
+    }
+}
+
+/// A turn of one face of a megaminx or kilominx, e.g. `U`, `R2`, `BR'` or `BL2'`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MinxMove {
+    pub face: MinxFace,
+    /// Clockwise fifth turns, between 1 and 4.
+    pub power: u8,
+}
+
+impl FromStr for MinxMove {
+    type Err = InvalidMove;
+
+    fn from_str(token: &str) -> Result<Self, Self::Err> {
+        MinxFace::ALL
+            .into_iter()
+            .find_map(|face| {
+                let power = parse_power(token.strip_prefix(face.name())?, 5)?;
+                Some(Self { face, power })
+            })
+            .ok_or_else(|| InvalidMove(token.to_owned()))
+    }
+}
+
+impl fmt::Display for MinxMove {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.face.name())?;
+        format_power(f, self.power, 5)
+    }
+}
+
+/// A puzzle, at the piece or coordinate level, that can make moves written as `M`.
+pub trait Turn<M> {
+    /// Makes `mv` and returns `true`, or returns `false` without changing anything if it isn't
+    /// one of the puzzle's moves.
+    fn turn(&mut self, mv: M) -> bool;
+}
+
+/// Parses a whitespace-separated sequence of moves written as `M`.
+pub fn parse_moves<M: FromStr>(s: &str) -> Result<Vec<M>, NotationError> {
+    tokens(s)
+        .map(|(position, token)| {
+            token.parse().map_err(|_| NotationError::UnknownMove {
+                token: token.to_owned(),
+                position,
+            })
+        })
+        .collect()
+}
+
+/// Makes the moves of `alg` on `puzzle`, stopping at the first one it can't parse or make.
+pub fn turn_alg<M: FromStr, T: Turn<M>>(puzzle: &mut T, alg: &str) -> Result<(), NotationError> {
+    for (position, token) in tokens(alg) {
+        let mv = token.parse().map_err(|_| NotationError::UnknownMove {
+            token: token.to_owned(),
+            position,
+        })?;
+        if !puzzle.turn(mv) {
+            return Err(NotationError::Unsupported {
+                token: token.to_owned(),
+                position,
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Moves on two independent cycles, `A` of order 5 and `B2` of order 2.
+    #[derive(Debug)]
+    struct Cycles(u64, u64);
+
+    impl Puzzle for Cycles {
+        const ID: &'static str = "cycles";
+        const STATE_SIZE: u64 = 10;
+        const MOVES: &'static [&'static str] = &["A", "B2"];
+        const ORDERS: &'static [usize] = &[5, 2];
+
+        fn solved() -> Self {
+            Self(0, 0)
+        }
+
+        fn encode(&self) -> u64 {
+            self.0 * 2 + self.1
+        }
+
+        fn decode(&mut self, coord: u64) {
+            *self = Self(coord / 2, coord % 2);
+        }
+
+        fn do_move(&mut self, mv: usize) {
+            match mv {
+                0 => self.0 = (self.0 + 1) % 5,
+                _ => self.1 = (self.1 + 1) % 2,
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_and_format() {
+        let moves = parse::<Cycles>(" A2' B2  A' A A2").unwrap();
+        assert_eq!(
+            moves.iter().map(|mv| mv.power).collect::<Vec<_>>(),
+            [3, 1, 4, 1, 2]
+        );
+        assert_eq!(format::<Cycles>(&moves), "A2' B2 A' A A2");
+        assert_eq!(apply::<Cycles>(&moves).encode(), 1);
+
+        assert_eq!(
+            parse::<Cycles>("A B2 C"),
+            Err(NotationError::UnknownMove {
+                token: "C".to_owned(),
+                position: 5,
+            })
+        );
+        assert!(parse::<Cycles>("A5").is_err());
+        assert!(parse::<Cycles>("A02").is_err());
+        assert!(parse::<Cycles>("B").is_err());
+    }
+
+    /// Two generators spelled the way 3x3 `<U, r>` spells them.
+    #[derive(Debug)]
+    struct Ur(u64, u64);
+
+    impl Puzzle for Ur {
+        const ID: &'static str = "ur";
+        const STATE_SIZE: u64 = 16;
+        const MOVES: &'static [&'static str] = &["U", "r"];
+        const ORDERS: &'static [usize] = &[4, 4];
+
+        fn solved() -> Self {
+            Self(0, 0)
+        }
+
+        fn encode(&self) -> u64 {
+            self.0 * 4 + self.1
+        }
+
+        fn decode(&mut self, coord: u64) {
+            *self = Self(coord / 4, coord % 4);
+        }
+
+        fn do_move(&mut self, mv: usize) {
+            match mv {
+                0 => self.0 = (self.0 + 1) % 4,
+                _ => self.1 = (self.1 + 1) % 4,
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_other_spellings() {
+        let moves = parse::<Ur>("Rw2 U' Rw' r").unwrap();
+        assert_eq!(format::<Ur>(&moves), "r2 U' r' r");
+        assert!(parse::<Ur>("2Rw").is_ok());
+        assert!(parse::<Ur>("R").is_err());
+    }
+
+    #[test]
+    fn test_cube_moves() {
+        let canonical = |token: &str| token.parse::<CubeMove>().map(|mv| mv.to_string());
+        for (token, expected) in [
+            ("R", "R"),
+            ("U2", "U2"),
+            ("D'", "D'"),
+            ("F2'", "F2"),
+            ("r", "r"),
+            ("Rw'", "r'"),
+            ("2Rw2", "r2"),
+            ("3Fw", "3Fw"),
+            ("2R", "2R"),
+            ("3L'", "3L'"),
+            ("M2", "M2"),
+            ("E'", "E'"),
+            ("S", "S"),
+        ] {
+            assert_eq!(canonical(token), Ok(expected.to_owned()), "{token}");
+        }
+
+        assert_eq!(
+            "2R'".parse(),
+            Ok(CubeMove {
+                face: Face::R,
+                layers: Layers::Slice(2),
+                power: 3,
+            })
+        );
+        assert_eq!(
+            "M".parse::<CubeMove>().map(|mv| (mv.face, mv.layers)),
+            Ok((Face::L, Layers::Middle))
+        );
+
+        for token in [
+            "", "Q", "R3", "R'2", "0R", "1R", "01R", "3r", "2M", "rw", "Rw w", "R2''",
+        ] {
+            assert_eq!(
+                token.parse::<CubeMove>(),
+                Err(InvalidMove(token.to_owned())),
+                "{token}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_minx_moves() {
+        let moves = parse_moves::<MinxMove>("U2' R2 BR BL' DBR2 D").unwrap();
+        assert_eq!(
+            moves
+                .iter()
+                .map(|mv| (mv.face, mv.power))
+                .collect::<Vec<_>>(),
+            [
+                (MinxFace::U, 3),
+                (MinxFace::R, 2),
+                (MinxFace::BR, 1),
+                (MinxFace::BL, 4),
+                (MinxFace::DBR, 2),
+                (MinxFace::D, 1),
+            ]
+        );
+        assert_eq!(
+            moves.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            ["U2'", "R2", "BR", "BL'", "DBR2", "D"]
+        );
+
+        assert_eq!(
+            parse_moves::<MinxMove>("U R3 R"),
+            Err(NotationError::UnknownMove {
+                token: "R3".to_owned(),
+                position: 2,
+            })
+        );
+        assert!("BD".parse::<MinxMove>().is_err());
+    }
+
+    /// A cube that only turns U, counting quarter turns.
+    struct UCube(u8);
+
+    impl Turn<CubeMove> for UCube {
+        fn turn(&mut self, mv: CubeMove) -> bool {
+            if (mv.face, mv.layers) != (Face::U, Layers::OUTER) {
+                return false;
+            }
+            self.0 = (self.0 + mv.power) % 4;
+            true
+        }
+    }
+
+    #[test]
+    fn test_turn_alg() {
+        let mut cube = UCube(0);
+        turn_alg(&mut cube, "U U2' U'").unwrap();
+        assert_eq!(cube.0, 2);
+
+        assert_eq!(
+            turn_alg::<CubeMove, _>(&mut cube, "U  R U"),
+            Err(NotationError::Unsupported {
+                token: "R".to_owned(),
+                position: 3,
+            })
+        );
+        assert_eq!(cube.0, 3);
+        assert_eq!(
+            turn_alg::<CubeMove, _>(&mut cube, "U x"),
+            Err(NotationError::UnknownMove {
+                token: "x".to_owned(),
+                position: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn test_invert() {
+        let moves = parse::<Cycles>("A B2 A2").unwrap();
+        assert_eq!(format::<Cycles>(&invert::<Cycles>(&moves)), "A2' B2 A'");
+
+        let mut puzzle = apply::<Cycles>(&moves);
+        for mv in invert::<Cycles>(&moves) {
+            mv.apply(&mut puzzle);
+        }
+        assert_eq!(puzzle.encode(), 0);
+    }
+
+    #[test]
+    fn test_find_move() {
+        assert_eq!(
+            find_move::<Cycles>(2, 8),
+            Some(Move {
+                generator: 0,
+                power: 3,
+            })
+        );
+        assert_eq!(
+            find_move::<Cycles>(2, 3),
+            Some(Move {
+                generator: 1,
+                power: 1,
+            })
+        );
+        assert_eq!(find_move::<Cycles>(2, 2), None);
+        assert_eq!(find_move::<Cycles>(2, 9), None);
+    }
+}
@@ -0,0 +1,277 @@
+//! Derives chunk and update-block sizes from the size of the state space, so they don't have to be
+//! worked out by hand for each puzzle, and reports how much memory and disk space a run takes.
+
+use std::{fmt, io, path::Path};
+
+use thiserror::Error;
+
+use crate::config::RunConfig;
+
+/// Upper bound on how far the planner looks past the smallest possible chunk count. Chunks are
+/// never planned at less than half of the largest size that fits in memory.
+const MAX_CHUNK_COUNT_FACTOR: u64 = 2;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum PlanError {
+    #[error("memory_budget ({memory_budget} bytes) leaves no room for chunks after update_memory ({update_memory} bytes)")]
+    NoChunkMemory {
+        memory_budget: usize,
+        update_memory: usize,
+    },
+
+    #[error("state size {state_size} is not a whole number of bytes")]
+    UnevenStateSize { state_size: u64 },
+
+    #[error("no multiple of {step} chunks divides {state_bytes} bytes into chunks of at most {max_chunk_size_bytes} bytes")]
+    NoEvenSplit {
+        state_bytes: u64,
+        step: u64,
+        max_chunk_size_bytes: u64,
+    },
+}
+
+/// The inputs to [`Planner::plan`].
+#[derive(Debug, Clone, Copy)]
+pub struct Planner {
+    pub state_size: u64,
+    pub threads: usize,
+    /// Total memory available to the search, including the update arrays.
+    pub memory_budget: usize,
+    pub update_memory: usize,
+    /// Length of the chunk root pattern, i.e. how many chunks it takes to cycle through the disks.
+    pub disks: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Plan {
+    pub num_chunks: u64,
+    pub chunk_size_bytes: usize,
+    pub num_update_blocks: usize,
+    pub update_array_threshold: u64,
+}
+
+impl Planner {
+    /// Picks the smallest number of chunks that
+    /// - is a multiple of both the thread count and the number of disks, so that every thread has
+    ///   work and chunks are spread evenly over the disks,
+    /// - divides the state space evenly, and
+    /// - fits one chunk per thread in the memory left over after the update arrays.
+    pub fn plan(&self) -> Result<Plan, PlanError> {
+        if !self.state_size.is_multiple_of(8) {
+            return Err(PlanError::UnevenStateSize {
+                state_size: self.state_size,
+            });
+        }
+
+        let chunk_memory = self.memory_budget.checked_sub(self.update_memory);
+        let max_chunk_size_bytes = match chunk_memory {
+            Some(m) if m >= self.threads => (m / self.threads) as u64,
+            _ => {
+                return Err(PlanError::NoChunkMemory {
+                    memory_budget: self.memory_budget,
+                    update_memory: self.update_memory,
+                });
+            }
+        };
+
+        let state_bytes = self.state_size / 8;
+        let step = lcm(self.threads as u64, self.disks as u64);
+        let min_chunks = state_bytes.div_ceil(max_chunk_size_bytes).div_ceil(step) * step;
+
+        let num_chunks = (min_chunks..=min_chunks * MAX_CHUNK_COUNT_FACTOR)
+            .step_by(step as usize)
+            .find(|&n| state_bytes.is_multiple_of(n))
+            .ok_or(PlanError::NoEvenSplit {
+                state_bytes,
+                step,
+                max_chunk_size_bytes,
+            })?;
+
+        let chunk_size_bytes = state_bytes / num_chunks;
+
+        Ok(Plan {
+            num_chunks,
+            chunk_size_bytes: chunk_size_bytes as usize,
+            num_update_blocks: 2 * num_chunks as usize * self.threads,
+            update_array_threshold: chunk_size_bytes,
+        })
+    }
+}
+
+fn lcm(a: u64, b: u64) -> u64 {
+    let (mut x, mut y) = (a, b);
+    while y != 0 {
+        (x, y) = (y, x % y);
+    }
+    a / x * b
+}
+
+/// Memory and disk usage of a run, printed before the search starts.
+pub struct Footprint<'a> {
+    config: &'a RunConfig,
+    state_size: u64,
+}
+
+impl<'a> Footprint<'a> {
+    pub fn new(config: &'a RunConfig, state_size: u64) -> Self {
+        Self { config, state_size }
+    }
+}
+
+impl fmt::Display for Footprint<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let config = self.config;
+        let state_bytes = self.state_size.div_ceil(8);
+        let chunk_size_bytes = config.chunk_size_bytes as u64;
+        let num_chunks = state_bytes.div_ceil(chunk_size_bytes);
+        let chunk_memory = config.threads as u64 * chunk_size_bytes;
+
+        writeln!(
+            f,
+            "{} states in {num_chunks} chunks of {} using {} threads",
+            self.state_size,
+            Bytes(chunk_size_bytes),
+            config.threads,
+        )?;
+        writeln!(
+            f,
+            "memory: {} for chunks + {} for updates in {} blocks = {}",
+            Bytes(chunk_memory),
+            Bytes(config.update_memory as u64),
+            config.num_update_blocks,
+            Bytes(chunk_memory + config.update_memory as u64),
+        )?;
+
+        let mut chunks_per_root = vec![0u64; config.root_directories.len()];
+        for chunk_idx in 0..num_chunks {
+            chunks_per_root[config.chunk_root_idx(chunk_idx as usize)] += 1;
+        }
+
+        write!(f, "disk, per copy of the state space:")?;
+        for (root, chunks) in config.root_directories.iter().zip(chunks_per_root) {
+            write!(
+                f,
+                "\n    {}: {chunks} chunks, {}",
+                root.display(),
+                Bytes(chunks * chunk_size_bytes),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The free space in the file system of `directory` that isn't reserved for the superuser.
+#[cfg(unix)]
+pub fn available_space(directory: &Path) -> io::Result<u64> {
+    use std::{ffi::CString, mem::MaybeUninit, os::unix::ffi::OsStrExt as _};
+
+    let path = CString::new(directory.as_os_str().as_bytes()).map_err(io::Error::other)?;
+    let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `path` is a valid C string, and `stat` is large enough for the result.
+    if unsafe { libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: `statvfs` succeeded, so it filled in `stat`.
+    let stat = unsafe { stat.assume_init() };
+    // The field types differ between platforms
+    #[allow(clippy::unnecessary_cast)]
+    let available = stat.f_bavail as u64 * stat.f_frsize as u64;
+    Ok(available)
+}
+
+/// Other platforms aren't checked.
+#[cfg(not(unix))]
+pub fn available_space(_directory: &Path) -> io::Result<u64> {
+    Ok(u64::MAX)
+}
+
+pub(crate) struct Bytes(pub(crate) u64);
+
+impl fmt::Display for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+        let mut value = self.0 as f64;
+        let mut unit = 0;
+        while value >= 1024.0 && unit < UNITS.len() - 1 {
+            value /= 1024.0;
+            unit += 1;
+        }
+
+        if unit == 0 {
+            write!(f, "{} B", self.0)
+        } else {
+            write!(f, "{value:.2} {}", UNITS[unit])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_matches_hand_computed() {
+        // 3x3-2-color-ufb: 48 chunks of 303118200 bytes
+        let planner = Planner {
+            state_size: 116397388800,
+            threads: 48,
+            memory_budget: 128 << 30,
+            update_memory: 112 << 30,
+            disks: 4,
+        };
+        assert_eq!(
+            planner.plan(),
+            Ok(Plan {
+                num_chunks: 48,
+                chunk_size_bytes: 303118200,
+                num_update_blocks: 2 * 48 * 48,
+                update_array_threshold: 303118200,
+            })
+        );
+    }
+
+    #[test]
+    fn test_plan_divides_evenly() {
+        let planner = Planner {
+            state_size: 7999675084800,
+            threads: 48,
+            memory_budget: 136 << 30,
+            update_memory: 112 << 30,
+            disks: 7,
+        };
+        let plan = planner.plan().unwrap();
+        assert_eq!(plan.num_chunks % (48 * 7), 0);
+        assert_eq!(
+            plan.num_chunks * plan.chunk_size_bytes as u64 * 8,
+            7999675084800
+        );
+        assert!(plan.chunk_size_bytes * 48 <= 24 << 30);
+    }
+
+    #[test]
+    fn test_plan_errors() {
+        let planner = Planner {
+            state_size: 479001600,
+            threads: 1,
+            memory_budget: 1 << 30,
+            update_memory: 2 << 30,
+            disks: 1,
+        };
+        assert!(matches!(
+            planner.plan(),
+            Err(PlanError::NoChunkMemory { .. })
+        ));
+
+        let planner = Planner {
+            state_size: 479001601,
+            memory_budget: 3 << 30,
+            ..planner
+        };
+        assert!(matches!(
+            planner.plan(),
+            Err(PlanError::UnevenStateSize { .. })
+        ));
+    }
+}
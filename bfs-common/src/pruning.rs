@@ -0,0 +1,460 @@
+//! Pruning tables exported from a depth table, and an IDA* search that uses them.
+//!
+//! A pruning table stores one entry per state, indexed by the same coordinate as the search, in
+//! one of two formats:
+//! - [`PruningFormat::Mod3`]: the depth modulo 3 in 2 bits, the same as the depth table. Together
+//!   with the exact depth of one state, this gives the exact depth of each of its neighbours.
+//! - [`PruningFormat::Capped`]: the depth in 4 bits, capped at a maximum. This gives a lower bound
+//!   on the depth of any state on its own.
+//!
+//! As in the depth table, an entry of 0 means the state wasn't reached, and other entries store
+//! one more than the value. The depths are distances from the goal states, so they are only lower
+//! bounds on the length of a solution if the inverse of every move is also a move.
+
+use std::{
+    fmt,
+    fs::{self, File},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use disk_based_bfs::expander::BfsExpander;
+use memmap2::{Mmap, MmapMut};
+
+use crate::{depths::DepthTable, tables::TableFileError};
+
+const MAGIC: [u8; 8] = *b"BFSPRUNE";
+
+/// Version of the file layout. Bump this whenever the layout changes.
+const VERSION: u32 = 1;
+
+/// Magic, version, format, cap, padding and state size.
+const HEADER_LEN: usize = 24;
+
+/// The largest cap of a [`PruningFormat::Capped`] table, so that entries fit in 4 bits.
+pub const MAX_CAP: u8 = 14;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PruningFormat {
+    /// The depth modulo 3, in 2 bits per state.
+    Mod3,
+
+    /// The depth capped at the given value, in 4 bits per state.
+    Capped(u8),
+}
+
+impl PruningFormat {
+    fn bits(self) -> u64 {
+        match self {
+            Self::Mod3 => 2,
+            Self::Capped(_) => 4,
+        }
+    }
+
+    fn data_len(self, state_size: u64) -> usize {
+        (state_size * self.bits()).div_ceil(8) as usize
+    }
+
+    fn header(self, state_size: u64) -> [u8; HEADER_LEN] {
+        let mut header = [0; HEADER_LEN];
+        header[..8].copy_from_slice(&MAGIC);
+        header[8..12].copy_from_slice(&VERSION.to_le_bytes());
+        (header[12], header[13]) = match self {
+            Self::Mod3 => (0, 0),
+            Self::Capped(cap) => (1, cap),
+        };
+        header[16..24].copy_from_slice(&state_size.to_le_bytes());
+        header
+    }
+}
+
+impl FromStr for PruningFormat {
+    type Err = String;
+
+    /// Parses `mod3`, or `capped:<cap>` with a cap between 1 and [`MAX_CAP`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "mod3" {
+            return Ok(Self::Mod3);
+        }
+
+        let cap = s
+            .strip_prefix("capped:")
+            .ok_or_else(|| format!("expected `mod3` or `capped:<cap>`, got {s:?}"))?;
+        match cap.parse::<u8>() {
+            Ok(cap @ 1..=MAX_CAP) => Ok(Self::Capped(cap)),
+            _ => Err(format!("cap must be between 1 and {MAX_CAP}, got {cap:?}")),
+        }
+    }
+}
+
+impl fmt::Display for PruningFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Mod3 => write!(f, "mod3"),
+            Self::Capped(cap) => write!(f, "capped{cap}"),
+        }
+    }
+}
+
+/// The path of the pruning table of the run `name` in `directory`.
+pub fn path(directory: &Path, name: &str, format: PruningFormat) -> PathBuf {
+    directory.join(format!("{name}.{format}.prune"))
+}
+
+fn get(data: &[u8], bits: u64, state: u64) -> u8 {
+    let bit = state * bits;
+    let mask = (1 << bits) - 1;
+    (data[(bit / 8) as usize] >> (bit % 8)) & mask
+}
+
+fn set(data: &mut [u8], bits: u64, state: u64, value: u8) {
+    let bit = state * bits;
+    data[(bit / 8) as usize] |= value << (bit % 8);
+}
+
+/// Writes the pruning table in `format` for the search that `depths` came from. `expander` must
+/// be the one used for the search, and `initial_states` the states it started from.
+///
+/// A capped table is filled in one depth at a time, by scanning every state for the ones at the
+/// previous depth, so this takes a while for large state spaces.
+pub fn export<E, const N: usize>(
+    depths: &DepthTable,
+    expander: &mut E,
+    initial_states: &[u64],
+    format: PruningFormat,
+    path: &Path,
+) -> Result<(), TableFileError>
+where
+    E: BfsExpander<N>,
+{
+    let state_size = depths.state_size();
+    let tmp_path = path.with_extension("prune.tmp");
+    let io_err = |path: &Path| {
+        let path = path.to_owned();
+        move |source| TableFileError::Io { path, source }
+    };
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(io_err(parent))?;
+    }
+
+    let file = File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&tmp_path)
+        .map_err(io_err(&tmp_path))?;
+    file.set_len((HEADER_LEN + format.data_len(state_size)) as u64)
+        .map_err(io_err(&tmp_path))?;
+
+    // SAFETY: the temporary file is only used here.
+    let mut mmap = unsafe { MmapMut::map_mut(&file) }.map_err(io_err(&tmp_path))?;
+    mmap[..HEADER_LEN].copy_from_slice(&format.header(state_size));
+    let data = &mut mmap[HEADER_LEN..];
+
+    match format {
+        PruningFormat::Mod3 => data.copy_from_slice(depths.data()),
+        PruningFormat::Capped(cap) => {
+            let bits = format.bits();
+            for &state in initial_states {
+                set(data, bits, state, 1);
+            }
+
+            let mut neighbours = [0; N];
+            for depth in 0..cap as usize - 1 {
+                tracing::info!("exporting depth {}", depth + 1);
+                for state in 0..state_size {
+                    if get(data, bits, state) as usize != depth + 1 {
+                        continue;
+                    }
+
+                    // The neighbours one layer further out are the ones with the next depth mod 3
+                    expander.expand(state, &mut neighbours);
+                    for &neighbour in &neighbours {
+                        if depths.get(neighbour) == Some((depth + 1) % 3)
+                            && get(data, bits, neighbour) == 0
+                        {
+                            set(data, bits, neighbour, depth as u8 + 2);
+                        }
+                    }
+                }
+            }
+
+            // Everything else that was reached is at least `cap` deep
+            for state in 0..state_size {
+                if depths.get(state).is_some() && get(data, bits, state) == 0 {
+                    set(data, bits, state, cap + 1);
+                }
+            }
+        }
+    }
+
+    mmap.flush().map_err(io_err(&tmp_path))?;
+    drop(mmap);
+    fs::rename(&tmp_path, path).map_err(io_err(path))
+}
+
+/// A pruning table, mapped from a file.
+pub struct PruningTable {
+    mmap: Mmap,
+    format: PruningFormat,
+    state_size: u64,
+}
+
+impl PruningTable {
+    pub fn open(
+        path: &Path,
+        format: PruningFormat,
+        state_size: u64,
+    ) -> Result<Self, TableFileError> {
+        let io_err = |source| TableFileError::Io {
+            path: path.to_owned(),
+            source,
+        };
+
+        let file = File::open(path).map_err(io_err)?;
+        // SAFETY: pruning tables are only ever replaced by renaming a new file over them, never
+        // modified in place.
+        let mmap = unsafe { Mmap::map(&file) }.map_err(io_err)?;
+
+        let header = mmap.get(..HEADER_LEN).ok_or(TableFileError::Truncated)?;
+        if header[..8] != MAGIC {
+            return Err(TableFileError::Magic);
+        }
+        let version = u32::from_le_bytes(header[8..12].try_into().unwrap());
+        if version != VERSION {
+            return Err(TableFileError::Version { found: version });
+        }
+        let expected = format.header(state_size);
+        if header[12..] != expected[12..] {
+            let found_size = u64::from_le_bytes(header[16..24].try_into().unwrap());
+            return Err(TableFileError::Mismatch {
+                what: "format",
+                found: format!("{:?} ({found_size} states)", &header[12..14]),
+                expected: format!("{format} ({state_size} states)"),
+            });
+        }
+        if mmap.len() < HEADER_LEN + format.data_len(state_size) {
+            return Err(TableFileError::Truncated);
+        }
+
+        Ok(Self {
+            mmap,
+            format,
+            state_size,
+        })
+    }
+
+    /// The stored value for `state`, i.e. its depth modulo 3 or its capped depth, or `None` if it
+    /// wasn't reached.
+    pub fn get(&self, state: u64) -> Option<usize> {
+        if state >= self.state_size {
+            return None;
+        }
+        let value = get(&self.mmap[HEADER_LEN..], self.format.bits(), state);
+        (value != 0).then(|| value as usize - 1)
+    }
+
+    /// A lower bound on the depth of `state`, given a lower bound `bound` on the depth of one of
+    /// its neighbours. For a [`PruningFormat::Mod3`] table, `bound` must be exact, and so is the
+    /// result.
+    fn bound(&self, state: u64, bound: usize) -> Option<usize> {
+        let value = self.get(state)?;
+        Some(match self.format {
+            PruningFormat::Mod3 => match (value + 3 - bound % 3) % 3 {
+                0 => bound,
+                1 => bound + 1,
+                _ => bound - 1,
+            },
+            PruningFormat::Capped(_) => value.max(bound.saturating_sub(1)),
+        })
+    }
+
+    /// A lower bound on the depth of `state` on its own. For a [`PruningFormat::Mod3`] table, this
+    /// walks down to a goal state to find the exact depth.
+    fn start_bound<E, const N: usize>(
+        &self,
+        expander: &mut E,
+        state: u64,
+        is_goal: &impl Fn(u64) -> bool,
+    ) -> Option<usize>
+    where
+        E: BfsExpander<N>,
+    {
+        let mut value = self.get(state)?;
+        if let PruningFormat::Capped(_) = self.format {
+            return Some(value);
+        }
+
+        let mut state = state;
+        let mut depth = 0;
+        let mut neighbours = [0; N];
+        while !is_goal(state) {
+            expander.expand(state, &mut neighbours);
+            value = (value + 2) % 3;
+            state = neighbours
+                .into_iter()
+                .find(|&neighbour| self.get(neighbour) == Some(value))?;
+            depth += 1;
+        }
+        Some(depth)
+    }
+
+    /// Finds a shortest path from `state` to a state where `is_goal` is true with IDA*, using the
+    /// table to prune. Returns the states along the path, not including `state` itself, or `None`
+    /// if `state` wasn't reached.
+    pub fn solve<E, const N: usize>(
+        &self,
+        expander: &mut E,
+        state: u64,
+        is_goal: impl Fn(u64) -> bool,
+    ) -> Option<Vec<u64>>
+    where
+        E: BfsExpander<N>,
+    {
+        let bound = self.start_bound(expander, state, &is_goal)?;
+        let mut path = Vec::new();
+
+        for max_depth in bound.. {
+            tracing::debug!("searching to depth {max_depth}");
+            let mut search = Search {
+                table: self,
+                expander: &mut *expander,
+                is_goal: &is_goal,
+                max_depth,
+                path: &mut path,
+            };
+            if search.search(state, None, bound) {
+                return Some(path);
+            }
+        }
+
+        unreachable!()
+    }
+}
+
+struct Search<'a, E, G> {
+    table: &'a PruningTable,
+    expander: &'a mut E,
+    is_goal: &'a G,
+    max_depth: usize,
+    path: &'a mut Vec<u64>,
+}
+
+impl<E, G> Search<'_, E, G>
+where
+    G: Fn(u64) -> bool,
+{
+    /// Depth-first search from `state`, whose depth is at least `bound`, coming from `previous`.
+    fn search<const N: usize>(&mut self, state: u64, previous: Option<u64>, bound: usize) -> bool
+    where
+        E: BfsExpander<N>,
+    {
+        if (self.is_goal)(state) {
+            return true;
+        }
+
+        let mut neighbours = [0; N];
+        self.expander.expand(state, &mut neighbours);
+        for neighbour in neighbours {
+            if Some(neighbour) == previous {
+                continue;
+            }
+            let Some(neighbour_bound) = self.table.bound(neighbour, bound) else {
+                continue;
+            };
+            if self.path.len() + 1 + neighbour_bound > self.max_depth {
+                continue;
+            }
+
+            self.path.push(neighbour);
+            if self.search(neighbour, Some(state), neighbour_bound) {
+                return true;
+            }
+            self.path.pop();
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        depths::{self, DepthTableWriter},
+        test_util::temp_dir,
+    };
+
+    /// Adding 1, 3 or their inverses modulo 20.
+    struct Expander;
+
+    impl BfsExpander<4> for Expander {
+        fn expand(&mut self, node: u64, expanded_nodes: &mut [u64; 4]) {
+            *expanded_nodes = [
+                (node + 1) % 20,
+                (node + 19) % 20,
+                (node + 3) % 20,
+                (node + 17) % 20,
+            ];
+        }
+    }
+
+    fn distance(state: u64) -> usize {
+        let d = state.min(20 - state);
+        (d / 3 + d % 3) as usize
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!("mod3".parse(), Ok(PruningFormat::Mod3));
+        assert_eq!("capped:9".parse(), Ok(PruningFormat::Capped(9)));
+        assert!("capped:15".parse::<PruningFormat>().is_err());
+        assert!("capped".parse::<PruningFormat>().is_err());
+        assert_eq!(PruningFormat::Capped(9).to_string(), "capped9");
+    }
+
+    #[test]
+    fn test_export_and_solve() {
+        let dir = temp_dir("pruning-export");
+
+        // State 20 is never reached
+        let writer = DepthTableWriter::create(&depths::path(&dir, "cycle"), 21).unwrap();
+        for state in 0..20 {
+            writer.set(state, distance(state));
+        }
+        let depth_table = writer.finish().unwrap();
+
+        for format in [PruningFormat::Mod3, PruningFormat::Capped(2)] {
+            let path = path(&dir, "cycle", format);
+            export(&depth_table, &mut Expander, &[0], format, &path).unwrap();
+            let table = PruningTable::open(&path, format, 21).unwrap();
+
+            for state in 0..20 {
+                let value = match format {
+                    PruningFormat::Mod3 => distance(state) % 3,
+                    PruningFormat::Capped(cap) => distance(state).min(cap as usize),
+                };
+                assert_eq!(table.get(state), Some(value));
+
+                let path = table.solve(&mut Expander, state, |s| s == 0).unwrap();
+                assert_eq!(path.len(), distance(state), "{format} {state}");
+                assert_eq!(path.last().copied().unwrap_or(state), 0);
+            }
+            assert_eq!(table.get(20), None);
+            assert_eq!(table.solve(&mut Expander, 20, |s| s == 0), None);
+        }
+
+        assert!(matches!(
+            PruningTable::open(
+                &path(&dir, "cycle", PruningFormat::Mod3),
+                PruningFormat::Capped(2),
+                21
+            ),
+            Err(TableFileError::Mismatch { .. })
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
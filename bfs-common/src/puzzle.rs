@@ -0,0 +1,141 @@
+use std::fmt;
+
+use rand::Rng;
+
+use crate::goal::GoalSet;
+
+/// A puzzle whose states are numbered `0..STATE_SIZE`.
+///
+/// Every number in that range must be a state the puzzle can reach from the solved state, so the
+/// numbering takes care of any parity and orientation constraints between the pieces.
+///
+/// This is implemented by the piece-level representation of each puzzle. The coordinate-level
+/// types used for the search must agree with it on `encode`, and on the solved state. The `Debug`
+/// output is used to describe states to people, e.g. in the list of antipodes.
+pub trait Puzzle: Sized + fmt::Debug {
+    /// Name of the puzzle, as used on the command line and in the run config.
+    const ID: &'static str;
+
+    /// Number of states, i.e. one more than the largest encoding.
+    const STATE_SIZE: u64;
+
+    /// Names of the moves accepted by [`Puzzle::do_move`], in move index order.
+    const MOVES: &'static [&'static str];
+
+    /// The order of each move in [`Puzzle::MOVES`], i.e. how many times it is applied before the
+    /// puzzle is back where it started.
+    const ORDERS: &'static [usize];
+
+    /// Names of the goal sets given by [`Puzzle::goal`], which a search can start from instead of
+    /// the solved state.
+    const GOALS: &'static [&'static str] = &[];
+
+    fn solved() -> Self;
+
+    fn encode(&self) -> u64;
+
+    fn decode(&mut self, coord: u64);
+
+    /// Applies the move `Self::MOVES[mv]`.
+    fn do_move(&mut self, mv: usize);
+
+    fn from_coord(coord: u64) -> Self {
+        let mut puzzle = Self::solved();
+        puzzle.decode(coord);
+        puzzle
+    }
+
+    /// A uniformly random state, e.g. for scrambling, or for estimating how deep the states are
+    /// before searching all of them.
+    fn random<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Self::from_coord(Self::random_coord(rng))
+    }
+
+    /// The coordinate of a uniformly random state.
+    fn random_coord<R: Rng + ?Sized>(rng: &mut R) -> u64 {
+        rng.random_range(0..Self::STATE_SIZE)
+    }
+
+    /// The goal set called `name`, which is one of [`Puzzle::GOALS`].
+    fn goal(_name: &str) -> Option<GoalSet> {
+        None
+    }
+
+    /// The coordinates that [`Puzzle::encode`] combines, by name, e.g. the permutation and the
+    /// orientation, for writing states out in a form other programs can read.
+    fn sub_coords(&self) -> Vec<(&'static str, u64)> {
+        Vec::new()
+    }
+
+    /// The pieces of the state by name, e.g. the corner permutation and orientation, each as the
+    /// piece or orientation at each position.
+    fn pieces(&self) -> Vec<(&'static str, Vec<u8>)> {
+        Vec::new()
+    }
+
+    fn move_index(name: &str) -> Option<usize> {
+        Self::MOVES.iter().position(|&mv| mv == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    /// A single cycle of length 5.
+    #[derive(Debug)]
+    struct Cycle(u8);
+
+    impl Puzzle for Cycle {
+        const ID: &'static str = "cycle";
+        const STATE_SIZE: u64 = 5;
+        const MOVES: &'static [&'static str] = &["A", "A'"];
+        const ORDERS: &'static [usize] = &[5, 5];
+
+        fn solved() -> Self {
+            Self(0)
+        }
+
+        fn encode(&self) -> u64 {
+            self.0 as u64
+        }
+
+        fn decode(&mut self, coord: u64) {
+            self.0 = coord as u8;
+        }
+
+        fn do_move(&mut self, mv: usize) {
+            self.0 = (self.0 + [1, 4][mv]) % 5;
+        }
+    }
+
+    #[test]
+    fn test_from_coord() {
+        for coord in 0..Cycle::STATE_SIZE {
+            assert_eq!(Cycle::from_coord(coord).encode(), coord);
+        }
+    }
+
+    #[test]
+    fn test_move_index() {
+        let mut cycle = Cycle::solved();
+        cycle.do_move(Cycle::move_index("A'").unwrap());
+        assert_eq!(cycle.encode(), 4);
+        assert_eq!(Cycle::move_index("B"), None);
+    }
+
+    #[test]
+    fn test_random() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut seen = [0; Cycle::STATE_SIZE as usize];
+        for _ in 0..1000 {
+            seen[Cycle::random(&mut rng).encode() as usize] += 1;
+        }
+        assert!(
+            seen.iter().all(|&count| (150..250).contains(&count)),
+            "{seen:?}"
+        );
+    }
+}
@@ -0,0 +1,511 @@
+//! Counts the states at each depth during a search, and writes the distance distribution at the
+//! end of it.
+
+use std::{
+    fmt, fs, io,
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use serde::Serialize;
+
+use crate::limits::StopReason;
+
+/// The deepest depth a [`DepthCounter`] can count states at.
+pub const MAX_DEPTH: usize = 255;
+
+/// Counts new states by depth. Each clone counts into slots of its own, which other clones can
+/// read without stopping it, so that [`DepthCounter::totals`] can be taken in the middle of a
+/// search. A clone's counts are added to the shared totals when it is dropped.
+pub struct DepthCounter {
+    counts: Arc<[AtomicU64]>,
+    shared: Arc<Mutex<SharedCounts>>,
+}
+
+/// The counts of the clones of a [`DepthCounter`] that were dropped, and the slots of the ones
+/// that are still counting.
+struct SharedCounts {
+    dropped: Vec<u64>,
+    live: Vec<Arc<[AtomicU64]>>,
+}
+
+fn slots() -> Arc<[AtomicU64]> {
+    (0..=MAX_DEPTH).map(|_| AtomicU64::new(0)).collect()
+}
+
+impl DepthCounter {
+    pub fn new() -> Self {
+        let counts = slots();
+        Self {
+            counts: Arc::clone(&counts),
+            shared: Arc::new(Mutex::new(SharedCounts {
+                dropped: vec![0; MAX_DEPTH + 1],
+                live: vec![counts],
+            })),
+        }
+    }
+
+    pub fn add(&mut self, depth: usize) {
+        self.add_many(depth, 1);
+    }
+
+    /// Counts `count` states at `depth` at once, e.g. all the states in a symmetry class.
+    pub fn add_many(&mut self, depth: usize, count: u64) {
+        assert!(
+            depth <= MAX_DEPTH,
+            "depth {depth} is deeper than {MAX_DEPTH}"
+        );
+        self.counts[depth].fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// The counts of all clones of this counter so far.
+    pub fn totals(&self) -> Vec<u64> {
+        let shared = self.shared.lock().unwrap();
+        let mut totals = shared.dropped.clone();
+        for counts in &shared.live {
+            for (total, count) in totals.iter_mut().zip(counts.iter()) {
+                *total += count.load(Ordering::Relaxed);
+            }
+        }
+
+        while totals.last() == Some(&0) {
+            totals.pop();
+        }
+        totals
+    }
+}
+
+impl Default for DepthCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clone for DepthCounter {
+    /// Returns a counter that shares totals with `self`, but starts with no counts of its own.
+    fn clone(&self) -> Self {
+        let counts = slots();
+        self.shared.lock().unwrap().live.push(Arc::clone(&counts));
+        Self {
+            counts,
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl Drop for DepthCounter {
+    fn drop(&mut self) {
+        let mut shared = self.shared.lock().unwrap();
+        shared
+            .live
+            .retain(|counts| !Arc::ptr_eq(counts, &self.counts));
+
+        for (total, count) in shared.dropped.iter_mut().zip(self.counts.iter()) {
+            *total += count.load(Ordering::Relaxed);
+        }
+    }
+}
+
+/// The number of states at each distance from the initial states.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DistanceDistribution {
+    counts: Vec<u64>,
+}
+
+impl DistanceDistribution {
+    /// `counts[d]` is the number of states at depth `d`. Trailing empty depths are dropped.
+    pub fn new(mut counts: Vec<u64>) -> Self {
+        while counts.last() == Some(&0) {
+            counts.pop();
+        }
+        Self { counts }
+    }
+
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    /// The number of states reached.
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// The largest depth of any state, or `None` if there are no states.
+    pub fn gods_number(&self) -> Option<usize> {
+        self.counts.len().checked_sub(1)
+    }
+
+    /// The mean depth of all states.
+    pub fn average(&self) -> f64 {
+        let weighted = self
+            .counts
+            .iter()
+            .enumerate()
+            .map(|(depth, &count)| depth as f64 * count as f64)
+            .sum::<f64>();
+        weighted / self.total() as f64
+    }
+
+    /// The distribution of a search of a state space of `state_size` states, ready to be written.
+    pub fn report<'a>(&'a self, puzzle: &'a str, run: &'a str, state_size: u64) -> Report<'a> {
+        Report {
+            puzzle,
+            run,
+            distribution: self,
+            state_size,
+            classes: None,
+            stopped: None,
+            goal: None,
+        }
+    }
+}
+
+/// A [`DistanceDistribution`] with the details of the run it came from. It displays as a plain
+/// text table, and serializes to JSON.
+pub struct Report<'a> {
+    puzzle: &'a str,
+    run: &'a str,
+    distribution: &'a DistanceDistribution,
+    state_size: u64,
+    /// The distribution of symmetry classes and the number of classes if it is known, for a
+    /// search that was reduced by symmetry.
+    classes: Option<(&'a DistanceDistribution, Option<u64>)>,
+    /// Why the search was stopped before it reached every state, if it was.
+    stopped: Option<StopReason>,
+    /// The description of the goal set and its number of states, unless it is the solved state.
+    goal: Option<(&'a str, usize)>,
+}
+
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    puzzle: &'a str,
+    run: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    goal: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    goal_states: Option<usize>,
+    state_size: u64,
+    total: u64,
+    complete: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stopped: Option<String>,
+    /// `None` if there are no states, or the search was stopped before it found the deepest ones.
+    gods_number: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    searched_depth: Option<usize>,
+    average_distance: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    class_count: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total_classes: Option<u64>,
+    depths: Vec<JsonDepth>,
+}
+
+#[derive(Serialize)]
+struct JsonDepth {
+    depth: usize,
+    count: u64,
+    cumulative: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    classes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cumulative_classes: Option<u64>,
+}
+
+impl<'a> Report<'a> {
+    /// Adds the number of classes at each depth of a search that was reduced by symmetry to
+    /// `class_count` classes, if it is known. The report's own distribution counts the states in
+    /// those classes.
+    pub fn with_classes(self, classes: &'a DistanceDistribution, class_count: Option<u64>) -> Self {
+        Self {
+            classes: Some((classes, class_count)),
+            ..self
+        }
+    }
+
+    /// Records that the search started from the `states` states of a goal set, instead of the
+    /// solved state alone.
+    pub fn with_goal(self, description: &'a str, states: usize) -> Self {
+        Self {
+            goal: Some((description, states)),
+            ..self
+        }
+    }
+
+    /// Marks the report as that of a search that was stopped early, so that the deepest depth
+    /// searched isn't taken for god's number.
+    pub fn stopped(self, reason: StopReason) -> Self {
+        Self {
+            stopped: Some(reason),
+            ..self
+        }
+    }
+
+    /// Whether every state of the state space was reached.
+    pub fn is_complete(&self) -> bool {
+        self.distribution.total() == self.state_size
+    }
+
+    fn cumulative(&self) -> impl Iterator<Item = (usize, u64, u64)> + '_ {
+        cumulative(self.distribution)
+    }
+
+    /// The number of classes and the cumulative number of classes at each depth, if the search
+    /// was reduced by symmetry.
+    fn cumulative_classes(&self) -> impl Iterator<Item = Option<(u64, u64)>> + '_ {
+        let mut classes = self
+            .classes
+            .map(|(classes, _)| cumulative(classes).map(|(_, count, total)| (count, total)));
+        std::iter::from_fn(move || match &mut classes {
+            Some(classes) => Some(classes.next()),
+            None => Some(None),
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        let report = JsonReport {
+            puzzle: self.puzzle,
+            run: self.run,
+            goal: self.goal.map(|(description, _)| description),
+            goal_states: self.goal.map(|(_, states)| states),
+            state_size: self.state_size,
+            total: self.distribution.total(),
+            complete: self.is_complete(),
+            stopped: self.stopped.map(|reason| reason.to_string()),
+            gods_number: self
+                .distribution
+                .gods_number()
+                .filter(|_| self.stopped.is_none()),
+            searched_depth: self.stopped.and(self.distribution.gods_number()),
+            average_distance: self.distribution.average(),
+            class_count: self.classes.and_then(|(_, class_count)| class_count),
+            total_classes: self.classes.map(|(classes, _)| classes.total()),
+            depths: self
+                .cumulative()
+                .zip(self.cumulative_classes())
+                .map(|((depth, count, cumulative), classes)| JsonDepth {
+                    depth,
+                    count,
+                    cumulative,
+                    classes: classes.map(|(count, _)| count),
+                    cumulative_classes: classes.map(|(_, cumulative)| cumulative),
+                })
+                .collect(),
+        };
+        serde_json::to_string_pretty(&report).unwrap()
+    }
+
+    /// Writes the report to `<name>.txt` and `<name>.json` in `directory`, creating it if needed.
+    pub fn write(&self, directory: &Path, name: &str) -> io::Result<()> {
+        fs::create_dir_all(directory)?;
+        fs::write(
+            directory.join(format!("{name}.txt")),
+            self.to_string() + "\n",
+        )?;
+        fs::write(
+            directory.join(format!("{name}.json")),
+            self.to_json() + "\n",
+        )
+    }
+}
+
+fn cumulative(distribution: &DistanceDistribution) -> impl Iterator<Item = (usize, u64, u64)> + '_ {
+    distribution
+        .counts
+        .iter()
+        .enumerate()
+        .scan(0, |total, (depth, &count)| {
+            *total += count;
+            Some((depth, count, *total))
+        })
+}
+
+impl fmt::Display for Report<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.distribution.total();
+        let width = total.to_string().len().max("cumulative".len());
+
+        let class_width = self.classes.map_or(0, |(classes, _)| {
+            classes.total().to_string().len().max("cumulative".len())
+        });
+
+        writeln!(f, "{} ({})", self.puzzle, self.run)?;
+        if let Some((description, states)) = self.goal {
+            writeln!(f, "goal: {description} ({states} states)")?;
+        }
+        writeln!(f)?;
+        write!(f, "depth  {:>width$}  {:>width$}", "count", "cumulative")?;
+        if self.classes.is_some() {
+            write!(
+                f,
+                "  {:>class_width$}  {:>class_width$}",
+                "classes", "cumulative"
+            )?;
+        }
+        writeln!(f)?;
+        for ((depth, count, cumulative), classes) in
+            self.cumulative().zip(self.cumulative_classes())
+        {
+            write!(f, "{depth:>5}  {count:>width$}  {cumulative:>width$}")?;
+            if let Some((count, cumulative)) = classes {
+                write!(f, "  {count:>class_width$}  {cumulative:>class_width$}")?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(f)?;
+
+        if self.is_complete() {
+            writeln!(f, "total: {total}")?;
+        } else {
+            writeln!(f, "total: {total} of {} states", self.state_size)?;
+        }
+        match self.classes {
+            Some((classes, Some(class_count))) => {
+                writeln!(f, "classes: {} of {class_count}", classes.total())?
+            }
+            Some((classes, None)) => writeln!(f, "classes: {}", classes.total())?,
+            None => {}
+        }
+        match (self.distribution.gods_number(), self.stopped) {
+            (Some(depth), Some(reason)) => {
+                writeln!(f, "stopped after depth {depth}: {reason}")?;
+                writeln!(f, "god's number: more than {depth}")?
+            }
+            (Some(depth), None) => writeln!(f, "god's number: {depth}")?,
+            (None, _) => writeln!(f, "god's number: none")?,
+        }
+        write!(f, "average distance: {:.6}", self.distribution.average())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_depth_counter() {
+        let mut counter = DepthCounter::new();
+        counter.add(0);
+
+        let mut clones = (0..4).map(|_| counter.clone()).collect::<Vec<_>>();
+        for (i, clone) in clones.iter_mut().enumerate() {
+            for depth in 1..=i + 1 {
+                clone.add(depth);
+            }
+        }
+
+        // The clones start empty, so `counter`'s own count isn't counted again, and the counts of
+        // clones that are still counting are included
+        let mut clone = clones.pop().unwrap();
+        assert_eq!(counter.totals(), vec![1, 4, 3, 2, 1]);
+        drop(clones);
+        clone.add(4);
+
+        assert_eq!(counter.totals(), vec![1, 4, 3, 2, 2]);
+        drop(clone);
+        assert_eq!(counter.totals(), vec![1, 4, 3, 2, 2]);
+    }
+
+    #[test]
+    fn test_distribution() {
+        let distribution = DistanceDistribution::new(vec![1, 4, 10, 5, 0]);
+        assert_eq!(distribution.counts(), &[1, 4, 10, 5]);
+        assert_eq!(distribution.total(), 20);
+        assert_eq!(distribution.gods_number(), Some(3));
+        assert_eq!(distribution.average(), 1.95);
+
+        let report = distribution.report("cycle", "cycle.htm", 20);
+        assert!(report.is_complete());
+        assert_eq!(
+            report.to_string(),
+            "cycle (cycle.htm)
+
+depth       count  cumulative
+    0           1           1
+    1           4           5
+    2          10          15
+    3           5          20
+
+total: 20
+god's number: 3
+average distance: 1.950000"
+        );
+
+        let json = serde_json::from_str::<serde_json::Value>(&report.to_json()).unwrap();
+        assert_eq!(json["gods_number"], 3);
+        assert_eq!(json["depths"][2]["cumulative"], 15);
+
+        assert!(!distribution.report("cycle", "cycle.htm", 24).is_complete());
+
+        let classes = DistanceDistribution::new(vec![1, 2, 4, 2]);
+        let report = distribution
+            .report("cycle", "cycle.sym.htm", 20)
+            .with_classes(&classes, Some(9));
+        assert_eq!(
+            report.to_string(),
+            "cycle (cycle.sym.htm)
+
+depth       count  cumulative     classes  cumulative
+    0           1           1           1           1
+    1           4           5           2           3
+    2          10          15           4           7
+    3           5          20           2           9
+
+total: 20
+classes: 9 of 9
+god's number: 3
+average distance: 1.950000"
+        );
+
+        let json = serde_json::from_str::<serde_json::Value>(&report.to_json()).unwrap();
+        assert_eq!(json["total_classes"], 9);
+        assert_eq!(json["depths"][1]["cumulative_classes"], 3);
+
+        let report = distribution
+            .report("cycle", "cycle.sym.htm", 20)
+            .with_classes(&classes, None);
+        assert!(report.to_string().contains("\nclasses: 9\n"));
+        let json = serde_json::from_str::<serde_json::Value>(&report.to_json()).unwrap();
+        assert!(json.get("class_count").is_none());
+        assert_eq!(DistanceDistribution::new(vec![]).gods_number(), None);
+
+        let report = distribution
+            .report("cycle", "cycle.even.htm", 20)
+            .with_goal("even", 2);
+        assert!(report
+            .to_string()
+            .starts_with("cycle (cycle.even.htm)\ngoal: even (2 states)\n\ndepth"));
+        let json = serde_json::from_str::<serde_json::Value>(&report.to_json()).unwrap();
+        assert_eq!(json["goal"], "even");
+        assert_eq!(json["goal_states"], 2);
+    }
+
+    #[test]
+    fn test_stopped() {
+        let distribution = DistanceDistribution::new(vec![1, 4, 10]);
+        let report = distribution
+            .report("cycle", "cycle.htm", 20)
+            .stopped(StopReason::MaxDepth(2));
+        assert!(!report.is_complete());
+        assert!(report.to_string().ends_with(
+            "total: 15 of 20 states
+stopped after depth 2: reached the maximum depth of 2
+god's number: more than 2
+average distance: 1.600000"
+        ));
+
+        let json = serde_json::from_str::<serde_json::Value>(&report.to_json()).unwrap();
+        assert_eq!(json["stopped"], "reached the maximum depth of 2");
+        assert!(json["gods_number"].is_null());
+        assert_eq!(json["searched_depth"], 2);
+
+        let json = distribution.report("cycle", "cycle.htm", 20).to_json();
+        let json = serde_json::from_str::<serde_json::Value>(&json).unwrap();
+        assert!(json.get("stopped").is_none());
+        assert!(json.get("goal").is_none());
+        assert_eq!(json["gods_number"], 2);
+    }
+}
@@ -0,0 +1,1293 @@
+//! The parts of a run that are the same for every puzzle: logging, the callback and settings
+//! provider, the builder itself, and the tasks that use the results of a search.
+
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use disk_based_bfs::{
+    builder::{BfsBuilder, BfsBuilderError},
+    callback::BfsCallback,
+    expander::BfsExpander,
+    provider::{BfsSettingsProvider, ChunkFilesBehavior, UpdateFilesBehavior},
+};
+use thiserror::Error;
+use tracing_subscriber::{layer::SubscriberExt as _, util::SubscriberInitExt as _, EnvFilter};
+
+use crate::{
+    antipodes::AntipodeCollector,
+    checkpoint::{self, Checkpoint, CheckpointError},
+    config::RunConfig,
+    depths::{self, DepthTable, DepthTableWriter},
+    dump::{Dump, DumpFiles, DumpWriter},
+    enumerate::{Enumerator, MoveFilter},
+    estimate,
+    goal::{GoalError, GoalSet},
+    limits::{LimitedExpander, Limits, Stop, StopReason},
+    manifest::{self, Manifest, ManifestError, Variant},
+    notation::{self, Move, NotationError},
+    plan::{self, Bytes},
+    pruning::{self, PruningFormat, PruningTable},
+    puzzle::Puzzle,
+    report::{DepthCounter, DistanceDistribution},
+    solutions::{self, SolutionCounts},
+    symmetry::{ReducedExpander, Reduction, SymmetryClasses},
+    tables::TableFileError,
+};
+
+/// What to do with a puzzle's expander.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Task {
+    /// Search the state space, stopping early if one of the limits is reached, and writing out
+    /// the states at the depths of `dump`.
+    Search { limits: Limits, dump: Option<Dump> },
+
+    /// Print an optimal solution of a scramble, using the depth table of an earlier search.
+    Solve(String),
+
+    /// Export a pruning table from the depth table of an earlier search.
+    ExportPruning(PruningFormat),
+
+    /// Print every solution of a scramble that is at most `extra` moves longer than an optimal
+    /// one and passes the filters, using the depth table of an earlier search.
+    Enumerate {
+        scramble: String,
+        extra: usize,
+        filters: Vec<MoveFilter>,
+    },
+
+    /// Count the optimal solutions of every state, using the depth table of an earlier search.
+    CountSolutions,
+
+    /// Print an optimal solution of a scramble found with IDA*, using an exported pruning table.
+    Ida {
+        scramble: String,
+        pruning: PruningFormat,
+    },
+
+    /// Estimate the distance distribution from a sample of random states, before searching.
+    Estimate {
+        samples: u64,
+        /// The most states to search from each sample before giving up on finding its depth.
+        max_states: usize,
+        /// The seed to draw the samples with, or `None` for a random one.
+        seed: Option<u64>,
+    },
+}
+
+impl Task {
+    /// Whether the task needs the distances from states to the goal states, which searching from
+    /// the goal states only gives if the inverse of every move is also a move, see
+    /// [`Variant::one_way`].
+    fn needs_inverses(&self) -> bool {
+        matches!(
+            self,
+            Self::Solve(_)
+                | Self::ExportPruning(_)
+                | Self::Ida { .. }
+                | Self::Enumerate { .. }
+                | Self::CountSolutions
+                | Self::Estimate { .. }
+        )
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum TaskError {
+    #[error("invalid scramble: {0}")]
+    Notation(#[from] NotationError),
+
+    #[error("invalid filter: {0}")]
+    Filter(NotationError),
+
+    #[error("depth or pruning table: {0}")]
+    DepthTable(#[from] TableFileError),
+
+    #[error("no `depth_table_directory` is set for this run")]
+    NoDepthTable,
+
+    #[error("state {state} was not reached by the search")]
+    Unreached { state: u64 },
+
+    #[error("only searching and estimating are supported when reducing by symmetry")]
+    Reduced,
+
+    #[error("there are no symmetries to reduce by in this metric")]
+    NoSymmetries,
+
+    #[error("this task needs the inverse of every move to be a move, unlike in this metric")]
+    OneWay,
+
+    #[error(
+        "the solution counts need {} in {}, but only {} is free",
+        Bytes(*needed),
+        directory.display(),
+        Bytes(*available)
+    )]
+    NoSpace {
+        directory: PathBuf,
+        needed: u64,
+        available: u64,
+    },
+
+    #[error(transparent)]
+    Goal(#[from] GoalError),
+
+    #[error("the symmetries don't map the goal set {0} to itself")]
+    AsymmetricGoal(String),
+
+    #[error("manifest: {0}")]
+    Manifest(#[from] ManifestError),
+
+    #[error("checkpoint: {0}")]
+    Checkpoint(#[from] CheckpointError),
+
+    #[error("search failed: {0}")]
+    Search(#[from] BfsBuilderError),
+}
+
+/// Depths at which a run starts doing extra work.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bounds {
+    /// States at this depth or deeper are logged, and the deepest of them are written out as the
+    /// antipodes at the end of the run.
+    pub log: usize,
+
+    /// Chunk and update files are kept, instead of deleted, from this depth on.
+    pub keep: usize,
+}
+
+impl Bounds {
+    pub const NEVER: usize = usize::MAX;
+}
+
+#[derive(Clone)]
+struct Callback<R> {
+    log: usize,
+    counter: DepthCounter,
+    antipodes: AntipodeCollector,
+    depths: Option<Arc<DepthTableWriter>>,
+    /// Counts the states in each class that is found, if the search is reduced by symmetry.
+    classes: Option<(DepthCounter, R)>,
+    dump: Option<DumpWriter>,
+    progress: Arc<Progress>,
+}
+
+impl<R: Reduction> BfsCallback for Callback<R> {
+    fn new_state(&mut self, depth: usize, state: u64) {
+        if self.progress.is_restored(depth) {
+            return;
+        }
+        self.counter.add(depth);
+        if let Some((counter, reduction)) = &mut self.classes {
+            counter.add_many(depth, reduction.size(state));
+        }
+        if let Some(depths) = &self.depths {
+            depths.set(state, depth);
+        }
+        let dumped = match &mut self.dump {
+            Some(dump) if dump.wants(depth) => {
+                let representative = match &mut self.classes {
+                    Some((_, reduction)) => reduction.representative(state),
+                    None => state,
+                };
+                dump.add(depth, state, representative);
+                true
+            }
+            _ => false,
+        };
+        if depth >= self.log {
+            // The states are in the dump already, so there is no need to log them too
+            if !dumped {
+                tracing::info!("depth {depth} state {state}");
+            }
+            self.antipodes.add(depth, state);
+        }
+    }
+
+    fn end_of_chunk(&self, depth: usize, _: usize) {
+        self.progress.end_of_chunk(depth);
+    }
+}
+
+/// Writes a [`Checkpoint`] of the search each time every chunk of a depth has been finished.
+struct Progress {
+    path: PathBuf,
+    /// The checkpoint that the search was resumed from, which has no depths if it wasn't.
+    restored: Checkpoint,
+    num_chunks: usize,
+    /// The number of chunks finished at each depth.
+    finished_chunks: Mutex<Vec<usize>>,
+    /// The number of initial states, which aren't necessarily passed to the callback.
+    initial: u64,
+    limits: Limits,
+    stop: Stop,
+    /// Why the search was stopped, once it has been.
+    stopped: OnceLock<StopReason>,
+    counter: DepthCounter,
+    class_sizes: Option<DepthCounter>,
+    antipodes: AntipodeCollector,
+    depths: Option<Arc<DepthTableWriter>>,
+}
+
+impl Progress {
+    /// Whether `depth` was finished before the search was resumed. Its states may be passed to
+    /// the callback again, but they have already been counted.
+    fn is_restored(&self, depth: usize) -> bool {
+        self.restored
+            .depth()
+            .is_some_and(|restored| depth <= restored)
+    }
+
+    fn end_of_chunk(&self, depth: usize) {
+        if self.is_restored(depth) {
+            return;
+        }
+
+        let mut finished_chunks = self.finished_chunks.lock().unwrap();
+        if finished_chunks.len() <= depth {
+            finished_chunks.resize(depth + 1, 0);
+        }
+        finished_chunks[depth] += 1;
+        if finished_chunks[depth] == self.num_chunks {
+            match self.write(depth) {
+                Ok(()) => tracing::info!("wrote checkpoint of depth {depth}"),
+                Err(err) => tracing::error!(
+                    "failed to write checkpoint of depth {depth} to {}: {err}",
+                    self.path.display()
+                ),
+            }
+            self.check_limits(depth);
+        }
+    }
+
+    /// Stops the search if it has reached one of its limits, now that `depth` is finished.
+    fn check_limits(&self, depth: usize) {
+        let states = distribution(self.counts(), self.initial).total();
+        if let Some(reason) = self.limits.reached(depth, states) {
+            if self.stopped.set(reason).is_ok() {
+                tracing::info!("stopping the search after depth {depth}: {reason}");
+                self.stop.set();
+            }
+        }
+    }
+
+    /// The counts of the whole search, including the restored depths.
+    fn counts(&self) -> Vec<u64> {
+        with_restored(&self.restored.counts, self.counter.totals())
+    }
+
+    /// The sizes of the classes found by the whole search, if it is reduced by symmetry.
+    fn class_sizes(&self) -> Option<Vec<u64>> {
+        let restored = self.restored.class_sizes.as_deref().unwrap_or_default();
+        let counter = self.class_sizes.as_ref()?;
+        Some(with_restored(restored, counter.totals()))
+    }
+
+    fn write(&self, depth: usize) -> Result<(), TaskError> {
+        // The checkpoint must not cover states that aren't in the depth table on disk yet
+        if let Some(depths) = &self.depths {
+            depths.flush()?;
+        }
+
+        let finished = |mut counts: Vec<u64>| {
+            counts.resize(depth + 1, 0);
+            counts
+        };
+        let checkpoint = Checkpoint {
+            counts: finished(self.counts()),
+            class_sizes: self.class_sizes().map(finished),
+            antipodes: self.antipodes.antipodes(),
+            ..self.restored.clone()
+        };
+        checkpoint.write(&self.path)?;
+        Ok(())
+    }
+}
+
+/// Adds the counts of the depths restored from a checkpoint to `counts`.
+fn with_restored(restored: &[u64], mut counts: Vec<u64>) -> Vec<u64> {
+    if counts.len() < restored.len() {
+        counts.resize(restored.len(), 0);
+    }
+    for (count, restored) in counts.iter_mut().zip(restored) {
+        *count += restored;
+    }
+    counts
+}
+
+struct Provider<'a> {
+    config: &'a RunConfig,
+    bound: usize,
+    /// Files are kept once the search is stopped, since they are of the last depths it searches.
+    stop: Stop,
+}
+
+impl Provider<'_> {
+    fn keep(&self, depth: usize) -> bool {
+        depth >= self.bound || self.stop.is_set()
+    }
+}
+
+impl BfsSettingsProvider for Provider<'_> {
+    fn chunk_root_idx(&self, chunk_idx: usize) -> usize {
+        self.config.chunk_root_idx(chunk_idx)
+    }
+
+    fn update_files_behavior(&self, depth: usize) -> UpdateFilesBehavior {
+        if self.keep(depth) {
+            UpdateFilesBehavior::MergeAndKeep
+        } else {
+            UpdateFilesBehavior::DontMerge
+        }
+    }
+
+    fn chunk_files_behavior(&self, depth: usize) -> ChunkFilesBehavior {
+        if self.keep(depth) {
+            ChunkFilesBehavior::Keep
+        } else {
+            ChunkFilesBehavior::Delete
+        }
+    }
+}
+
+pub fn init_tracing() {
+    tracing_subscriber::registry()
+        .with(
+            EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| "disk_based_bfs=trace,bfs_common=trace".into()),
+        )
+        .with(
+            tracing_subscriber::fmt::layer()
+                .compact()
+                .with_ansi(false)
+                .with_thread_names(true)
+                .with_line_number(true),
+        )
+        .init();
+}
+
+/// Runs `task` for the state space of `P`, whose initial states are the goal set of `config`.
+/// `variant` is recorded in the manifest of a search.
+pub fn run<P, E, const N: usize>(
+    config: &RunConfig,
+    task: &Task,
+    variant: Variant,
+    expander: E,
+    bounds: Bounds,
+) -> Result<(), TaskError>
+where
+    P: Puzzle,
+    E: BfsExpander<N> + Clone + Sync + Send,
+{
+    if variant.one_way && task.needs_inverses() {
+        return Err(TaskError::OneWay);
+    }
+
+    let goal = config.goal.resolve::<P>()?;
+    match task {
+        Task::Search { limits, dump } => search::<P, E, &SymmetryClasses, N>(
+            config,
+            variant,
+            expander,
+            bounds,
+            &goal,
+            *limits,
+            dump.as_ref(),
+            None,
+        ),
+        Task::Solve(scramble) => solve::<P, E, N>(config, expander, &goal, scramble),
+        Task::ExportPruning(format) => export_pruning::<P, E, N>(config, expander, &goal, *format),
+        Task::Enumerate {
+            scramble,
+            extra,
+            filters,
+        } => enumerate::<P, E, N>(config, expander, &goal, scramble, *extra, filters),
+        Task::CountSolutions => count_solutions::<P, E, N>(config, expander, &goal),
+        Task::Ida { scramble, pruning } => {
+            ida::<P, E, N>(config, expander, &goal, scramble, *pruning)
+        }
+        Task::Estimate {
+            samples,
+            max_states,
+            seed,
+        } => estimate::<P, E, N>(config, &expander, &goal, *samples, *max_states, *seed),
+    }
+}
+
+/// Runs `task` for the classes of the state space of `P` under `reduction`. `expander` works on
+/// the whole state space, and the initial states are the classes of the goal set of `config`,
+/// which the symmetries must map to itself. `M` is the number of expansion nodes of the
+/// [`ReducedExpander`], i.e. `N * reduction.expansions()`.
+pub fn run_reduced<P, E, R, const N: usize, const M: usize>(
+    config: &RunConfig,
+    task: &Task,
+    variant: Variant,
+    expander: E,
+    bounds: Bounds,
+    reduction: R,
+) -> Result<(), TaskError>
+where
+    P: Puzzle,
+    E: BfsExpander<N> + Clone + Sync + Send,
+    R: Reduction,
+{
+    if variant.one_way && task.needs_inverses() {
+        return Err(TaskError::OneWay);
+    }
+
+    let goal = config.goal.resolve::<P>()?;
+    match task {
+        Task::Search { limits, dump } => {
+            assert_eq!(M, N * reduction.expansions());
+            let expander = ReducedExpander::<E, R, N> {
+                expander,
+                reduction: reduction.clone(),
+            };
+            search::<P, _, R, M>(
+                config,
+                variant,
+                expander,
+                bounds,
+                &goal,
+                *limits,
+                dump.as_ref(),
+                Some(reduction),
+            )
+        }
+        // The depths of the states don't depend on how the search is reduced
+        Task::Estimate {
+            samples,
+            max_states,
+            seed,
+        } => estimate::<P, E, N>(config, &expander, &goal, *samples, *max_states, *seed),
+        _ => Err(TaskError::Reduced),
+    }
+}
+
+/// Searches the state space of `P`, or its classes under `reduction` if it is given, starting
+/// from `goal`, and writes the distance distribution and antipodes to
+/// `config.report_directory`, and the depth table to `config.depth_table_directory` if it is set.
+/// If the first root directory has a checkpoint of the same search, it carries on from there.
+/// Refuses to start if a root directory has the manifest of a different search.
+///
+/// The search stops after the first depth at which one of `limits` is reached. Everything is
+/// still written for the depths searched, and chunk and update files are kept from the maximum
+/// depth on, or from the depth at which the search is stopped. The checkpoint is kept too, so that
+/// running the search again with other limits carries on from there.
+///
+/// The states at the depths of `dump` are written to `config.report_directory` as well, one file
+/// per depth, instead of being logged.
+#[allow(clippy::too_many_arguments)]
+fn search<P, E, R, const N: usize>(
+    config: &RunConfig,
+    variant: Variant,
+    expander: E,
+    bounds: Bounds,
+    goal: &GoalSet,
+    limits: Limits,
+    dump: Option<&Dump>,
+    mut reduction: Option<R>,
+) -> Result<(), TaskError>
+where
+    P: Puzzle,
+    E: BfsExpander<N> + Clone + Sync + Send,
+    R: Reduction,
+{
+    tracing::info!("starting search of {}", P::ID);
+
+    let state_size = reduction.as_ref().map_or(P::STATE_SIZE, R::state_size);
+    let initial_states = match &mut reduction {
+        Some(reduction) => {
+            let mut classes = goal
+                .states
+                .iter()
+                .map(|&state| reduction.class(state))
+                .collect::<Vec<_>>();
+            classes.sort_unstable();
+            classes.dedup();
+            // Each class is only in the goal set if all of its states are
+            let size = classes.iter().map(|&c| reduction.size(c)).sum::<u64>();
+            if size != goal.states.len() as u64 {
+                return Err(TaskError::AsymmetricGoal(goal.description.clone()));
+            }
+            classes
+        }
+        None => goal.states.clone(),
+    };
+    if !goal.is_solved::<P>() {
+        tracing::info!(
+            "starting from the goal set {} of {} states",
+            goal.description,
+            goal.states.len()
+        );
+    }
+    let one_way = variant.one_way;
+    let mut manifest = Manifest::new(
+        P::ID,
+        config,
+        variant,
+        goal,
+        reduction.is_some(),
+        state_size,
+    );
+    for directory in &config.root_directories {
+        let path = manifest::path(directory);
+        if let Some(existing) = Manifest::read(&path)? {
+            existing.check(&path, &manifest)?;
+            if existing.finished.is_none() {
+                manifest.started = existing.started;
+            }
+        }
+    }
+    manifest.write()?;
+
+    let counter = DepthCounter::new();
+    let state_counter = DepthCounter::new();
+    let antipodes = AntipodeCollector::new(bounds.log);
+
+    let depth_table_directory = match (&config.depth_table_directory, &reduction) {
+        (Some(_), Some(_)) => {
+            tracing::warn!("depth tables aren't written when reducing by symmetry");
+            None
+        }
+        (directory, _) => directory.as_ref(),
+    };
+    let depth_table_path =
+        depth_table_directory.map(|directory| depths::path(directory, &config.name));
+
+    let checkpoint_path = checkpoint::path(&config.root_directories[0]);
+    let expected = Checkpoint::new(
+        P::ID,
+        &config.name,
+        state_size,
+        depth_table_path.as_deref().map(DepthTableWriter::tmp_path),
+    );
+    let restored = match Checkpoint::read(&checkpoint_path)? {
+        Some(checkpoint) => {
+            checkpoint.check(&checkpoint_path, &expected)?;
+            checkpoint
+        }
+        None => expected,
+    };
+    let resuming = restored.depth().is_some();
+    if let Some(depth) = restored.depth() {
+        tracing::info!(
+            "resuming search after depth {depth} from {}",
+            checkpoint_path.display()
+        );
+    }
+    if let Some(restored) = &restored.antipodes {
+        for &state in &restored.states {
+            antipodes.add(restored.depth, state);
+        }
+    }
+
+    let depth_table = depth_table_path
+        .map(|path| {
+            if resuming {
+                tracing::info!("resuming depth table at {}", path.display());
+                DepthTableWriter::resume(&path, P::STATE_SIZE)
+            } else {
+                tracing::info!("writing depth table to {}", path.display());
+                DepthTableWriter::create(&path, P::STATE_SIZE)
+            }
+            .map(Arc::new)
+        })
+        .transpose()?;
+    if let Some(depth_table) = &depth_table {
+        for &state in &initial_states {
+            depth_table.set(state, 0);
+        }
+    }
+
+    let progress = Arc::new(Progress {
+        path: checkpoint_path.clone(),
+        restored,
+        num_chunks: config.num_chunks(state_size),
+        finished_chunks: Mutex::new(Vec::new()),
+        initial: initial_states.len() as u64,
+        limits,
+        stop: Stop::new(),
+        stopped: OnceLock::new(),
+        counter: counter.clone(),
+        class_sizes: reduction.is_some().then(|| state_counter.clone()),
+        antipodes: antipodes.clone(),
+        depths: depth_table.clone(),
+    });
+    let dump_files = dump.map(|dump| {
+        Arc::new(DumpFiles::new::<P>(
+            &config.report_directory,
+            &config.name,
+            dump.format,
+            reduction.is_some(),
+        ))
+    });
+
+    // A search resumed at or past one of its limits only has to finish up
+    progress.check_limits(progress.restored.depth().unwrap_or(0));
+
+    BfsBuilder::new()
+        .threads(config.threads)
+        .chunk_size_bytes(config.chunk_size_bytes)
+        .update_memory(config.update_memory)
+        .num_update_blocks(config.num_update_blocks)
+        .capacity_check_frequency(config.capacity_check_frequency)
+        .initial_states(&initial_states)
+        .state_size(state_size)
+        .root_directories(&config.root_directories)
+        .initial_memory_limit(config.initial_memory_limit)
+        .available_disk_space_limit(config.available_disk_space_limit)
+        .update_array_threshold(config.update_array_threshold)
+        .use_locked_io(config.use_locked_io)
+        .sync_filesystem(config.sync_filesystem)
+        .compute_checksums(config.compute_checksums)
+        .use_compression(config.use_compression)
+        .expander(LimitedExpander {
+            expander: expander.clone(),
+            stop: progress.stop.clone(),
+        })
+        .callback(Callback {
+            log: bounds.log,
+            counter,
+            antipodes: antipodes.clone(),
+            depths: depth_table.clone(),
+            classes: reduction
+                .clone()
+                .map(|reduction| (state_counter, reduction)),
+            dump: dump.zip(dump_files.as_ref()).map(|(dump, files)| {
+                DumpWriter::new::<P>(
+                    Arc::clone(files),
+                    dump.depths.0.clone(),
+                    reduction.is_some(),
+                )
+            }),
+            progress: Arc::clone(&progress),
+        })
+        .settings_provider(Provider {
+            config,
+            bound: limits
+                .max_depth
+                .map_or(bounds.keep, |max| max.min(bounds.keep)),
+            stop: progress.stop.clone(),
+        })
+        .run_no_defaults()?;
+
+    let searched = distribution(progress.counts(), initial_states.len() as u64);
+    let (distribution, class_distribution) = match &mut reduction {
+        Some(reduction) => {
+            let initial_size = initial_states.iter().map(|&c| reduction.size(c)).sum();
+            let states = distribution(progress.class_sizes().unwrap_or_default(), initial_size);
+            (states, Some((searched, reduction.class_count())))
+        }
+        None => (searched, None),
+    };
+    let stopped = progress.stopped.get().copied();
+    drop(progress);
+    let mut report = distribution.report(P::ID, &config.name, P::STATE_SIZE);
+    if let Some((classes, class_count)) = &class_distribution {
+        report = report.with_classes(classes, *class_count);
+    }
+    // Stopping only makes a difference if there were states left to search
+    if !goal.is_solved::<P>() {
+        report = report.with_goal(&goal.description, goal.states.len());
+    }
+    if let Some(reason) = stopped.filter(|_| !report.is_complete()) {
+        report = report.stopped(reason);
+        manifest.stopped = Some(reason.to_string());
+    }
+    tracing::info!("distance distribution:\n{report}");
+    if !report.is_complete() {
+        tracing::warn!(
+            "reached {} of {} states",
+            distribution.total(),
+            P::STATE_SIZE
+        );
+    }
+
+    manifest.finished = Some(chrono::Utc::now());
+    manifest.counts = distribution.counts().to_vec();
+    manifest.classes = class_distribution
+        .as_ref()
+        .map(|(classes, _)| classes.counts().to_vec());
+    if let Err(err) = manifest.write() {
+        tracing::error!("failed to write manifest: {err}");
+    }
+
+    match report.write(&config.report_directory, &config.name) {
+        Ok(()) => tracing::info!(
+            "wrote distance distribution to {}",
+            config.report_directory.display()
+        ),
+        Err(err) => tracing::error!(
+            "failed to write distance distribution to {}: {err}",
+            config.report_directory.display()
+        ),
+    }
+
+    // Every clone of the writers has been dropped along with the callbacks
+    if let Some(files) = dump_files {
+        match Arc::into_inner(files).unwrap().finish() {
+            Ok(written) if written.is_empty() => {
+                tracing::warn!("the search found no states at the depths to write out")
+            }
+            Ok(written) => {
+                for (path, rows) in written {
+                    tracing::info!("wrote {rows} states to {}", path.display());
+                }
+            }
+            Err(err) => tracing::error!("failed to write out states: {err}"),
+        }
+    }
+    let depth_table = depth_table
+        .map(|writer| Arc::into_inner(writer).unwrap().finish())
+        .transpose()?;
+
+    // A search that was stopped early can be carried on by running it again, and otherwise there
+    // is nothing left to resume
+    if manifest.stopped.is_none() {
+        Checkpoint::remove(&checkpoint_path)?;
+    }
+
+    // Antipodes are only collected from `bounds.log` on, so there are none if the search ended
+    // before that depth
+    let Some(mut antipodes) = antipodes.antipodes() else {
+        return Ok(());
+    };
+    if let Some(reduction) = &mut reduction {
+        for state in &mut antipodes.states {
+            *state = reduction.representative(*state);
+        }
+    }
+
+    let mut expander = expander;
+    let is_goal = |state| initial_states.contains(&state);
+    let solved = P::solved().encode();
+    // The depth table only leads back to the goal states if every move can be undone by a move
+    let depth_table = depth_table.filter(|_| !one_way);
+    let scramble = |state| {
+        let solution = solution::<P, E, N>(depth_table.as_ref()?, &mut expander, state, is_goal)?;
+        // A scramble starts from the solved state, so there is none for a state whose solution
+        // ends at another state of the goal set
+        let mut puzzle = P::from_coord(state);
+        for &mv in &solution {
+            mv.apply(&mut puzzle);
+        }
+        (puzzle.encode() == solved)
+            .then(|| notation::format::<P>(&notation::invert::<P>(&solution)))
+    };
+
+    let path = config
+        .report_directory
+        .join(format!("{}.antipodes.txt", config.name));
+    match antipodes.write::<P>(&path, &config.name, scramble) {
+        Ok(()) => tracing::info!(
+            "wrote {} antipodes to {}",
+            antipodes.states.len(),
+            path.display()
+        ),
+        Err(err) => tracing::error!("failed to write antipodes to {}: {err}", path.display()),
+    }
+
+    Ok(())
+}
+
+/// The distribution of `counts`, with at least `initial` states at depth 0, since the initial
+/// states aren't necessarily passed to the callback.
+fn distribution(mut counts: Vec<u64>, initial: u64) -> DistanceDistribution {
+    if counts.is_empty() {
+        counts.push(0);
+    }
+    counts[0] = counts[0].max(initial);
+    DistanceDistribution::new(counts)
+}
+
+/// Estimates the distance distribution from `samples` random states, and prints it and writes it
+/// to `config.report_directory`.
+fn estimate<P, E, const N: usize>(
+    config: &RunConfig,
+    expander: &E,
+    goal: &GoalSet,
+    samples: u64,
+    max_states: usize,
+    seed: Option<u64>,
+) -> Result<(), TaskError>
+where
+    P: Puzzle,
+    E: BfsExpander<N> + Clone + Sync + Send,
+{
+    let seed = seed.unwrap_or_else(rand::random);
+    tracing::info!(
+        "estimating the distance distribution of {} from {samples} random states with seed {seed}",
+        P::ID
+    );
+
+    let estimate = estimate::estimate::<P, E, N>(
+        expander,
+        &goal.states,
+        samples,
+        max_states,
+        seed,
+        config.threads,
+    );
+    let report = estimate.report(P::ID, &config.name, P::STATE_SIZE, max_states, seed);
+    println!("{report}");
+    if estimate.limited() > 0 {
+        tracing::warn!(
+            "{} samples reached the limit of {max_states} states, so the deepest depths are \
+             underestimated",
+            estimate.limited()
+        );
+    }
+
+    match report.write(&config.report_directory, &config.name) {
+        Ok(()) => tracing::info!(
+            "wrote estimated distance distribution to {}",
+            config.report_directory.display()
+        ),
+        Err(err) => tracing::error!(
+            "failed to write estimated distance distribution to {}: {err}",
+            config.report_directory.display()
+        ),
+    }
+
+    Ok(())
+}
+
+/// Prints an optimal solution of `scramble`, which takes it to the nearest state of `goal`.
+fn solve<P, E, const N: usize>(
+    config: &RunConfig,
+    mut expander: E,
+    goal: &GoalSet,
+    scramble: &str,
+) -> Result<(), TaskError>
+where
+    P: Puzzle,
+    E: BfsExpander<N>,
+{
+    let state = notation::apply::<P>(&notation::parse::<P>(scramble)?).encode();
+
+    let directory = config
+        .depth_table_directory
+        .as_ref()
+        .ok_or(TaskError::NoDepthTable)?;
+    let depth_table = DepthTable::open(&depths::path(directory, &config.name), P::STATE_SIZE)?;
+
+    let solution = solution::<P, E, N>(&depth_table, &mut expander, state, |s| goal.contains(s))
+        .ok_or(TaskError::Unreached { state })?;
+    tracing::info!("found a solution of length {}", solution.len());
+    // The solutions are only counted on request, so there may be no table of them
+    let path = solutions::path(directory, &config.name);
+    if let Ok(counts) = SolutionCounts::open(&path, P::STATE_SIZE) {
+        if let Some(count) = counts.get(state) {
+            tracing::info!("the scramble has {count} optimal solutions");
+        }
+    }
+    println!("{}", notation::format::<P>(&solution));
+
+    Ok(())
+}
+
+/// Prints every solution of `scramble` to the nearest state of `goal` that is at most `extra`
+/// moves longer than an optimal one and passes `filters`, one per line, shortest first.
+fn enumerate<P, E, const N: usize>(
+    config: &RunConfig,
+    mut expander: E,
+    goal: &GoalSet,
+    scramble: &str,
+    extra: usize,
+    filters: &[MoveFilter],
+) -> Result<(), TaskError>
+where
+    P: Puzzle,
+    E: BfsExpander<N>,
+{
+    let state = notation::apply::<P>(&notation::parse::<P>(scramble)?).encode();
+
+    let directory = config
+        .depth_table_directory
+        .as_ref()
+        .ok_or(TaskError::NoDepthTable)?;
+    let depth_table = DepthTable::open(&depths::path(directory, &config.name), P::STATE_SIZE)?;
+
+    let is_goal = |s| goal.contains(s);
+    let mut enumerator = Enumerator::new::<P>(&depth_table, &mut expander, &is_goal, filters)
+        .map_err(TaskError::Filter)?;
+    let depth = enumerator
+        .depth(state)
+        .ok_or(TaskError::Unreached { state })?;
+    for length in depth..=depth + extra {
+        let mut count = 0u64;
+        enumerator.enumerate(state, depth, length, &mut |moves| {
+            count += 1;
+            println!("{}", notation::format::<P>(moves));
+        });
+        tracing::info!("found {count} solutions of length {length}");
+    }
+
+    Ok(())
+}
+
+/// Writes the number of optimal solutions of every state next to the depth table, and the
+/// statistics of the counts at each depth to `config.report_directory`.
+fn count_solutions<P, E, const N: usize>(
+    config: &RunConfig,
+    expander: E,
+    goal: &GoalSet,
+) -> Result<(), TaskError>
+where
+    P: Puzzle,
+    E: BfsExpander<N> + Clone + Sync + Send,
+{
+    let directory = config
+        .depth_table_directory
+        .as_ref()
+        .ok_or(TaskError::NoDepthTable)?;
+    let depth_table = DepthTable::open(&depths::path(directory, &config.name), P::STATE_SIZE)?;
+
+    // The table is written to a sparse file, which would only run out of space part way through
+    let needed = solutions::file_len(P::STATE_SIZE);
+    let available = plan::available_space(directory).map_err(|source| TableFileError::Io {
+        path: directory.clone(),
+        source,
+    })?;
+    if needed > available {
+        return Err(TaskError::NoSpace {
+            directory: directory.clone(),
+            needed,
+            available,
+        });
+    }
+
+    let path = solutions::path(directory, &config.name);
+    tracing::info!("counting optimal solutions into {}", path.display());
+    let stats = solutions::count(&depth_table, &expander, &goal.states, &path, config.threads)?;
+    let report = stats.report(P::ID, &config.name);
+    println!("{report}");
+
+    match report.write(&config.report_directory, &config.name) {
+        Ok(()) => tracing::info!(
+            "wrote solution counts to {}",
+            config.report_directory.display()
+        ),
+        Err(err) => tracing::error!(
+            "failed to write solution counts to {}: {err}",
+            config.report_directory.display()
+        ),
+    }
+
+    Ok(())
+}
+
+/// Writes a pruning table in `format` next to the depth table.
+fn export_pruning<P, E, const N: usize>(
+    config: &RunConfig,
+    mut expander: E,
+    goal: &GoalSet,
+    format: PruningFormat,
+) -> Result<(), TaskError>
+where
+    P: Puzzle,
+    E: BfsExpander<N>,
+{
+    let directory = config
+        .depth_table_directory
+        .as_ref()
+        .ok_or(TaskError::NoDepthTable)?;
+    let depth_table = DepthTable::open(&depths::path(directory, &config.name), P::STATE_SIZE)?;
+
+    let path = pruning::path(directory, &config.name, format);
+    tracing::info!("exporting {format} pruning table to {}", path.display());
+    pruning::export(&depth_table, &mut expander, &goal.states, format, &path)?;
+    tracing::info!("exported pruning table");
+
+    Ok(())
+}
+
+/// Prints an optimal solution of `scramble` to the nearest state of `goal`, found with IDA*.
+fn ida<P, E, const N: usize>(
+    config: &RunConfig,
+    mut expander: E,
+    goal: &GoalSet,
+    scramble: &str,
+    format: PruningFormat,
+) -> Result<(), TaskError>
+where
+    P: Puzzle,
+    E: BfsExpander<N>,
+{
+    let state = notation::apply::<P>(&notation::parse::<P>(scramble)?).encode();
+
+    let directory = config
+        .depth_table_directory
+        .as_ref()
+        .ok_or(TaskError::NoDepthTable)?;
+    let path = pruning::path(directory, &config.name, format);
+    let table = PruningTable::open(&path, format, P::STATE_SIZE)?;
+
+    let path = table
+        .solve(&mut expander, state, |s| goal.contains(s))
+        .ok_or(TaskError::Unreached { state })?;
+    let solution = moves::<P>(state, path);
+    tracing::info!("found a solution of length {}", solution.len());
+    println!("{}", notation::format::<P>(&solution));
+
+    Ok(())
+}
+
+/// An optimal sequence of moves from `state` to a goal state, or `None` if `state` wasn't reached
+/// by the search.
+fn solution<P, E, const N: usize>(
+    depth_table: &DepthTable,
+    expander: &mut E,
+    state: u64,
+    is_goal: impl Fn(u64) -> bool,
+) -> Option<Vec<Move>>
+where
+    P: Puzzle,
+    E: BfsExpander<N>,
+{
+    let path = depth_table.path(expander, state, is_goal)?;
+    Some(moves::<P>(state, path))
+}
+
+/// The moves that take `state` along `path`, a sequence of neighbouring states.
+fn moves<P: Puzzle>(state: u64, path: Vec<u64>) -> Vec<Move> {
+    let mut from = state;
+    path.into_iter()
+        .map(|to| {
+            let mv = notation::find_move::<P>(from, to)
+                .expect("the expander's neighbours are one move away on the puzzle");
+            from = to;
+            mv
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use super::*;
+    use crate::{goal::Goal, tables::TableMode, test_util::temp_dir};
+
+    /// Two independent cycles, of lengths 12 and 10.
+    #[derive(Debug, Clone)]
+    struct Cycles {
+        a: u64,
+        b: u64,
+    }
+
+    impl Cycles {
+        fn a(&mut self) {
+            self.a = (self.a + 1) % 12;
+        }
+
+        fn b(&mut self) {
+            self.b = (self.b + 1) % 10;
+        }
+    }
+
+    impl Puzzle for Cycles {
+        const ID: &'static str = "cycles";
+        const STATE_SIZE: u64 = 120;
+        const MOVES: &'static [&'static str] = &["A", "B"];
+        const ORDERS: &'static [usize] = &[12, 10];
+
+        fn solved() -> Self {
+            Self { a: 0, b: 0 }
+        }
+
+        fn encode(&self) -> u64 {
+            self.a * 10 + self.b
+        }
+
+        fn decode(&mut self, coord: u64) {
+            self.a = coord / 10;
+            self.b = coord % 10;
+        }
+
+        fn do_move(&mut self, mv: usize) {
+            match mv {
+                0 => self.a(),
+                _ => self.b(),
+            }
+        }
+    }
+
+    crate::expander! {
+        struct Expander {
+            cycles: Cycles,
+        }
+        const EXPANSION_NODES;
+        metric = Qtm;
+        generators = [a: 12, b: 10];
+    }
+
+    /// A search split into 4 chunks over 2 root directories in `dir`.
+    fn config(dir: &Path) -> RunConfig {
+        RunConfig {
+            name: "cycles".to_owned(),
+            threads: 2,
+            chunk_size_bytes: 4,
+            update_memory: 1 << 16,
+            num_update_blocks: 8,
+            capacity_check_frequency: 16,
+            root_directories: vec![dir.join("a"), dir.join("b")],
+            chunk_root_pattern: vec![0, 1],
+            initial_memory_limit: 1 << 16,
+            available_disk_space_limit: 1 << 20,
+            update_array_threshold: 4,
+            use_locked_io: false,
+            sync_filesystem: false,
+            compute_checksums: true,
+            use_compression: false,
+            table_directory: None,
+            table_mode: TableMode::default(),
+            report_directory: dir.join("reports"),
+            depth_table_directory: Some(dir.join("depths")),
+            goal: Goal::Solved,
+        }
+    }
+
+    /// Searches with `config`, and returns the manifest it leaves behind.
+    fn search(config: &RunConfig, limits: Limits) -> Manifest {
+        let task = Task::Search { limits, dump: None };
+        let expander = Expander {
+            cycles: Cycles::solved(),
+        };
+        let bounds = Bounds {
+            log: Bounds::NEVER,
+            keep: Bounds::NEVER,
+        };
+        run::<Cycles, _, EXPANSION_NODES>(config, &task, Variant::default(), expander, bounds)
+            .unwrap();
+        Manifest::read(&manifest::path(&config.root_directories[0]))
+            .unwrap()
+            .unwrap()
+    }
+
+    fn depth_table(config: &RunConfig) -> DepthTable {
+        let directory = config.depth_table_directory.as_ref().unwrap();
+        DepthTable::open(&depths::path(directory, &config.name), Cycles::STATE_SIZE).unwrap()
+    }
+
+    #[test]
+    fn test_resume_stopped_search() {
+        let dir = temp_dir("runner-resume");
+
+        let whole = config(&dir.join("whole"));
+        let expected = search(&whole, Limits::NONE);
+        // The farthest states are 6 turns of A and 5 of B away
+        assert_eq!(expected.counts.len(), 12);
+        assert_eq!(expected.counts.iter().sum::<u64>(), Cycles::STATE_SIZE);
+
+        let resumed = config(&dir.join("resumed"));
+        let checkpoint_path = checkpoint::path(&resumed.root_directories[0]);
+        let stopped = search(
+            &resumed,
+            Limits {
+                max_depth: Some(4),
+                max_states: None,
+            },
+        );
+        assert_eq!(stopped.counts, expected.counts[..5]);
+        assert!(stopped.stopped.is_some());
+        assert!(checkpoint_path.exists());
+
+        let finished = search(&resumed, Limits::NONE);
+        assert_eq!(finished.counts, expected.counts);
+        assert_eq!(finished.stopped, None);
+        assert!(!checkpoint_path.exists());
+
+        let (whole, resumed) = (depth_table(&whole), depth_table(&resumed));
+        for state in 0..Cycles::STATE_SIZE {
+            assert_eq!(resumed.get(state), whole.get(state), "state {state}");
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_count_solutions() {
+        let dir = temp_dir("runner-count-solutions");
+        let config = config(&dir);
+        search(&config, Limits::NONE);
+
+        let expander = Expander {
+            cycles: Cycles::solved(),
+        };
+        let bounds = Bounds {
+            log: Bounds::NEVER,
+            keep: Bounds::NEVER,
+        };
+        run::<Cycles, _, EXPANSION_NODES>(
+            &config,
+            &Task::CountSolutions,
+            Variant::default(),
+            expander,
+            bounds,
+        )
+        .unwrap();
+
+        // An optimal solution turns each cycle the shorter way round, in any order, and either way
+        // round when both are as long
+        let binomial = |n: u64, k: u64| (1..=k).fold(1, |c, i| c * (n + 1 - i) / i);
+        let directory = config.depth_table_directory.as_ref().unwrap();
+        let counts =
+            SolutionCounts::open(&solutions::path(directory, "cycles"), Cycles::STATE_SIZE)
+                .unwrap();
+        for state in 0..Cycles::STATE_SIZE {
+            let (a, b) = (state / 10, state % 10);
+            let (turns_a, turns_b) = (a.min(12 - a), b.min(10 - b));
+            let expected = binomial(turns_a + turns_b, turns_a)
+                * if a == 6 { 2 } else { 1 }
+                * if b == 5 { 2 } else { 1 };
+            assert_eq!(counts.get(state), Some(expected), "state {state}");
+        }
+        assert!(config
+            .report_directory
+            .join("cycles.solutions.json")
+            .exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_one_way() {
+        let dir = temp_dir("runner-one-way");
+        let variant = Variant {
+            one_way: true,
+            ..Variant::default()
+        };
+        let expander = Expander {
+            cycles: Cycles::solved(),
+        };
+        let bounds = Bounds {
+            log: Bounds::NEVER,
+            keep: Bounds::NEVER,
+        };
+
+        let tasks = [
+            Task::Solve("A".to_owned()),
+            Task::CountSolutions,
+            Task::Estimate {
+                samples: 10,
+                max_states: 100,
+                seed: Some(0),
+            },
+        ];
+        for task in &tasks {
+            let result = run::<Cycles, _, EXPANSION_NODES>(
+                &config(&dir),
+                task,
+                variant.clone(),
+                expander.clone(),
+                bounds,
+            );
+            assert!(matches!(result, Err(TaskError::OneWay)), "{task:?}");
+        }
+        assert!(!dir.exists());
+    }
+}
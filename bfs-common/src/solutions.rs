@@ -0,0 +1,614 @@
+//! The number of optimal solutions of every state, counted from a depth table.
+//!
+//! A state at depth `d` has one optimal solution for each optimal solution of each of its
+//! neighbours at depth `d - 1`, counting a neighbour once for each move that leads to it. The
+//! counts are filled in one depth at a time, starting from 1 for each goal state, and written to
+//! a table of one little-endian `u64` per state, with 0 for the states that weren't reached.
+//!
+//! The counts are of move sequences from the goal states, so they are only counts of solutions in
+//! metrics that aren't [one-way](crate::manifest::Variant::one_way), and the runner doesn't count
+//! them in the others. They saturate at [`MAX_COUNT`].
+
+use std::{
+    cmp, fmt,
+    fs::{self, File},
+    io,
+    mem::align_of,
+    ops::Range,
+    path::{Path, PathBuf},
+    slice,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use disk_based_bfs::expander::BfsExpander;
+use memmap2::{Mmap, MmapMut};
+use rayon::iter::{IntoParallelIterator as _, IntoParallelRefIterator as _, ParallelIterator as _};
+use serde::Serialize;
+
+use crate::{depths::DepthTable, tables::TableFileError};
+
+const MAGIC: [u8; 8] = *b"BFSCOUNT";
+
+/// Version of the file layout. Bump this whenever the layout changes.
+const VERSION: u32 = 1;
+
+/// Magic, version, padding and state size.
+const HEADER_LEN: usize = 24;
+
+/// The largest count that is stored. The top bit of each entry marks the states whose count is
+/// final while the table is being filled in.
+pub const MAX_COUNT: u64 = (1 << 63) - 1;
+
+const FINISHED: u64 = 1 << 63;
+
+/// The path of the solution count table of the run `name` in `directory`.
+pub fn path(directory: &Path, name: &str) -> PathBuf {
+    directory.join(format!("{name}.solutions"))
+}
+
+fn header(state_size: u64) -> [u8; HEADER_LEN] {
+    let mut header = [0; HEADER_LEN];
+    header[..8].copy_from_slice(&MAGIC);
+    header[8..12].copy_from_slice(&VERSION.to_le_bytes());
+    header[16..24].copy_from_slice(&state_size.to_le_bytes());
+    header
+}
+
+fn data_len(state_size: u64) -> usize {
+    state_size as usize * 8
+}
+
+fn get(data: &[u8], state: u64) -> u64 {
+    let i = state as usize * 8;
+    u64::from_le_bytes(data[i..i + 8].try_into().unwrap())
+}
+
+/// The length of the solution count table of a state space of `state_size` states.
+pub fn file_len(state_size: u64) -> u64 {
+    (HEADER_LEN + data_len(state_size)) as u64
+}
+
+/// The number of states that one thread scans at a time when counting.
+const CHUNK_STATES: u64 = 1 << 20;
+
+/// Views the entries of a table being filled in as atomics, so that the threads can add to the
+/// counts of the neighbours in any chunk. The values are still little-endian.
+fn entries(data: &mut [u8]) -> &[AtomicU64] {
+    assert_eq!(data.as_ptr().align_offset(align_of::<AtomicU64>()), 0);
+    // SAFETY: the data is aligned, borrowed mutably for the lifetime of the result, and every bit
+    // pattern is a valid `AtomicU64`.
+    unsafe { slice::from_raw_parts(data.as_mut_ptr().cast(), data.len() / 8) }
+}
+
+fn load(entry: &AtomicU64) -> u64 {
+    u64::from_le(entry.load(Ordering::Relaxed))
+}
+
+fn store(entry: &AtomicU64, value: u64) {
+    entry.store(value.to_le(), Ordering::Relaxed);
+}
+
+/// Adds `count` to the count of `entry`, saturating at [`MAX_COUNT`].
+fn add(entry: &AtomicU64, count: u64) {
+    let sum = |value: u64| {
+        u64::from_le(value)
+            .saturating_add(count)
+            .min(MAX_COUNT)
+            .to_le()
+    };
+    // The update always succeeds, since it never returns `None`
+    let _ = entry.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |value| {
+        Some(sum(value))
+    });
+}
+
+/// Counts the optimal solutions of every state reached by the search that `depths` came from, and
+/// writes them to `path`, which takes [`file_len`] bytes. `expander` must be the one used for the
+/// search, and `initial_states` the states it started from. Returns the statistics of the counts
+/// at each depth.
+///
+/// Like exporting a capped pruning table, each depth scans every state for the ones at that
+/// depth, in chunks of states spread over `threads` threads.
+pub fn count<E, const N: usize>(
+    depths: &DepthTable,
+    expander: &E,
+    initial_states: &[u64],
+    path: &Path,
+    threads: usize,
+) -> Result<SolutionStats, TableFileError>
+where
+    E: BfsExpander<N> + Clone + Send + Sync,
+{
+    let state_size = depths.state_size();
+    let tmp_path = path.with_extension("solutions.tmp");
+    let io_err = |path: &Path| {
+        let path = path.to_owned();
+        move |source| TableFileError::Io { path, source }
+    };
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(io_err(parent))?;
+    }
+
+    let file = File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&tmp_path)
+        .map_err(io_err(&tmp_path))?;
+    file.set_len(file_len(state_size))
+        .map_err(io_err(&tmp_path))?;
+
+    // SAFETY: the temporary file is only used here.
+    let mut mmap = unsafe { MmapMut::map_mut(&file) }.map_err(io_err(&tmp_path))?;
+    mmap[..HEADER_LEN].copy_from_slice(&header(state_size));
+    let entries = entries(&mut mmap[HEADER_LEN..]);
+
+    for &state in initial_states {
+        store(&entries[state as usize], 1);
+    }
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .expect("failed to start the counting threads");
+    let chunks = state_size.div_ceil(CHUNK_STATES);
+
+    let mut stats = SolutionStats::default();
+    for depth in 0.. {
+        tracing::info!("counting solutions at depth {depth}");
+
+        // Only the states at this depth are read and marked as finished, and only the ones at the
+        // next depth are added to, so the chunks don't depend on each other
+        let layer = pool.install(|| {
+            (0..chunks)
+                .into_par_iter()
+                .map_init(
+                    || (expander.clone(), [0; N]),
+                    |(expander, neighbours), chunk| {
+                        let start = chunk * CHUNK_STATES;
+                        let end = (start + CHUNK_STATES).min(state_size);
+                        count_chunk(depths, expander, neighbours, entries, depth, start..end)
+                    },
+                )
+                .reduce(DepthSolutions::default, DepthSolutions::merge)
+        });
+
+        if layer.positions == 0 {
+            break;
+        }
+        stats.depths.push(layer);
+    }
+
+    pool.install(|| {
+        entries.par_iter().for_each(|entry| {
+            store(entry, load(entry) & !FINISHED);
+        });
+    });
+
+    mmap.flush().map_err(io_err(&tmp_path))?;
+    drop(mmap);
+    fs::rename(&tmp_path, path).map_err(io_err(path))?;
+    Ok(stats)
+}
+
+/// Finishes the counts of the states at `depth` in `states`, and adds them to the counts of their
+/// neighbours at the next depth.
+fn count_chunk<E, const N: usize>(
+    depths: &DepthTable,
+    expander: &mut E,
+    neighbours: &mut [u64; N],
+    entries: &[AtomicU64],
+    depth: usize,
+    states: Range<u64>,
+) -> DepthSolutions
+where
+    E: BfsExpander<N>,
+{
+    let mut layer = DepthSolutions::default();
+
+    // The states at this depth are the ones with the right depth modulo 3 that have a count, but
+    // aren't finished yet
+    for state in states {
+        let entry = &entries[state as usize];
+        let count = load(entry);
+        if count == 0 || count & FINISHED != 0 || depths.get(state) != Some(depth % 3) {
+            continue;
+        }
+        layer.add(state, count);
+        store(entry, count | FINISHED);
+
+        // The neighbours one layer further out are the ones with the next depth mod 3
+        expander.expand(state, neighbours);
+        for &neighbour in neighbours.iter() {
+            if depths.get(neighbour) == Some((depth + 1) % 3) {
+                add(&entries[neighbour as usize], count);
+            }
+        }
+    }
+
+    layer
+}
+
+/// A table of solution counts, mapped from a file.
+pub struct SolutionCounts {
+    mmap: Mmap,
+    state_size: u64,
+}
+
+impl SolutionCounts {
+    pub fn open(path: &Path, state_size: u64) -> Result<Self, TableFileError> {
+        let io_err = |source| TableFileError::Io {
+            path: path.to_owned(),
+            source,
+        };
+
+        let file = File::open(path).map_err(io_err)?;
+        // SAFETY: count tables are only ever replaced by renaming a new file over them, never
+        // modified in place.
+        let mmap = unsafe { Mmap::map(&file) }.map_err(io_err)?;
+
+        let header = mmap.get(..HEADER_LEN).ok_or(TableFileError::Truncated)?;
+        if header[..8] != MAGIC {
+            return Err(TableFileError::Magic);
+        }
+        let version = u32::from_le_bytes(header[8..12].try_into().unwrap());
+        if version != VERSION {
+            return Err(TableFileError::Version { found: version });
+        }
+        let found_size = u64::from_le_bytes(header[16..24].try_into().unwrap());
+        if found_size != state_size {
+            return Err(TableFileError::Mismatch {
+                what: "state size",
+                found: found_size.to_string(),
+                expected: state_size.to_string(),
+            });
+        }
+        if mmap.len() < HEADER_LEN + data_len(state_size) {
+            return Err(TableFileError::Truncated);
+        }
+
+        Ok(Self { mmap, state_size })
+    }
+
+    /// The number of optimal solutions of `state`, or `None` if it wasn't reached.
+    pub fn get(&self, state: u64) -> Option<u64> {
+        if state >= self.state_size {
+            return None;
+        }
+        let count = get(&self.mmap[HEADER_LEN..], state);
+        (count != 0).then_some(count)
+    }
+}
+
+/// The solution counts of the states at one depth.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DepthSolutions {
+    pub positions: u64,
+    /// The number of positions with a unique optimal solution.
+    pub unique: u64,
+    /// The number of optimal solutions of all the positions together.
+    pub solutions: u128,
+    /// The most optimal solutions of any position, and the first position that has that many.
+    pub max: u64,
+    pub max_state: u64,
+}
+
+impl DepthSolutions {
+    fn add(&mut self, state: u64, count: u64) {
+        self.positions += 1;
+        self.unique += u64::from(count == 1);
+        self.solutions += u128::from(count);
+        if count > self.max {
+            self.max = count;
+            self.max_state = state;
+        }
+    }
+
+    /// The counts of two disjoint sets of positions together. Of the positions with the most
+    /// solutions, the one with the smallest coordinate is kept, as if they were added in order.
+    fn merge(self, other: Self) -> Self {
+        let (max, max_state) = match self.max.cmp(&other.max) {
+            cmp::Ordering::Less => (other.max, other.max_state),
+            cmp::Ordering::Greater => (self.max, self.max_state),
+            cmp::Ordering::Equal => (self.max, self.max_state.min(other.max_state)),
+        };
+        Self {
+            positions: self.positions + other.positions,
+            unique: self.unique + other.unique,
+            solutions: self.solutions + other.solutions,
+            max,
+            max_state,
+        }
+    }
+
+    /// The mean number of optimal solutions of the positions.
+    pub fn mean(&self) -> f64 {
+        self.solutions as f64 / self.positions as f64
+    }
+}
+
+/// The solution counts at each depth, starting from the goal states at depth 0.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SolutionStats {
+    pub depths: Vec<DepthSolutions>,
+}
+
+impl SolutionStats {
+    pub fn positions(&self) -> u64 {
+        self.depths.iter().map(|depth| depth.positions).sum()
+    }
+
+    /// The number of positions with a unique optimal solution.
+    pub fn unique(&self) -> u64 {
+        self.depths.iter().map(|depth| depth.unique).sum()
+    }
+
+    /// The depth of the position with the most optimal solutions, if any were counted.
+    pub fn most(&self) -> Option<(usize, &DepthSolutions)> {
+        self.depths
+            .iter()
+            .enumerate()
+            .rev()
+            .max_by_key(|(_, depth)| depth.max)
+    }
+
+    pub fn report<'a>(&'a self, puzzle: &'a str, run: &'a str) -> SolutionReport<'a> {
+        SolutionReport {
+            puzzle,
+            run,
+            stats: self,
+        }
+    }
+}
+
+/// [`SolutionStats`] with the details of the run they are for. It displays as a plain text table,
+/// and serializes to JSON.
+pub struct SolutionReport<'a> {
+    puzzle: &'a str,
+    run: &'a str,
+    stats: &'a SolutionStats,
+}
+
+#[derive(Serialize)]
+struct JsonSolutions<'a> {
+    puzzle: &'a str,
+    run: &'a str,
+    positions: u64,
+    unique: u64,
+    depths: Vec<JsonDepth<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonDepth<'a> {
+    depth: usize,
+    #[serde(flatten)]
+    solutions: &'a DepthSolutions,
+    mean: f64,
+}
+
+impl SolutionReport<'_> {
+    pub fn to_json(&self) -> String {
+        let report = JsonSolutions {
+            puzzle: self.puzzle,
+            run: self.run,
+            positions: self.stats.positions(),
+            unique: self.stats.unique(),
+            depths: self
+                .stats
+                .depths
+                .iter()
+                .enumerate()
+                .map(|(depth, solutions)| JsonDepth {
+                    depth,
+                    solutions,
+                    mean: solutions.mean(),
+                })
+                .collect(),
+        };
+        serde_json::to_string_pretty(&report).unwrap()
+    }
+
+    /// Writes the report to `<name>.solutions.txt` and `<name>.solutions.json` in `directory`,
+    /// creating it if needed.
+    pub fn write(&self, directory: &Path, name: &str) -> io::Result<()> {
+        fs::create_dir_all(directory)?;
+        fs::write(
+            directory.join(format!("{name}.solutions.txt")),
+            self.to_string() + "\n",
+        )?;
+        fs::write(
+            directory.join(format!("{name}.solutions.json")),
+            self.to_json() + "\n",
+        )
+    }
+}
+
+impl fmt::Display for SolutionReport<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stats = self.stats;
+        let width = |value: String, header: &str| value.len().max(header.len());
+        let positions_width = width(stats.positions().to_string(), "positions");
+        let solutions_width = stats
+            .depths
+            .iter()
+            .map(|depth| width(depth.solutions.to_string(), "solutions"))
+            .max()
+            .unwrap_or(0);
+        let max_width = stats
+            .depths
+            .iter()
+            .map(|depth| width(depth.max.to_string(), "max"))
+            .max()
+            .unwrap_or(0);
+
+        writeln!(f, "{} ({})", self.puzzle, self.run)?;
+        writeln!(f, "number of optimal solutions of each position")?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "depth  {:>positions_width$}  {:>positions_width$}  {:>solutions_width$}  {:>8}  \
+             {:>max_width$}",
+            "positions", "unique", "solutions", "mean", "max"
+        )?;
+        for (depth, solutions) in stats.depths.iter().enumerate() {
+            writeln!(
+                f,
+                "{depth:>5}  {:>positions_width$}  {:>positions_width$}  {:>solutions_width$}  \
+                 {:>8.3}  {:>max_width$}",
+                solutions.positions,
+                solutions.unique,
+                solutions.solutions,
+                solutions.mean(),
+                solutions.max
+            )?;
+        }
+        writeln!(f)?;
+
+        let positions = stats.positions();
+        writeln!(
+            f,
+            "positions with a unique optimal solution: {} of {positions} ({:.6})",
+            stats.unique(),
+            stats.unique() as f64 / positions as f64
+        )?;
+        match stats.most() {
+            Some((depth, solutions)) => write!(
+                f,
+                "most optimal solutions: {} (state {} at depth {depth})",
+                solutions.max, solutions.max_state
+            ),
+            None => write!(f, "most optimal solutions: none"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{
+        depths::{self, DepthTableWriter},
+        test_util::temp_dir,
+    };
+
+    /// Adding 1, 3 or their inverses modulo 20.
+    #[derive(Clone)]
+    struct Expander;
+
+    impl BfsExpander<4> for Expander {
+        fn expand(&mut self, node: u64, expanded_nodes: &mut [u64; 4]) {
+            *expanded_nodes = [
+                (node + 1) % 20,
+                (node + 19) % 20,
+                (node + 3) % 20,
+                (node + 17) % 20,
+            ];
+        }
+    }
+
+    /// The depth and number of optimal solutions of every state, from a plain BFS.
+    fn bfs(initial_states: &[u64]) -> HashMap<u64, (usize, u64)> {
+        let mut found = initial_states
+            .iter()
+            .map(|&state| (state, (0, 1)))
+            .collect::<HashMap<_, _>>();
+        let mut layer = initial_states.to_vec();
+        let mut neighbours = [0; 4];
+        for depth in 1.. {
+            let mut next = Vec::new();
+            for state in layer {
+                let count = found[&state].1;
+                Expander.expand(state, &mut neighbours);
+                for neighbour in neighbours {
+                    let entry = found.entry(neighbour).or_insert_with(|| {
+                        next.push(neighbour);
+                        (depth, 0)
+                    });
+                    if entry.0 == depth {
+                        entry.1 += count;
+                    }
+                }
+            }
+            if next.is_empty() {
+                return found;
+            }
+            layer = next;
+        }
+        unreachable!()
+    }
+
+    #[test]
+    fn test_count() {
+        let dir = temp_dir("solutions-count");
+        for initial_states in [&[0][..], &[0, 10]] {
+            let expected = bfs(initial_states);
+            let writer = DepthTableWriter::create(&depths::path(&dir, "cycle"), 21).unwrap();
+            for (&state, &(depth, _)) in &expected {
+                writer.set(state, depth);
+            }
+            let table = writer.finish().unwrap();
+
+            let path = path(&dir, "cycle");
+            let stats = count(&table, &Expander, initial_states, &path, 2).unwrap();
+            let counts = SolutionCounts::open(&path, 21).unwrap();
+            for state in 0..20 {
+                assert_eq!(counts.get(state), Some(expected[&state].1), "{state}");
+            }
+            assert_eq!(counts.get(20), None);
+            assert_eq!(stats.positions(), 20);
+            assert_eq!(
+                stats.unique(),
+                expected.values().filter(|&&(_, count)| count == 1).count() as u64
+            );
+        }
+
+        // From 0, state 10 is three 3s and a 1 away, in either direction and in any order
+        assert_eq!(bfs(&[0])[&10], (4, 8));
+        assert!(matches!(
+            SolutionCounts::open(&path(&dir, "cycle"), 20),
+            Err(TableFileError::Mismatch { .. })
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_report() {
+        let mut stats = SolutionStats::default();
+        for (depth, counts) in [&[1][..], &[1, 1], &[1, 2, 2, 1]].into_iter().enumerate() {
+            let mut layer = DepthSolutions::default();
+            for (i, &count) in counts.iter().enumerate() {
+                layer.add((depth * 10 + i) as u64, count);
+            }
+            stats.depths.push(layer);
+        }
+        assert_eq!(stats.positions(), 7);
+
+        // Counting the last depth in two chunks gives the same result
+        let (mut first, mut second) = (DepthSolutions::default(), DepthSolutions::default());
+        for (state, count) in [(20, 1), (21, 2)] {
+            first.add(state, count);
+        }
+        for (state, count) in [(22, 2), (23, 1)] {
+            second.add(state, count);
+        }
+        assert_eq!(second.clone().merge(first.clone()), stats.depths[2]);
+        assert_eq!(first.merge(second), stats.depths[2]);
+        assert_eq!(stats.unique(), 5);
+        assert_eq!(stats.most().unwrap().0, 2);
+
+        let report = stats.report("cycle", "cycle.htm");
+        let text = report.to_string();
+        assert!(text.starts_with("cycle (cycle.htm)\n"), "{text}");
+        assert!(text.contains("positions with a unique optimal solution: 5 of 7 (0.714286)\n"));
+        assert!(text.ends_with("most optimal solutions: 2 (state 21 at depth 2)"));
+
+        let json = serde_json::from_str::<serde_json::Value>(&report.to_json()).unwrap();
+        assert_eq!(json["unique"], 5);
+        assert_eq!(json["depths"][2]["solutions"], 6);
+        assert_eq!(json["depths"][2]["mean"], 1.5);
+    }
+}
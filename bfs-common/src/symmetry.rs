@@ -0,0 +1,463 @@
+//! Reduces a state space by a group of symmetries, so that a search visits one state from each
+//! class of equivalent states instead of all of them.
+//!
+//! The symmetries must map the initial states to themselves and the moves of the metric to moves
+//! of the metric, so that every state in a class is at the same depth. A [`Reduction`] gives each
+//! class a coordinate, and a [`ReducedExpander`] searches those coordinates with an expander for
+//! the whole state space.
+//!
+//! Most symmetries commute with moves: the neighbours of the image of a state are the images of
+//! its neighbours. Antisymmetries, like taking the inverse of a state, reverse the order of moves
+//! instead, so they also take a state's depth to itself but turn a move made after it into a move
+//! made before it.
+
+use disk_based_bfs::expander::BfsExpander;
+
+use crate::puzzle::Puzzle;
+
+/// Maps the states of a state space to the coordinates of their classes under a group of
+/// symmetries.
+pub trait Reduction: Clone + Send + Sync {
+    /// The number of coordinates used for classes.
+    fn state_size(&self) -> u64;
+
+    /// The number of classes, if it is known without searching.
+    fn class_count(&self) -> Option<u64>;
+
+    fn class(&mut self, state: u64) -> u64;
+
+    /// A state in `class`.
+    fn representative(&mut self, class: u64) -> u64;
+
+    /// The number of states in `class`.
+    fn size(&mut self, class: u64) -> u64;
+
+    /// The number of states expanded for each class by a [`ReducedExpander`].
+    fn expansions(&self) -> usize {
+        1
+    }
+
+    /// The `i`th state expanded for `class`, for `i < self.expansions()`. Between them, their
+    /// neighbours must be in the classes of the neighbours of every state in `class`.
+    ///
+    /// For symmetries that commute with moves the representative is enough, but a group with
+    /// antisymmetries also needs the image of the representative under one of them.
+    fn expanded_state(&mut self, class: u64, i: usize) -> u64 {
+        debug_assert_eq!(i, 0);
+        self.representative(class)
+    }
+}
+
+/// An expander over the classes of a [`Reduction`], made from an expander over the whole state
+/// space with `N` expansion nodes. A class is expanded by expanding each of its
+/// [expanded states](Reduction::expanded_state), so there are `N * reduction.expansions()`
+/// expansion nodes.
+#[derive(Clone)]
+pub struct ReducedExpander<E, R, const N: usize> {
+    pub expander: E,
+    pub reduction: R,
+}
+
+impl<E, R, const N: usize, const M: usize> BfsExpander<M> for ReducedExpander<E, R, N>
+where
+    E: BfsExpander<N>,
+    R: Reduction,
+{
+    fn expand(&mut self, node: u64, expanded_nodes: &mut [u64; M]) {
+        debug_assert_eq!(M, N * self.reduction.expansions());
+
+        let mut neighbours = [0; N];
+        for (i, nodes) in expanded_nodes.chunks_exact_mut(N).enumerate() {
+            let state = self.reduction.expanded_state(node, i);
+            self.expander.expand(state, &mut neighbours);
+            for (node, &neighbour) in nodes.iter_mut().zip(&neighbours) {
+                *node = self.reduction.class(neighbour);
+            }
+        }
+    }
+}
+
+/// A [`Reduction`] by a small group of symmetries and antisymmetries of a puzzle, which works out
+/// classes as they are needed instead of building tables, so it works for state spaces of any
+/// size.
+///
+/// The coordinate of a class is the smallest encoding of any state in it, which is also its
+/// representative. Class coordinates are in the same range as states, so the search still needs
+/// a bit for every state, but expands one or two states for each class instead of all of them.
+#[derive(Clone)]
+pub struct SmallestImage<P: 'static> {
+    puzzle: P,
+    symmetries: &'static [fn(&P) -> P],
+    antisymmetries: &'static [fn(&P) -> P],
+}
+
+impl<P: Puzzle> SmallestImage<P> {
+    /// `symmetries` are the symmetries that commute with moves, other than the identity, and
+    /// `antisymmetries` are the ones that reverse them. Together with the identity, they must be
+    /// a group.
+    pub fn new(symmetries: &'static [fn(&P) -> P], antisymmetries: &'static [fn(&P) -> P]) -> Self {
+        Self {
+            puzzle: P::solved(),
+            symmetries,
+            antisymmetries,
+        }
+    }
+
+    /// The images of `state` under every element of the group, except the identity.
+    fn images(&mut self, state: u64) -> impl Iterator<Item = u64> + '_ {
+        self.puzzle.decode(state);
+        let puzzle = &self.puzzle;
+        self.symmetries
+            .iter()
+            .chain(self.antisymmetries)
+            .map(move |symmetry| symmetry(puzzle).encode())
+    }
+}
+
+impl<P: Puzzle + Clone + Send + Sync> Reduction for SmallestImage<P> {
+    fn state_size(&self) -> u64 {
+        P::STATE_SIZE
+    }
+
+    fn class_count(&self) -> Option<u64> {
+        None
+    }
+
+    fn class(&mut self, state: u64) -> u64 {
+        self.images(state).fold(state, u64::min)
+    }
+
+    fn representative(&mut self, class: u64) -> u64 {
+        class
+    }
+
+    fn size(&mut self, class: u64) -> u64 {
+        // The size of the group divided by the number of its elements that fix `class`
+        let order = 1 + self.symmetries.len() + self.antisymmetries.len();
+        let fixed = 1 + self.images(class).filter(|&image| image == class).count();
+        (order / fixed) as u64
+    }
+
+    fn expansions(&self) -> usize {
+        if self.antisymmetries.is_empty() {
+            1
+        } else {
+            2
+        }
+    }
+
+    fn expanded_state(&mut self, class: u64, i: usize) -> u64 {
+        // The images of the representative under the other antisymmetries are images of this one
+        // under symmetries, so their neighbours are in the same classes
+        match i {
+            0 => class,
+            _ => {
+                self.puzzle.decode(class);
+                self.antisymmetries[0](&self.puzzle).encode()
+            }
+        }
+    }
+}
+
+/// The classes of a state space under a group of symmetries, with tables to find the class of a
+/// state and the states in a class. Classes are numbered in order of their representatives, which
+/// are the smallest state in each class.
+///
+/// The tables have an entry for every state, so this is only for state spaces that fit in memory.
+pub struct SymmetryClasses {
+    symmetry_count: usize,
+    /// The class of each state.
+    classes: Vec<u32>,
+    /// For each state, the index of a symmetry that takes its representative to it.
+    symmetries: Vec<u8>,
+    representatives: Vec<u32>,
+    /// The number of states in each class.
+    sizes: Vec<u8>,
+}
+
+impl SymmetryClasses {
+    /// Finds the classes of a state space of `state_size` states under `symmetry_count`
+    /// symmetries. `conjugates(state, images)` must fill `images` with the image of `state` under
+    /// each symmetry, starting with the identity.
+    pub fn build(
+        state_size: u64,
+        symmetry_count: usize,
+        mut conjugates: impl FnMut(u64, &mut [u64]),
+    ) -> Self {
+        assert!(state_size <= u32::MAX as u64);
+        assert!(symmetry_count <= u8::MAX as usize);
+
+        let mut classes = vec![u32::MAX; state_size as usize];
+        let mut symmetries = vec![0; state_size as usize];
+        let mut representatives = Vec::new();
+        let mut sizes = Vec::new();
+        let mut images = vec![0; symmetry_count];
+
+        for state in 0..state_size {
+            if classes[state as usize] != u32::MAX {
+                continue;
+            }
+
+            // States are visited in order, so this is the smallest state in its class
+            let class = representatives.len() as u32;
+            conjugates(state, &mut images);
+            debug_assert_eq!(images[0], state);
+
+            let mut size = 0;
+            for (symmetry, &image) in images.iter().enumerate() {
+                if classes[image as usize] == u32::MAX {
+                    classes[image as usize] = class;
+                    symmetries[image as usize] = symmetry as u8;
+                    size += 1;
+                }
+            }
+
+            representatives.push(state as u32);
+            sizes.push(size);
+        }
+
+        tracing::info!(
+            "found {} classes of {state_size} states under {symmetry_count} symmetries",
+            representatives.len()
+        );
+
+        Self {
+            symmetry_count,
+            classes,
+            symmetries,
+            representatives,
+            sizes,
+        }
+    }
+
+    /// The number of classes.
+    pub fn len(&self) -> u64 {
+        self.representatives.len() as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.representatives.is_empty()
+    }
+
+    pub fn symmetry_count(&self) -> usize {
+        self.symmetry_count
+    }
+
+    pub fn class(&self, state: u64) -> u64 {
+        self.classes[state as usize] as u64
+    }
+
+    /// The index of a symmetry that takes the representative of the class of `state` to `state`.
+    pub fn symmetry(&self, state: u64) -> usize {
+        self.symmetries[state as usize] as usize
+    }
+
+    /// The class and symmetry of `state` combined into one coordinate,
+    /// `class * symmetry_count + symmetry`.
+    pub fn sym_coord(&self, state: u64) -> u64 {
+        self.class(state) * self.symmetry_count as u64 + self.symmetry(state) as u64
+    }
+
+    pub fn representative(&self, class: u64) -> u64 {
+        self.representatives[class as usize] as u64
+    }
+
+    /// The number of states in `class`.
+    pub fn size(&self, class: u64) -> u64 {
+        self.sizes[class as usize] as u64
+    }
+}
+
+impl Reduction for &SymmetryClasses {
+    fn state_size(&self) -> u64 {
+        self.len()
+    }
+
+    fn class_count(&self) -> Option<u64> {
+        Some(self.len())
+    }
+
+    fn class(&mut self, state: u64) -> u64 {
+        SymmetryClasses::class(self, state)
+    }
+
+    fn representative(&mut self, class: u64) -> u64 {
+        SymmetryClasses::representative(self, class)
+    }
+
+    fn size(&mut self, class: u64) -> u64 {
+        SymmetryClasses::size(self, class)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The identity and negation modulo 6.
+    fn conjugates(state: u64, images: &mut [u64]) {
+        images.copy_from_slice(&[state, (6 - state) % 6]);
+    }
+
+    #[test]
+    fn test_build() {
+        let classes = SymmetryClasses::build(6, 2, conjugates);
+        assert_eq!(classes.len(), 4);
+        assert_eq!(
+            (0..4)
+                .map(|c| classes.representative(c))
+                .collect::<Vec<_>>(),
+            [0, 1, 2, 3]
+        );
+        assert_eq!(
+            (0..4).map(|c| classes.size(c)).collect::<Vec<_>>(),
+            [1, 2, 2, 1]
+        );
+
+        for state in 0..6 {
+            let class = classes.class(state);
+            let mut images = [0; 2];
+            conjugates(classes.representative(class), &mut images);
+            assert_eq!(images[classes.symmetry(state)], state);
+            assert_eq!(
+                classes.sym_coord(state),
+                class * 2 + classes.symmetry(state) as u64
+            );
+        }
+        assert_eq!(classes.class(5), 1);
+        assert_eq!(classes.symmetry(5), 1);
+    }
+
+    /// Adding or subtracting 1 modulo 6.
+    #[derive(Clone)]
+    struct Expander;
+
+    impl BfsExpander<2> for Expander {
+        fn expand(&mut self, node: u64, expanded_nodes: &mut [u64; 2]) {
+            *expanded_nodes = [(node + 1) % 6, (node + 5) % 6];
+        }
+    }
+
+    #[test]
+    fn test_reduced_expander() {
+        let classes = SymmetryClasses::build(6, 2, conjugates);
+        let mut expander = ReducedExpander {
+            expander: Expander,
+            reduction: &classes,
+        };
+
+        let mut expanded = [0; 2];
+        for (class, neighbours) in [(0, [1, 1]), (1, [2, 0]), (2, [3, 1]), (3, [2, 2])] {
+            expander.expand(class, &mut expanded);
+            assert_eq!(expanded, neighbours);
+        }
+    }
+
+    /// Permutations of 4 pieces, generated by swapping the first two and cycling all four.
+    #[derive(Debug, Clone, PartialEq)]
+    struct Perm([u8; 4]);
+
+    impl Perm {
+        fn a(&mut self) {
+            self.0.swap(0, 1);
+        }
+
+        fn b(&mut self) {
+            self.0.rotate_left(1);
+        }
+
+        fn inverse(&self) -> Self {
+            let mut inverse = [0; 4];
+            for (i, &piece) in self.0.iter().enumerate() {
+                inverse[piece as usize] = i as u8;
+            }
+            Self(inverse)
+        }
+    }
+
+    impl Puzzle for Perm {
+        const ID: &'static str = "perm";
+        const STATE_SIZE: u64 = 24;
+        const MOVES: &'static [&'static str] = &["A", "B"];
+        const ORDERS: &'static [usize] = &[2, 4];
+
+        fn solved() -> Self {
+            Self([0, 1, 2, 3])
+        }
+
+        fn encode(&self) -> u64 {
+            // Lehmer code
+            (0..4).fold(0, |coord, i| {
+                let smaller = self.0[i + 1..].iter().filter(|&&p| p < self.0[i]).count();
+                coord * (4 - i as u64) + smaller as u64
+            })
+        }
+
+        fn decode(&mut self, mut coord: u64) {
+            let mut pieces = vec![0, 1, 2, 3];
+            for i in 0..4 {
+                let radix = (1..4 - i as u64).product::<u64>();
+                self.0[i] = pieces.remove((coord / radix) as usize);
+                coord %= radix;
+            }
+        }
+
+        fn do_move(&mut self, mv: usize) {
+            match mv {
+                0 => self.a(),
+                _ => self.b(),
+            }
+        }
+    }
+
+    crate::expander! {
+        struct PermExpander {
+            perm: Perm,
+        }
+        const PERM_EXPANSION_NODES;
+        metric = Htm;
+        generators = [a: 2, b: 4];
+    }
+
+    #[test]
+    fn test_smallest_image() {
+        let mut reduction = SmallestImage::<Perm>::new(&[], &[Perm::inverse]);
+        for state in 0..24 {
+            let perm = Perm::from_coord(state);
+            assert_eq!(Perm::from_coord(perm.encode()), perm);
+
+            let inverse = perm.inverse().encode();
+            let class = reduction.class(state);
+            assert_eq!(class, state.min(inverse));
+            assert_eq!(reduction.class(inverse), class);
+            assert_eq!(reduction.size(class), if inverse == state { 1 } else { 2 });
+        }
+
+        // The full search and the search over classes find the same number of states at each
+        // depth
+        let solved = Perm::solved().encode();
+        let full = crate::expander::layers(
+            &mut PermExpander {
+                perm: Perm::solved(),
+            },
+            &[solved],
+            usize::MAX,
+        );
+        let reduced = crate::expander::layers::<_, { 2 * PERM_EXPANSION_NODES }>(
+            &mut ReducedExpander::<_, _, PERM_EXPANSION_NODES> {
+                expander: PermExpander {
+                    perm: Perm::solved(),
+                },
+                reduction: reduction.clone(),
+            },
+            &[reduction.class(solved)],
+            usize::MAX,
+        );
+        assert_eq!(full.iter().map(Vec::len).sum::<usize>(), 24);
+        assert_eq!(full.len(), reduced.len());
+        for (full, reduced) in full.iter().zip(&reduced) {
+            let unfolded = reduced.iter().map(|&c| reduction.size(c)).sum::<u64>();
+            assert_eq!(unfolded, full.len() as u64);
+        }
+    }
+}
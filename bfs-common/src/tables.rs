@@ -0,0 +1,731 @@
+//! Transposition tables that are built once, saved to a file, and memory-mapped on later runs.
+//!
+//! A table file starts with a header recording the format version, the puzzle, the name and
+//! length of each table, and a checksum of the table data. If any of it doesn't match what the
+//! puzzle expects, or the file can't be read, the tables are rebuilt and the file is replaced.
+//!
+//! The checksum is only checked against the data the first time a file is loaded, since that
+//! means reading all of it. A marker file next to it, e.g. `3x3-ep.tables.checked`, records the
+//! length and modification time of the file that was checked, and while they still match, later
+//! loads only check the header and the length of the file.
+
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, BufWriter, Write as _},
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::UNIX_EPOCH,
+};
+
+use memmap2::Mmap;
+use rayon::iter::{IndexedParallelIterator as _, IntoParallelIterator as _, ParallelIterator as _};
+use serde::Deserialize;
+use thiserror::Error;
+
+const MAGIC: [u8; 8] = *b"BFSTABLE";
+
+/// Version of the file layout. Bump this whenever the layout changes.
+const VERSION: u32 = 1;
+
+/// Written in native byte order, so that files from a machine with a different byte order are
+/// rejected rather than misread.
+const BYTE_ORDER_MARK: u32 = 0x0102_0304;
+
+/// Table data starts at a multiple of this many bytes.
+const DATA_ALIGN: usize = 8;
+
+/// Number of consecutive rows [`par_build`] gives to a thread at a time.
+const BLOCK_SIZE: usize = 1 << 14;
+
+#[derive(Debug, Error)]
+pub enum TableFileError {
+    #[error("failed to access {path}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+
+    #[error("not a table file")]
+    Magic,
+
+    #[error("file has version {found}, expected {VERSION}")]
+    Version { found: u32 },
+
+    #[error("file was written on a machine with a different byte order")]
+    ByteOrder,
+
+    #[error("file is truncated")]
+    Truncated,
+
+    #[error("header {what} is {found:?}, expected {expected:?}")]
+    Mismatch {
+        what: &'static str,
+        found: String,
+        expected: String,
+    },
+
+    #[error("checksum is {found:#010x}, expected {expected:#010x}")]
+    Checksum { found: u32, expected: u32 },
+}
+
+/// Which transposition tables to keep.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TableMode {
+    /// Every table, including ones for moves that could be made from other moves.
+    #[default]
+    Full,
+
+    /// Only the tables that can't be derived from others. Derived moves are made by chaining
+    /// generator lookups, or on the piece-level puzzle, which is slower but frees the memory.
+    Compact,
+}
+
+/// A set of transposition tables belonging to one puzzle.
+pub trait TableSet: Sized {
+    /// Identifies the tables in the file name and header, usually the puzzle ID.
+    const ID: &'static str;
+
+    /// Name and length of each table, in the order they are built and stored. The name is usually
+    /// the move that the table applies.
+    const LAYOUT: &'static [(&'static str, usize)];
+
+    /// Names of the tables that are left out in [`TableMode::Compact`].
+    const DERIVED: &'static [&'static str] = &[];
+
+    /// Builds the tables from scratch, in the order given by [`layout`].
+    fn build(mode: TableMode) -> Vec<Vec<u32>>;
+
+    /// Puts together the tables, in the order given by [`TableSet::LAYOUT`], with `None` in place
+    /// of tables that were left out.
+    fn from_tables(tables: Vec<Option<Table>>) -> Self;
+
+    /// Loads the tables from `directory` if they are there and up to date, or builds them and
+    /// saves them there otherwise. With no directory, the tables are always built.
+    fn load(directory: Option<&Path>, mode: TableMode) -> Self {
+        Self::load_with_checksum(directory, mode).0
+    }
+
+    /// Like [`TableSet::load`], but also returns the checksum of the table data, which is the same
+    /// whether the tables were loaded or built.
+    fn load_with_checksum(directory: Option<&Path>, mode: TableMode) -> (Self, u32) {
+        let Some(directory) = directory else {
+            let tables = build::<Self>(mode);
+            let checksum = checksum(&tables);
+            return (
+                assemble::<Self>(mode, tables.into_iter().map(Table::from)),
+                checksum,
+            );
+        };
+
+        let path = directory.join(match mode {
+            TableMode::Full => format!("{}.tables", Self::ID),
+            TableMode::Compact => format!("{}.compact.tables", Self::ID),
+        });
+        match read::<Self>(&path, mode) {
+            Ok((tables, checksum)) => {
+                tracing::info!("loaded transposition tables from {}", path.display());
+                return (assemble::<Self>(mode, tables), checksum);
+            }
+            Err(TableFileError::Io { source, .. }) if source.kind() == io::ErrorKind::NotFound => {
+                tracing::info!("no transposition tables at {}", path.display());
+            }
+            Err(err) => {
+                tracing::warn!(
+                    "rebuilding transposition tables in {}: {err}",
+                    path.display()
+                );
+            }
+        }
+
+        let tables = build::<Self>(mode);
+        let checksum = checksum(&tables);
+        match write::<Self>(&path, mode, &tables, checksum) {
+            Ok(()) => tracing::info!("saved transposition tables to {}", path.display()),
+            Err(err) => tracing::warn!("failed to save transposition tables: {err}"),
+        }
+
+        (
+            assemble::<Self>(mode, tables.into_iter().map(Table::from)),
+            checksum,
+        )
+    }
+}
+
+/// Name and length of each table kept in `mode`, in the order they are built and stored.
+pub fn layout<T: TableSet>(mode: TableMode) -> Vec<(&'static str, usize)> {
+    T::LAYOUT
+        .iter()
+        .filter(|(name, _)| mode == TableMode::Full || !T::DERIVED.contains(name))
+        .copied()
+        .collect()
+}
+
+/// Drops the tables that aren't kept in `mode` from a full set of tables, for puzzles that build
+/// every table anyway.
+pub fn retain<T: TableSet>(mode: TableMode, tables: Vec<Vec<u32>>) -> Vec<Vec<u32>> {
+    T::LAYOUT
+        .iter()
+        .zip(tables)
+        .filter(|((name, _), _)| mode == TableMode::Full || !T::DERIVED.contains(name))
+        .map(|(_, table)| table)
+        .collect()
+}
+
+fn assemble<T: TableSet>(mode: TableMode, tables: impl IntoIterator<Item = Table>) -> T {
+    let mut tables = tables.into_iter();
+    let tables = T::LAYOUT
+        .iter()
+        .map(|(name, _)| {
+            if mode == TableMode::Compact && T::DERIVED.contains(name) {
+                None
+            } else {
+                tables.next()
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let bytes = tables
+        .iter()
+        .flatten()
+        .map(|t| t.len() as u64 * 4)
+        .sum::<u64>();
+    tracing::info!(
+        "using {} of {} transposition tables ({bytes} bytes)",
+        tables.iter().flatten().count(),
+        T::LAYOUT.len(),
+    );
+
+    T::from_tables(tables)
+}
+
+/// A table of `u32`s, either in memory or mapped from a file.
+pub struct Table {
+    ptr: *const u32,
+    len: usize,
+    backing: Backing,
+}
+
+/// Owns the memory that [`Table::ptr`] points into. It is never read directly.
+#[allow(dead_code)]
+enum Backing {
+    Owned(Vec<u32>),
+    Mapped(Arc<Mmap>),
+}
+
+// SAFETY: `ptr` points into the backing, which is immutable and owned by the table.
+unsafe impl Send for Table {}
+unsafe impl Sync for Table {}
+
+impl Table {
+    pub fn is_mapped(&self) -> bool {
+        matches!(self.backing, Backing::Mapped(_))
+    }
+}
+
+impl From<Vec<u32>> for Table {
+    fn from(vec: Vec<u32>) -> Self {
+        Self {
+            ptr: vec.as_ptr(),
+            len: vec.len(),
+            backing: Backing::Owned(vec),
+        }
+    }
+}
+
+impl Deref for Table {
+    type Target = [u32];
+
+    fn deref(&self) -> &[u32] {
+        // SAFETY: `ptr` and `len` describe a slice in the backing, which lives as long as `self`.
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl fmt::Debug for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Table")
+            .field("len", &self.len)
+            .field("mapped", &self.is_mapped())
+            .finish()
+    }
+}
+
+impl PartialEq for Table {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for Table {}
+
+/// Builds `K` tables of length `len` in parallel. `row(state, i)` returns entry `i` of every
+/// table, where `state` is scratch space (usually a puzzle) created by `init` once per thread and
+/// reused between rows.
+pub fn par_build<S, const K: usize>(
+    len: usize,
+    init: impl Fn() -> S + Sync + Send,
+    row: impl Fn(&mut S, usize) -> [u32; K] + Sync + Send,
+) -> [Vec<u32>; K] {
+    let mut tables: [Vec<u32>; K] = std::array::from_fn(|_| vec![0; len]);
+
+    let mut chunks = tables
+        .iter_mut()
+        .map(|table| table.chunks_mut(BLOCK_SIZE))
+        .collect::<Vec<_>>();
+    let blocks = (0..len.div_ceil(BLOCK_SIZE))
+        .map(|_| std::array::from_fn::<_, K, _>(|k| chunks[k].next().unwrap()))
+        .collect::<Vec<_>>();
+
+    blocks
+        .into_par_iter()
+        .enumerate()
+        .for_each_init(init, |state, (block_idx, mut block)| {
+            let start = block_idx * BLOCK_SIZE;
+            for j in 0..BLOCK_SIZE.min(len - start) {
+                let values = row(state, start + j);
+                for (table, value) in block.iter_mut().zip(values) {
+                    table[j] = value;
+                }
+            }
+        });
+
+    tables
+}
+
+fn build<T: TableSet>(mode: TableMode) -> Vec<Vec<u32>> {
+    tracing::info!("building transposition tables for {}", T::ID);
+    let tables = T::build(mode);
+    assert_eq!(tables.len(), layout::<T>(mode).len());
+    tables
+}
+
+fn checksum(tables: &[Vec<u32>]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    for table in tables {
+        hasher.update(as_bytes(table));
+    }
+    hasher.finalize()
+}
+
+fn as_bytes(table: &[u32]) -> &[u8] {
+    // SAFETY: any `u32` is 4 initialized bytes, and `u8` has no alignment requirement.
+    unsafe { std::slice::from_raw_parts(table.as_ptr().cast(), std::mem::size_of_val(table)) }
+}
+
+fn header<T: TableSet>(mode: TableMode, checksum: u32) -> Vec<u8> {
+    let layout = layout::<T>(mode);
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&MAGIC);
+    header.extend_from_slice(&VERSION.to_le_bytes());
+    header.extend_from_slice(&BYTE_ORDER_MARK.to_ne_bytes());
+    write_str(&mut header, T::ID);
+    header.extend_from_slice(&(layout.len() as u32).to_le_bytes());
+    for (name, len) in layout {
+        write_str(&mut header, name);
+        header.extend_from_slice(&(len as u64).to_le_bytes());
+    }
+    header.extend_from_slice(&checksum.to_le_bytes());
+    header.resize(header.len().next_multiple_of(DATA_ALIGN), 0);
+    header
+}
+
+fn write_str(header: &mut Vec<u8>, s: &str) {
+    header.extend_from_slice(&(s.len() as u32).to_le_bytes());
+    header.extend_from_slice(s.as_bytes());
+}
+
+/// Writes the file next to `path` first, so that an interrupted write never leaves a file that
+/// looks complete.
+fn write<T: TableSet>(
+    path: &Path,
+    mode: TableMode,
+    tables: &[Vec<u32>],
+    checksum: u32,
+) -> Result<(), TableFileError> {
+    let io_err = |path: &Path| {
+        let path = path.to_owned();
+        move |source| TableFileError::Io { path, source }
+    };
+
+    let tmp_path = path.with_extension("tables.tmp");
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(io_err(parent))?;
+    }
+
+    let mut file = BufWriter::new(File::create(&tmp_path).map_err(io_err(&tmp_path))?);
+    file.write_all(&header::<T>(mode, checksum))
+        .map_err(io_err(&tmp_path))?;
+    for table in tables {
+        file.write_all(as_bytes(table)).map_err(io_err(&tmp_path))?;
+    }
+    file.into_inner()
+        .map_err(|err| err.into_error())
+        .and_then(|file| file.sync_all())
+        .map_err(io_err(&tmp_path))?;
+
+    fs::rename(&tmp_path, path).map_err(io_err(path))?;
+
+    // The checksum was computed from the tables rather than the file, so there is no need to
+    // read it back
+    let file = File::open(path).map_err(io_err(path))?;
+    mark_checked(path, &file, checksum);
+    Ok(())
+}
+
+/// The path of the marker recording that the data of the table file at `path` was checked.
+fn marker_path(path: &Path) -> PathBuf {
+    path.with_extension("tables.checked")
+}
+
+/// The contents of the marker of `file`, whose data has checksum `checksum`.
+fn marker(file: &File, checksum: u32) -> io::Result<String> {
+    let metadata = file.metadata()?;
+    let modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    Ok(format!(
+        "{checksum:08x} {} {}\n",
+        metadata.len(),
+        modified.as_nanos()
+    ))
+}
+
+/// Writes the marker of `file` at `path`. If it can't be written, the data is checked again on
+/// the next load.
+fn mark_checked(path: &Path, file: &File, checksum: u32) {
+    let marker_path = marker_path(path);
+    if let Err(err) = marker(file, checksum).and_then(|marker| fs::write(&marker_path, marker)) {
+        tracing::warn!("failed to write {}: {err}", marker_path.display());
+    }
+}
+
+/// Reads the tables and their checksum, which has been checked against the data, either now or
+/// when the marker of the file was written.
+fn read<T: TableSet>(path: &Path, mode: TableMode) -> Result<(Vec<Table>, u32), TableFileError> {
+    let layout = layout::<T>(mode);
+    let io_err = |source| TableFileError::Io {
+        path: path.to_owned(),
+        source,
+    };
+
+    let file = File::open(path).map_err(io_err)?;
+    // SAFETY: table files are only ever replaced by renaming a new file over them, never modified
+    // in place.
+    let mmap = Arc::new(unsafe { Mmap::map(&file) }.map_err(io_err)?);
+
+    let mut reader = HeaderReader {
+        bytes: &mmap,
+        pos: 0,
+    };
+    if reader.bytes(MAGIC.len())? != MAGIC {
+        return Err(TableFileError::Magic);
+    }
+    let version = reader.u32()?;
+    if version != VERSION {
+        return Err(TableFileError::Version { found: version });
+    }
+    if reader.bytes(4)? != BYTE_ORDER_MARK.to_ne_bytes() {
+        return Err(TableFileError::ByteOrder);
+    }
+
+    let mismatch = |what, found: String, expected: String| TableFileError::Mismatch {
+        what,
+        found,
+        expected,
+    };
+
+    let id = reader.str()?;
+    if id != T::ID {
+        return Err(mismatch("id", id.to_owned(), T::ID.to_owned()));
+    }
+    let count = reader.u32()? as usize;
+    if count != layout.len() {
+        return Err(mismatch(
+            "table count",
+            count.to_string(),
+            layout.len().to_string(),
+        ));
+    }
+    for &(name, len) in &layout {
+        let found_name = reader.str()?;
+        let found_len = reader.u64()?;
+        if (found_name, found_len) != (name, len as u64) {
+            return Err(mismatch(
+                "table",
+                format!("{found_name} ({found_len})"),
+                format!("{name} ({len})"),
+            ));
+        }
+    }
+    let expected_checksum = reader.u32()?;
+
+    let data_start = reader.pos.next_multiple_of(DATA_ALIGN);
+    let data_len = layout.iter().map(|&(_, len)| len * 4).sum::<usize>();
+    let data = mmap
+        .get(data_start..data_start + data_len)
+        .ok_or(TableFileError::Truncated)?;
+    let marker = marker(&file, expected_checksum).map_err(io_err)?;
+    if fs::read_to_string(marker_path(path)).ok() != Some(marker) {
+        tracing::info!("checking transposition tables in {}", path.display());
+        let found_checksum = crc32fast::hash(data);
+        if found_checksum != expected_checksum {
+            return Err(TableFileError::Checksum {
+                found: found_checksum,
+                expected: expected_checksum,
+            });
+        }
+        mark_checked(path, &file, expected_checksum);
+    }
+
+    let mut offset = data_start;
+    let tables = layout
+        .iter()
+        .map(|&(_, len)| {
+            // The mapping is page aligned and `offset` is a multiple of 4, so this is aligned
+            let ptr = mmap[offset..].as_ptr().cast::<u32>();
+            offset += len * 4;
+            Table {
+                ptr,
+                len,
+                backing: Backing::Mapped(Arc::clone(&mmap)),
+            }
+        })
+        .collect();
+
+    Ok((tables, expected_checksum))
+}
+
+struct HeaderReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> HeaderReader<'a> {
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], TableFileError> {
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos + n)
+            .ok_or(TableFileError::Truncated)?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, TableFileError> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, TableFileError> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    fn str(&mut self) -> Result<&'a str, TableFileError> {
+        let len = self.u32()? as usize;
+        std::str::from_utf8(self.bytes(len)?).map_err(|_| TableFileError::Truncated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    use TableMode::{Compact, Full};
+
+    /// Adding 1 and 2 modulo 5, where adding 2 can be done by adding 1 twice.
+    struct Tables {
+        plus1: Table,
+        plus2: Option<Table>,
+    }
+
+    impl TableSet for Tables {
+        const ID: &'static str = "test";
+        const LAYOUT: &'static [(&'static str, usize)] = &[("plus1", 5), ("plus2", 5)];
+        const DERIVED: &'static [&'static str] = &["plus2"];
+
+        fn build(mode: TableMode) -> Vec<Vec<u32>> {
+            retain::<Self>(
+                mode,
+                vec![
+                    (0..5).map(|i| (i + 1) % 5).collect(),
+                    (0..5).map(|i| (i + 2) % 5).collect(),
+                ],
+            )
+        }
+
+        fn from_tables(tables: Vec<Option<Table>>) -> Self {
+            let [plus1, plus2] = tables.try_into().unwrap();
+            Self {
+                plus1: plus1.unwrap(),
+                plus2,
+            }
+        }
+    }
+
+    /// Same layout, but a different ID.
+    struct OtherTables;
+
+    impl TableSet for OtherTables {
+        const ID: &'static str = "other";
+        const LAYOUT: &'static [(&'static str, usize)] = Tables::LAYOUT;
+
+        fn build(mode: TableMode) -> Vec<Vec<u32>> {
+            Tables::build(mode)
+        }
+
+        fn from_tables(_: Vec<Option<Table>>) -> Self {
+            Self
+        }
+    }
+
+    #[test]
+    fn test_par_build() {
+        let len = 3 * BLOCK_SIZE + 5;
+        let [squares, calls] = par_build(
+            len,
+            || 0,
+            |calls, i| {
+                *calls += 1;
+                [(i * i) as u32, *calls]
+            },
+        );
+
+        assert!(squares
+            .iter()
+            .enumerate()
+            .all(|(i, &x)| x == (i * i) as u32));
+        assert!(calls.iter().all(|&c| c >= 1));
+    }
+
+    #[test]
+    fn test_save_and_map() {
+        let dir = temp_dir("tables-save-and-map");
+
+        let (built, built_checksum) = Tables::load_with_checksum(Some(&dir), Full);
+        assert!(!built.plus1.is_mapped());
+
+        let (loaded, loaded_checksum) = Tables::load_with_checksum(Some(&dir), Full);
+        assert!(loaded.plus1.is_mapped());
+        assert_eq!(*loaded.plus1, [1, 2, 3, 4, 0]);
+        assert_eq!(loaded.plus2, built.plus2);
+
+        assert_eq!(loaded_checksum, built_checksum);
+        assert_eq!(Tables::load_with_checksum(None, Full).1, built_checksum);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_compact() {
+        let dir = temp_dir("tables-compact");
+
+        let built = Tables::load(Some(&dir), Compact);
+        assert_eq!(*built.plus1, [1, 2, 3, 4, 0]);
+        assert_eq!(built.plus2, None);
+
+        let loaded = Tables::load(Some(&dir), Compact);
+        assert!(loaded.plus1.is_mapped());
+        assert_eq!(loaded.plus2, None);
+
+        // Full tables are kept in a separate file
+        assert!(!Tables::load(Some(&dir), Full).plus1.is_mapped());
+        assert!(Tables::load(Some(&dir), Full).plus2.unwrap().is_mapped());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Changes the file at `path` with `change`, keeping its modification time.
+    fn rewrite(path: &Path, change: impl FnOnce(&mut Vec<u8>)) {
+        let modified = fs::metadata(path).unwrap().modified().unwrap();
+        let mut bytes = fs::read(path).unwrap();
+        change(&mut bytes);
+        fs::write(path, &bytes).unwrap();
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+    }
+
+    /// Flips a bit of the table data in the file at `path`.
+    fn corrupt(path: &Path) {
+        rewrite(path, |bytes| *bytes.last_mut().unwrap() ^= 1);
+    }
+
+    #[test]
+    fn test_checked_once() {
+        let dir = temp_dir("tables-checked-once");
+        let path = dir.join("test.tables");
+
+        let tables = Tables::build(Full);
+        write::<Tables>(&path, Full, &tables, checksum(&tables)).unwrap();
+        assert!(marker_path(&path).exists());
+
+        // The data isn't read again while the marker matches the file
+        corrupt(&path);
+        assert!(read::<Tables>(&path, Full).is_ok());
+
+        fs::remove_file(marker_path(&path)).unwrap();
+        assert!(matches!(
+            read::<Tables>(&path, Full),
+            Err(TableFileError::Checksum { .. })
+        ));
+        assert!(!marker_path(&path).exists());
+
+        // Undoing the corruption, the file is checked once more and marked again
+        corrupt(&path);
+        assert!(read::<Tables>(&path, Full).is_ok());
+        assert!(marker_path(&path).exists());
+
+        // A file of a different length is checked again
+        corrupt(&path);
+        rewrite(&path, |bytes| bytes.push(0));
+        assert!(matches!(
+            read::<Tables>(&path, Full),
+            Err(TableFileError::Checksum { .. })
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_rebuild_on_mismatch() {
+        let dir = temp_dir("tables-rebuild-on-mismatch");
+        let path = dir.join("test.tables");
+
+        let tables = Tables::build(Full);
+        write::<Tables>(&path, Full, &tables, checksum(&tables)).unwrap();
+        assert!(read::<Tables>(&path, Full).is_ok());
+        assert!(matches!(
+            read::<Tables>(&path, Compact),
+            Err(TableFileError::Mismatch {
+                what: "table count",
+                ..
+            })
+        ));
+
+        // Renamed to a file belonging to another table set
+        fs::rename(&path, dir.join("other.tables")).unwrap();
+        assert!(matches!(
+            read::<OtherTables>(&dir.join("other.tables"), Full),
+            Err(TableFileError::Mismatch { what: "id", .. })
+        ));
+
+        // Corrupted table data, in a file that wasn't checked before
+        write::<Tables>(&path, Full, &tables, checksum(&tables)).unwrap();
+        corrupt(&path);
+        fs::remove_file(marker_path(&path)).unwrap();
+        assert!(matches!(
+            read::<Tables>(&path, Full),
+            Err(TableFileError::Checksum { .. })
+        ));
+
+        let rebuilt = Tables::load(Some(&dir), Full);
+        assert!(!rebuilt.plus1.is_mapped());
+        assert!(read::<Tables>(&path, Full).is_ok());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,11 @@
+//! Helpers shared by the tests of several modules.
+
+use std::{fs, path::PathBuf};
+
+/// An empty directory for a test to write its files to, which is only used by this process. Names
+/// start with the module of the test, e.g. `depths-walk`, so that tests don't share directories.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("bfs-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
@@ -0,0 +1,25 @@
+//! Helpers for the tests of the puzzle crates, behind the `test-support` feature.
+
+use rand::{rngs::StdRng, Rng as _, SeedableRng as _};
+
+use crate::puzzle::Puzzle;
+
+/// 100 random sequences of 30 moves of `P`, as indices into [`Puzzle::MOVES`]. They are the same
+/// on every call.
+pub fn scrambles<P: Puzzle>() -> impl Iterator<Item = Vec<usize>> {
+    let mut rng = StdRng::seed_from_u64(1);
+    (0..100).map(move |_| {
+        (0..30)
+            .map(|_| rng.random_range(0..P::MOVES.len()))
+            .collect()
+    })
+}
+
+/// The state that `moves` take the solved state to.
+pub fn scrambled<P: Puzzle>(moves: &[usize]) -> P {
+    let mut puzzle = P::solved();
+    for &mv in moves {
+        puzzle.do_move(mv);
+    }
+    puzzle
+}
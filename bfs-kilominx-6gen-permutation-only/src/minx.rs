@@ -1,3 +1,8 @@
+use bfs_common::{
+    notation::{MinxFace, MinxMove, Turn},
+    puzzle::Puzzle,
+};
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Kilominx {
     corners: [u8; 15],
@@ -68,22 +73,6 @@ impl Kilominx {
         self.corners[6] = a;
     }
 
-    pub fn do_move(&mut self, mv: &str) {
-        match mv {
-            "U" => self.u(),
-            "L" => self.l(),
-            "F" => self.f(),
-            "R" => self.r(),
-            "BR" => self.br(),
-            "BL" => self.bl(),
-            _ => panic!("Invalid move"),
-        }
-    }
-
-    pub fn do_alg(&mut self, alg: &str) {
-        alg.split_whitespace().for_each(|mv| self.do_move(mv));
-    }
-
     pub fn encode(&self) -> u64 {
         combinatorics::indexing::encode_even_permutation(self.corners)
     }
@@ -93,8 +82,69 @@ impl Kilominx {
     }
 }
 
+impl Default for Kilominx {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Puzzle for Kilominx {
+    const ID: &'static str = "kilominx-6gen-permutation-only";
+    const STATE_SIZE: u64 = 653837184000;
+    const MOVES: &'static [&'static str] = &["U", "L", "F", "R", "BR", "BL"];
+    const ORDERS: &'static [usize] = &[5; 6];
+
+    fn solved() -> Self {
+        Self::new()
+    }
+
+    fn encode(&self) -> u64 {
+        self.encode()
+    }
+
+    fn decode(&mut self, coord: u64) {
+        self.decode(coord);
+    }
+
+    fn pieces(&self) -> Vec<(&'static str, Vec<u8>)> {
+        vec![("corners", self.corners.to_vec())]
+    }
+
+    fn do_move(&mut self, mv: usize) {
+        match mv {
+            0 => self.u(),
+            1 => self.l(),
+            2 => self.f(),
+            3 => self.r(),
+            4 => self.br(),
+            5 => self.bl(),
+            _ => panic!("Invalid move"),
+        }
+    }
+}
+
+impl Turn<MinxMove> for Kilominx {
+    fn turn(&mut self, mv: MinxMove) -> bool {
+        let fifth: fn(&mut Self) = match mv.face {
+            MinxFace::U => Self::u,
+            MinxFace::L => Self::l,
+            MinxFace::F => Self::f,
+            MinxFace::R => Self::r,
+            MinxFace::BR => Self::br,
+            MinxFace::BL => Self::bl,
+            _ => return false,
+        };
+        for _ in 0..mv.power {
+            fifth(self);
+        }
+        true
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use bfs_common::notation::turn_alg;
+
     use super::*;
 
     #[test]
@@ -128,11 +178,10 @@ mod tests {
 
     #[test]
     fn test_t_perm() {
-        let scramble =
-            "R U R R R R U U U U R R R R F R R U U U U R R R R U U U U R U R R R R F F F F U";
+        let scramble = "R U R' U' R' F R2 U' R' U' R U R' F' U";
 
         let mut minx = Kilominx::new();
-        minx.do_alg(scramble);
+        turn_alg(&mut minx, scramble).unwrap();
 
         assert_eq!(
             minx.corners,
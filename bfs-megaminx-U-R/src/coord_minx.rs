@@ -1,5 +1,10 @@
 #![allow(dead_code)]
 
+use bfs_common::{
+    notation::{MinxFace, MinxMove, Turn},
+    tables::Table,
+};
+
 use crate::{
     minx::{Megaminx, EP_SIZE},
     transposition_tables::TranspositionTables,
@@ -35,14 +40,28 @@ impl<'a> CoordMinx<'a> {
         Megaminx::from(self).is_solved()
     }
 
+    /// Applies a derived move from its tables, returning `false` if they weren't built.
+    fn apply(&mut self, corners: &Option<Table>, edges: &Option<Table>) -> bool {
+        let (Some(corners), Some(edges)) = (corners, edges) else {
+            return false;
+        };
+        self.corners = corners[self.corners as usize];
+        self.edges = edges[self.edges as usize];
+        true
+    }
+
     pub fn u(&mut self) {
         self.corners = self.transposition_tables.u_corners[self.corners as usize];
         self.edges = self.transposition_tables.u_edges[self.edges as usize];
     }
 
     pub fn u2p(&mut self) {
-        self.corners = self.transposition_tables.u2p_corners[self.corners as usize];
-        self.edges = self.transposition_tables.u2p_edges[self.edges as usize];
+        let tables = self.transposition_tables;
+        if !self.apply(&tables.u2p_corners, &tables.u2p_edges) {
+            self.u();
+            self.u();
+            self.u();
+        }
     }
 
     pub fn r(&mut self) {
@@ -51,33 +70,22 @@ impl<'a> CoordMinx<'a> {
     }
 
     pub fn r2p(&mut self) {
-        self.corners = self.transposition_tables.r2p_corners[self.corners as usize];
-        self.edges = self.transposition_tables.r2p_edges[self.edges as usize];
+        let tables = self.transposition_tables;
+        if !self.apply(&tables.r2p_corners, &tables.r2p_edges) {
+            self.r();
+            self.r();
+            self.r();
+        }
     }
 
     pub fn ur(&mut self) {
-        self.corners = self.transposition_tables.ur_corners[self.corners as usize];
-        self.edges = self.transposition_tables.ur_edges[self.edges as usize];
-    }
-
-    pub fn do_move(&mut self, mv: &str) {
-        match mv {
-            "U" => self.u(),
-            "U2" => (0..2).for_each(|_| self.u()),
-            "U2'" => (0..3).for_each(|_| self.u()),
-            "U'" => (0..4).for_each(|_| self.u()),
-            "R" => self.r(),
-            "R2" => (0..2).for_each(|_| self.r()),
-            "R2'" => (0..3).for_each(|_| self.r()),
-            "R'" => (0..4).for_each(|_| self.r()),
-            _ => panic!("Invalid move"),
+        let tables = self.transposition_tables;
+        if !self.apply(&tables.ur_corners, &tables.ur_edges) {
+            self.u();
+            self.r();
         }
     }
 
-    pub fn do_alg(&mut self, alg: &str) {
-        alg.split_whitespace().for_each(|mv| self.do_move(mv));
-    }
-
     pub fn encode(&self) -> u64 {
         self.corners as u64 * EP_SIZE as u64 + self.edges as u64
     }
@@ -87,3 +95,17 @@ impl<'a> CoordMinx<'a> {
         self.edges = (coord % EP_SIZE as u64) as u32;
     }
 }
+
+impl Turn<MinxMove> for CoordMinx<'_> {
+    fn turn(&mut self, mv: MinxMove) -> bool {
+        let fifth: fn(&mut Self) = match mv.face {
+            MinxFace::U => Self::u,
+            MinxFace::R => Self::r,
+            _ => return false,
+        };
+        for _ in 0..mv.power {
+            fifth(self);
+        }
+        true
+    }
+}
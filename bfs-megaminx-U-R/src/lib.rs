@@ -2,18 +2,18 @@
 
 mod coord_minx;
 mod minx;
+mod symmetry;
 mod transposition_tables;
 
-use std::path::PathBuf;
-
-use disk_based_bfs::{
-    builder::BfsBuilder,
-    callback::BfsCallback,
-    expander::BfsExpander,
-    provider::{BfsSettingsProvider, ChunkFilesBehavior, UpdateFilesBehavior},
+use bfs_common::{
+    config::RunConfig,
+    manifest::Variant,
+    runner::{self, Bounds, Task, TaskError},
+    tables::TableSet as _,
 };
-use tracing_subscriber::{layer::SubscriberExt as _, util::SubscriberInitExt as _, EnvFilter};
+use disk_based_bfs::expander::BfsExpander;
 
+pub use crate::minx::Megaminx;
 use crate::{coord_minx::CoordMinx, transposition_tables::TranspositionTables};
 
 const EXPANSION_NODES_HTM: usize = 8;
@@ -71,102 +71,67 @@ impl BfsExpander<EXPANSION_NODES_QTM> for ExpanderQtm<'_> {
     }
 }
 
-#[derive(Clone)]
-struct Callback(usize);
-
-impl BfsCallback for Callback {
-    fn new_state(&mut self, depth: usize, state: u64) {
-        if depth >= self.0 {
-            tracing::info!("depth {depth} state {state}");
-        }
-    }
-
-    fn end_of_chunk(&self, _: usize, _: usize) {}
-}
-
-struct Provider(usize);
-
-impl BfsSettingsProvider for Provider {
-    fn chunk_root_idx(&self, chunk_idx: usize) -> usize {
-        [0, 1, 2, 3, 1, 2, 3][chunk_idx % 7]
-    }
-
-    fn update_files_behavior(&self, depth: usize) -> UpdateFilesBehavior {
-        if depth >= self.0 {
-            UpdateFilesBehavior::MergeAndKeep
-        } else {
-            UpdateFilesBehavior::DontMerge
-        }
-    }
-
-    fn chunk_files_behavior(&self, depth: usize) -> ChunkFilesBehavior {
-        if depth >= self.0 {
-            ChunkFilesBehavior::Keep
-        } else {
-            ChunkFilesBehavior::Delete
-        }
-    }
-}
-
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Metric {
     Htm,
     Qtm,
 }
 
-pub fn run(metric: Metric) {
-    tracing_subscriber::registry()
-        .with(
-            EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "disk_based_bfs=trace,bfs_megaminx_U_R=trace".into()),
-        )
-        .with(
-            tracing_subscriber::fmt::layer()
-                .compact()
-                .with_ansi(false)
-                .with_thread_names(true)
-                .with_line_number(true),
-        )
-        .init();
-
-    let transposition_tables = TranspositionTables::new();
+/// Runs `task`, for the classes of states under mirroring and inversion if `symmetry` is set.
+pub fn run(
+    metric: Metric,
+    symmetry: bool,
+    config: &RunConfig,
+    task: &Task,
+) -> Result<(), TaskError> {
+    let (transposition_tables, tables) = TranspositionTables::load_with_checksum(
+        config.table_directory.as_deref(),
+        config.table_mode,
+    );
+    let variant = Variant {
+        metric: Some(format!("{metric:?}")),
+        tables: Some(tables),
+        ..Variant::default()
+    };
 
     macro_rules! run {
-        ($expander:ident, $callback_bound:expr, $provider_bound:expr) => {
-            BfsBuilder::new()
-                .threads(48)
-                // 42 * 48 chunks
-                .chunk_size_bytes(496011600)
-                .update_memory(112 * (1 << 30))
-                .num_update_blocks(2 * 42 * 48 * 48)
-                .capacity_check_frequency(256)
-                .initial_states(&[CoordMinx::new(&transposition_tables).encode()])
-                .state_size(7999675084800)
-                .root_directories(&[
-                    PathBuf::from("/media/ben/drive1/bfs/megaminx-U-R/"),
-                    PathBuf::from("/media/ben/drive2/bfs/megaminx-U-R/"),
-                    PathBuf::from("/media/ben/drive3/bfs/megaminx-U-R/"),
-                    PathBuf::from("/media/ben/drive4/bfs/megaminx-U-R/"),
-                ])
-                .initial_memory_limit(1 << 34)
-                .available_disk_space_limit(256 * (1 << 30))
-                .update_array_threshold(496011600)
-                .use_locked_io(false)
-                .sync_filesystem(true)
-                .compute_checksums(true)
-                .use_compression(true)
-                .expander($expander {
-                    minx: CoordMinx::new(&transposition_tables),
-                })
-                .callback(Callback($callback_bound))
-                .settings_provider(Provider($provider_bound))
-                .run_no_defaults()
-                .unwrap()
-        };
+        ($expander:ident, $expansion_nodes:ident, $callback_bound:expr, $provider_bound:expr) => {{
+            let expander = $expander {
+                minx: CoordMinx::new(&transposition_tables),
+            };
+            let bounds = Bounds {
+                log: $callback_bound,
+                keep: $provider_bound,
+            };
+            if symmetry {
+                runner::run_reduced::<Megaminx, _, _, $expansion_nodes, { 2 * $expansion_nodes }>(
+                    config,
+                    task,
+                    variant,
+                    expander,
+                    bounds,
+                    symmetry::reduction(),
+                )
+            } else {
+                runner::run::<Megaminx, _, $expansion_nodes>(
+                    config, task, variant, expander, bounds,
+                )
+            }
+        }};
     }
 
     match metric {
-        Metric::Htm => run!(ExpanderHtm, CALLBACK_BOUND_HTM, PROVIDER_BOUND_HTM),
-        Metric::Qtm => run!(ExpanderQtm, CALLBACK_BOUND_QTM, PROVIDER_BOUND_QTM),
+        Metric::Htm => run!(
+            ExpanderHtm,
+            EXPANSION_NODES_HTM,
+            CALLBACK_BOUND_HTM,
+            PROVIDER_BOUND_HTM
+        ),
+        Metric::Qtm => run!(
+            ExpanderQtm,
+            EXPANSION_NODES_QTM,
+            CALLBACK_BOUND_QTM,
+            PROVIDER_BOUND_QTM
+        ),
     }
 }
@@ -1,5 +1,11 @@
 #![allow(dead_code)]
 
+use bfs_common::{
+    goal::GoalSet,
+    notation::{MinxFace, MinxMove, Turn},
+    puzzle::Puzzle,
+};
+
 use crate::coord_minx::CoordMinx;
 
 pub const CP_SIZE: usize = 20160;
@@ -7,7 +13,17 @@ pub const CO_SIZE: usize = 2187;
 pub const EP_SIZE: usize = 181440;
 pub const CORNERS_SIZE: usize = CP_SIZE * CO_SIZE;
 
-#[derive(Debug, PartialEq)]
+/// Where the reflection that swaps the U and R faces takes each corner position. It turns U into R'
+/// and R into U'.
+const MIRROR_CORNERS: [u8; 8] = [5, 6, 7, 3, 4, 0, 1, 2];
+
+/// Where the reflection takes each edge position.
+const MIRROR_EDGES: [u8; 9] = [5, 6, 7, 8, 4, 0, 1, 2, 3];
+
+/// The twist that the reflection adds to the reference facelet of each corner position.
+const MIRROR_TWISTS: [u8; 8] = [0, 1, 2, 0, 2, 0, 1, 2];
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Megaminx {
     cp: [u8; 8],
     co: [u8; 8],
@@ -69,22 +85,32 @@ impl Megaminx {
         self.ep[8] = a;
     }
 
-    pub fn do_move(&mut self, mv: &str) {
-        match mv {
-            "U" => self.u(),
-            "U2" => (0..2).for_each(|_| self.u()),
-            "U2'" => (0..3).for_each(|_| self.u()),
-            "U'" => (0..4).for_each(|_| self.u()),
-            "R" => self.r(),
-            "R2" => (0..2).for_each(|_| self.r()),
-            "R2'" => (0..3).for_each(|_| self.r()),
-            "R'" => (0..4).for_each(|_| self.r()),
-            _ => panic!("Invalid move"),
+    /// The megaminx seen in a mirror that swaps U and R, so that the mirror image of a scramble
+    /// is the scramble with every U turned into R' and every R into U'.
+    pub fn mirror(&self) -> Self {
+        let mut minx = Self::new();
+        for i in 0..8 {
+            let (to, piece) = (MIRROR_CORNERS[i] as usize, self.cp[i] as usize);
+            minx.cp[to] = MIRROR_CORNERS[piece];
+            minx.co[to] = (6 + MIRROR_TWISTS[i] - MIRROR_TWISTS[piece] - self.co[i]) % 3;
+        }
+        for i in 0..9 {
+            minx.ep[MIRROR_EDGES[i] as usize] = MIRROR_EDGES[self.ep[i] as usize];
         }
+        minx
     }
 
-    pub fn do_alg(&mut self, alg: &str) {
-        alg.split_whitespace().for_each(|mv| self.do_move(mv));
+    /// The megaminx that the inverse of a scramble of this one leads to.
+    pub fn inverse(&self) -> Self {
+        let mut minx = Self::new();
+        for i in 0..8 {
+            minx.cp[self.cp[i] as usize] = i as u8;
+            minx.co[self.cp[i] as usize] = (3 - self.co[i]) % 3;
+        }
+        for i in 0..9 {
+            minx.ep[self.ep[i] as usize] = i as u8;
+        }
+        minx
     }
 
     pub fn cp_coord(&self) -> u32 {
@@ -145,8 +171,81 @@ impl From<&CoordMinx<'_>> for Megaminx {
     }
 }
 
+impl Default for Megaminx {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Puzzle for Megaminx {
+    const ID: &'static str = "megaminx-U-R";
+    const STATE_SIZE: u64 = CORNERS_SIZE as u64 * EP_SIZE as u64;
+    const MOVES: &'static [&'static str] = &["U", "R"];
+    const ORDERS: &'static [usize] = &[5, 5];
+    const GOALS: &'static [&'static str] = &["auf"];
+
+    fn solved() -> Self {
+        Self::new()
+    }
+
+    fn encode(&self) -> u64 {
+        self.encode()
+    }
+
+    fn decode(&mut self, coord: u64) {
+        self.decode(coord);
+    }
+
+    fn sub_coords(&self) -> Vec<(&'static str, u64)> {
+        vec![
+            ("corners", self.corners_coord() as u64),
+            ("ep", self.ep_coord() as u64),
+        ]
+    }
+
+    fn pieces(&self) -> Vec<(&'static str, Vec<u8>)> {
+        vec![
+            ("cp", self.cp.to_vec()),
+            ("co", self.co.to_vec()),
+            ("ep", self.ep.to_vec()),
+        ]
+    }
+
+    fn do_move(&mut self, mv: usize) {
+        match mv {
+            0 => self.u(),
+            1 => self.r(),
+            _ => panic!("Invalid move"),
+        }
+    }
+
+    fn goal(name: &str) -> Option<GoalSet> {
+        match name {
+            // Solved up to a turn of the U face
+            "auf" => Some(GoalSet::from_scrambles::<Self>(&["", "U", "U2", "U2'", "U'"]).unwrap()),
+            _ => None,
+        }
+    }
+}
+
+impl Turn<MinxMove> for Megaminx {
+    fn turn(&mut self, mv: MinxMove) -> bool {
+        let fifth: fn(&mut Self) = match mv.face {
+            MinxFace::U => Self::u,
+            MinxFace::R => Self::r,
+            _ => return false,
+        };
+        for _ in 0..mv.power {
+            fifth(self);
+        }
+        true
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use bfs_common::notation::turn_alg;
+
     use crate::transposition_tables::TranspositionTables;
 
     use super::*;
@@ -185,7 +284,7 @@ mod tests {
         R2' U R2 U' R2' U2 R' U R' U2' R U2' R2 U2 R2 U2' R U2' R' U2' R2' U R2 U2 R' U2'";
 
         let mut minx = Megaminx::new();
-        minx.do_alg(scramble);
+        turn_alg(&mut minx, scramble).unwrap();
 
         assert_eq!(
             minx,
@@ -198,7 +297,7 @@ mod tests {
 
         let solution = "U' R' U R2 U' R U' R2' U' R' U2' R2' U' R' U2' R' U R2 U' R' U'";
 
-        minx.do_alg(solution);
+        turn_alg(&mut minx, solution).unwrap();
 
         assert_eq!(minx.is_solved(), true);
     }
@@ -212,7 +311,7 @@ mod tests {
         let mut minx = CoordMinx::new(&transposition_tables);
         dbg!(&minx);
         dbg!(Megaminx::from(&minx));
-        minx.do_alg(scramble);
+        turn_alg(&mut minx, scramble).unwrap();
 
         assert_eq!(
             Megaminx::from(&minx),
@@ -225,7 +324,7 @@ mod tests {
 
         let solution = "U' R' U R2 U' R U' R2' U' R' U2' R2' U' R' U2' R' U R2 U' R' U'";
 
-        minx.do_alg(solution);
+        turn_alg(&mut minx, solution).unwrap();
         dbg!(&minx);
 
         assert_eq!(minx.is_solved(), true);
@@ -0,0 +1,121 @@
+//! The symmetries of the megaminx <U, R> state space: the reflection that swaps U and R,
+//! inversion, and the two combined.
+
+use bfs_common::symmetry::SmallestImage;
+
+use crate::minx::Megaminx;
+
+const SYMMETRIES: &[fn(&Megaminx) -> Megaminx] = &[Megaminx::mirror];
+
+const ANTISYMMETRIES: &[fn(&Megaminx) -> Megaminx] = &[Megaminx::inverse, mirror_inverse];
+
+fn mirror_inverse(minx: &Megaminx) -> Megaminx {
+    minx.mirror().inverse()
+}
+
+/// The classes of states under the 4 symmetries.
+pub fn reduction() -> SmallestImage<Megaminx> {
+    SmallestImage::new(SYMMETRIES, ANTISYMMETRIES)
+}
+
+#[cfg(test)]
+mod tests {
+    use bfs_common::{
+        expander::layers,
+        puzzle::Puzzle,
+        symmetry::{ReducedExpander, Reduction},
+        testing::{scrambled, scrambles},
+    };
+
+    use super::*;
+
+    bfs_common::expander! {
+        struct Expander {
+            minx: Megaminx,
+        }
+        const EXPANSION_NODES;
+        metric = Htm;
+        generators = [u: 5, r: 5];
+    }
+
+    #[test]
+    fn test_mirror() {
+        // U becomes R' and R becomes U'
+        let mirrored_moves = [[1; 4], [0; 4]];
+        for moves in scrambles::<Megaminx>() {
+            let minx = scrambled::<Megaminx>(&moves);
+            let mirror = minx.mirror();
+            assert_eq!(mirror.mirror(), minx);
+            assert_eq!(Megaminx::from_coord(mirror.encode()), mirror);
+
+            let mirrored = moves
+                .iter()
+                .flat_map(|&mv| mirrored_moves[mv])
+                .collect::<Vec<_>>();
+            assert_eq!(scrambled::<Megaminx>(&mirrored), mirror);
+        }
+    }
+
+    #[test]
+    fn test_inverse() {
+        for moves in scrambles::<Megaminx>() {
+            let minx = scrambled::<Megaminx>(&moves);
+            let inverse = minx.inverse();
+            assert_eq!(inverse.inverse(), minx);
+            assert_eq!(Megaminx::from_coord(inverse.encode()), inverse);
+
+            let inverted = moves
+                .iter()
+                .rev()
+                .flat_map(|&mv| vec![mv; Megaminx::ORDERS[mv] - 1])
+                .collect::<Vec<_>>();
+            assert_eq!(scrambled::<Megaminx>(&inverted), inverse);
+        }
+    }
+
+    #[test]
+    fn test_reduced_search() {
+        let solved = Megaminx::new().encode();
+        let mut reduction = reduction();
+
+        let full = layers(
+            &mut Expander {
+                minx: Megaminx::new(),
+            },
+            &[solved],
+            6,
+        );
+        let reduced = layers::<_, { 2 * EXPANSION_NODES }>(
+            &mut ReducedExpander::<_, _, EXPANSION_NODES> {
+                expander: Expander {
+                    minx: Megaminx::new(),
+                },
+                reduction: reduction.clone(),
+            },
+            &[reduction.class(solved)],
+            6,
+        );
+
+        assert_eq!(full.len(), reduced.len());
+        for (full, reduced) in full.iter().zip(&reduced) {
+            let unfolded = reduced.iter().map(|&c| reduction.size(c)).sum::<u64>();
+            assert_eq!(unfolded, full.len() as u64);
+            assert!(reduced.len() < full.len() || full.len() == 1);
+        }
+    }
+
+    #[test]
+    fn test_goals() {
+        // The inverse of a U turn is a U turn, but the mirror makes it an R turn, so AUF can't be
+        // searched by class
+        let auf = Megaminx::goal("auf").unwrap();
+        assert_eq!(auf.states.len(), 5);
+        for &state in &auf.states {
+            assert!(auf.contains(Megaminx::from_coord(state).inverse().encode()));
+        }
+        assert!(!auf
+            .states
+            .iter()
+            .all(|&state| auf.contains(Megaminx::from_coord(state).mirror().encode())));
+    }
+}
@@ -1,89 +1,126 @@
+use bfs_common::{
+    puzzle::Puzzle as _,
+    tables::{par_build, retain, Table, TableMode, TableSet},
+};
+
 use crate::minx::{Megaminx, CORNERS_SIZE, EP_SIZE};
 
 #[derive(Debug, PartialEq)]
 pub struct TranspositionTables {
-    pub u_corners: Vec<u32>,
-    pub u_edges: Vec<u32>,
-    pub u2p_corners: Vec<u32>,
-    pub u2p_edges: Vec<u32>,
-    pub r_corners: Vec<u32>,
-    pub r_edges: Vec<u32>,
-    pub r2p_corners: Vec<u32>,
-    pub r2p_edges: Vec<u32>,
-    pub ur_corners: Vec<u32>,
-    pub ur_edges: Vec<u32>,
+    pub u_corners: Table,
+    pub u_edges: Table,
+    pub u2p_corners: Option<Table>,
+    pub u2p_edges: Option<Table>,
+    pub r_corners: Table,
+    pub r_edges: Table,
+    pub r2p_corners: Option<Table>,
+    pub r2p_edges: Option<Table>,
+    pub ur_corners: Option<Table>,
+    pub ur_edges: Option<Table>,
 }
 
 impl TranspositionTables {
+    /// Builds the tables in memory, without saving them.
     pub fn new() -> Self {
-        let mut u_corners = vec![0; CORNERS_SIZE];
-        let mut u_edges = vec![0; EP_SIZE];
-        let mut u2p_corners = vec![0; CORNERS_SIZE];
-        let mut u2p_edges = vec![0; EP_SIZE];
-        let mut r_corners = vec![0; CORNERS_SIZE];
-        let mut r_edges = vec![0; EP_SIZE];
-        let mut r2p_corners = vec![0; CORNERS_SIZE];
-        let mut r2p_edges = vec![0; EP_SIZE];
-        let mut ur_corners = vec![0; CORNERS_SIZE];
-        let mut ur_edges = vec![0; EP_SIZE];
+        Self::load(None, TableMode::Full)
+    }
+}
 
-        let mut minx = Megaminx::new();
+impl TableSet for TranspositionTables {
+    const ID: &'static str = Megaminx::ID;
+    const LAYOUT: &'static [(&'static str, usize)] = &[
+        ("u_corners", CORNERS_SIZE),
+        ("u_edges", EP_SIZE),
+        ("u2p_corners", CORNERS_SIZE),
+        ("u2p_edges", EP_SIZE),
+        ("r_corners", CORNERS_SIZE),
+        ("r_edges", EP_SIZE),
+        ("r2p_corners", CORNERS_SIZE),
+        ("r2p_edges", EP_SIZE),
+        ("ur_corners", CORNERS_SIZE),
+        ("ur_edges", EP_SIZE),
+    ];
+    const DERIVED: &'static [&'static str] = &[
+        "u2p_corners",
+        "u2p_edges",
+        "r2p_corners",
+        "r2p_edges",
+        "ur_corners",
+        "ur_edges",
+    ];
 
-        for i in 0..CORNERS_SIZE {
+    fn build(mode: TableMode) -> Vec<Vec<u32>> {
+        let corners = par_build(CORNERS_SIZE, Megaminx::new, |minx, i| {
             minx.set_corners_coord(i as u32);
             minx.u();
-            u_corners[i] = minx.corners_coord();
+            let u = minx.corners_coord();
             minx.r();
-            ur_corners[i] = minx.corners_coord();
+            let ur = minx.corners_coord();
             minx.r();
             minx.r();
             minx.r();
             minx.r();
             minx.u();
             minx.u();
-            u2p_corners[i] = minx.corners_coord();
+            let u2p = minx.corners_coord();
             minx.u();
             minx.u();
             minx.r();
-            r_corners[i] = minx.corners_coord();
+            let r = minx.corners_coord();
             minx.r();
             minx.r();
-            r2p_corners[i] = minx.corners_coord();
-        }
+            let r2p = minx.corners_coord();
+            [u, u2p, r, r2p, ur]
+        });
 
-        for i in 0..EP_SIZE {
+        let edges = par_build(EP_SIZE, Megaminx::new, |minx, i| {
             minx.set_ep_coord(i as u32);
             minx.u();
-            u_edges[i] = minx.ep_coord();
+            let u = minx.ep_coord();
             minx.r();
-            ur_edges[i] = minx.ep_coord();
+            let ur = minx.ep_coord();
             minx.r();
             minx.r();
             minx.r();
             minx.r();
             minx.u();
             minx.u();
-            u2p_edges[i] = minx.ep_coord();
+            let u2p = minx.ep_coord();
             minx.u();
             minx.u();
             minx.r();
-            r_edges[i] = minx.ep_coord();
+            let r = minx.ep_coord();
             minx.r();
             minx.r();
-            r2p_edges[i] = minx.ep_coord();
-        }
+            let r2p = minx.ep_coord();
+            [u, u2p, r, r2p, ur]
+        });
+
+        // Both are in move order, so interleaving them gives the layout order
+        let tables = corners
+            .into_iter()
+            .zip(edges)
+            .flat_map(|(corners, edges)| [corners, edges])
+            .collect();
+
+        retain::<Self>(mode, tables)
+    }
+
+    fn from_tables(tables: Vec<Option<Table>>) -> Self {
+        let mut tables = tables.into_iter();
+        let mut next = || tables.next().unwrap();
 
         Self {
-            u_corners,
-            u_edges,
-            u2p_corners,
-            u2p_edges,
-            r_corners,
-            r_edges,
-            r2p_corners,
-            r2p_edges,
-            ur_corners,
-            ur_edges,
+            u_corners: next().unwrap(),
+            u_edges: next().unwrap(),
+            u2p_corners: next(),
+            u2p_edges: next(),
+            r_corners: next().unwrap(),
+            r_edges: next().unwrap(),
+            r2p_corners: next(),
+            r2p_edges: next(),
+            ur_corners: next(),
+            ur_edges: next(),
         }
     }
 }
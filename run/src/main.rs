@@ -1 +1,474 @@
-fn main() {}
+use std::{path::PathBuf, process::ExitCode};
+
+use bfs_common::{
+    config::ConfigFile,
+    dump::{Dump, DumpDepths, DumpFormat},
+    enumerate::MoveFilter,
+    goal::Goal,
+    limits::Limits,
+    plan::Footprint,
+    pruning::PruningFormat,
+    runner::{self, Task},
+};
+use clap::{Parser, Subcommand, ValueEnum};
+
+#[derive(Parser)]
+#[command(about = "Run a disk-based BFS over one of the puzzle state spaces")]
+struct Cli {
+    /// TOML file with the run settings for each puzzle
+    #[arg(long, global = true, default_value = "run-config.toml")]
+    config: PathBuf,
+
+    /// Print an optimal solution of this scramble instead of searching, using the depth table
+    /// written by an earlier search
+    #[arg(long, global = true, value_name = "SCRAMBLE")]
+    solve: Option<String>,
+
+    /// Export a pruning table from the depth table written by an earlier search
+    #[arg(long, global = true, conflicts_with_all = ["solve", "ida"])]
+    export_pruning: bool,
+
+    /// Print an optimal solution of this scramble found with IDA*, using an exported pruning
+    /// table
+    #[arg(long, global = true, value_name = "SCRAMBLE", conflicts_with = "solve")]
+    ida: Option<String>,
+
+    /// Format of the pruning table for `--export-pruning` and `--ida`: `mod3`, or `capped:<cap>`
+    /// for depths capped at a value up to 14
+    #[arg(long, global = true, value_name = "FORMAT", default_value = "mod3")]
+    pruning: PruningFormat,
+
+    /// Print every solution of this scramble that is at most `--extra` moves longer than an
+    /// optimal one, using the depth table written by an earlier search
+    #[arg(
+        long,
+        global = true,
+        value_name = "SCRAMBLE",
+        conflicts_with_all = ["solve", "ida", "export_pruning"]
+    )]
+    enumerate: Option<String>,
+
+    /// How many moves longer than optimal the solutions printed by `--enumerate` may be
+    #[arg(long, global = true, value_name = "MOVES", default_value_t = 0)]
+    extra: usize,
+
+    /// Only print the solutions of `--enumerate` that pass this filter: `start=<moves>` or
+    /// `end=<moves>` to start or end with one of a comma-separated list of moves, or `start!=` and
+    /// `end!=` to not. Can be given more than once
+    #[arg(long, global = true, value_name = "FILTER")]
+    filter: Vec<MoveFilter>,
+
+    /// Count the optimal solutions of every state, using the depth table written by an earlier
+    /// search, and report how many states have a unique optimal solution. The counts take 8 bytes
+    /// per state next to the depth table, e.g. 3.57 GiB for 3x3-ep but 2 TiB for 4x4-U-2R, and the
+    /// task refuses to start if there isn't that much free space
+    #[arg(
+        long,
+        global = true,
+        conflicts_with_all = ["solve", "ida", "export_pruning", "enumerate"]
+    )]
+    count_solutions: bool,
+
+    /// Estimate the distance distribution from this many random states instead of searching,
+    /// by solving each of them with a search from both ends
+    #[arg(
+        long,
+        global = true,
+        value_name = "SAMPLES",
+        conflicts_with_all = ["solve", "ida", "export_pruning", "enumerate", "count_solutions"]
+    )]
+    estimate: Option<u64>,
+
+    /// The most states to search from each sample of `--estimate` before counting it as deeper
+    /// than the search got
+    #[arg(
+        long,
+        global = true,
+        value_name = "STATES",
+        default_value_t = 10_000_000
+    )]
+    max_states: usize,
+
+    /// Seed to draw the samples of `--estimate` with, to repeat an earlier estimate
+    #[arg(long, global = true)]
+    seed: Option<u64>,
+
+    /// Stop the search once this depth is finished
+    #[arg(
+        long,
+        global = true,
+        value_name = "DEPTH",
+        conflicts_with_all = [
+            "solve",
+            "ida",
+            "export_pruning",
+            "estimate",
+            "enumerate",
+            "count_solutions",
+        ]
+    )]
+    max_depth: Option<usize>,
+
+    /// Stop the search at the end of the depth in which this many states have been visited, or
+    /// classes when reducing by symmetry
+    #[arg(
+        long,
+        global = true,
+        value_name = "STATES",
+        conflicts_with_all = [
+            "solve",
+            "ida",
+            "export_pruning",
+            "estimate",
+            "enumerate",
+            "count_solutions",
+        ]
+    )]
+    max_visited: Option<u64>,
+
+    /// Write every state the search finds at this depth, e.g. `12`, or range of depths, e.g.
+    /// `10-12`, to a file per depth in the report directory, with the decoded pieces of each
+    #[arg(
+        long,
+        global = true,
+        value_name = "DEPTHS",
+        conflicts_with_all = [
+            "solve",
+            "ida",
+            "export_pruning",
+            "estimate",
+            "enumerate",
+            "count_solutions",
+        ]
+    )]
+    dump: Option<DumpDepths>,
+
+    /// Format of the files written by `--dump`: `csv` or `jsonl`
+    #[arg(long, global = true, value_name = "FORMAT", default_value = "csv")]
+    dump_format: DumpFormat,
+
+    /// Start from a goal set of the puzzle instead of the solved state, e.g. `auf` for any turn of
+    /// the U layer. Runs with a goal set have a config section and output files of their own
+    #[arg(long, global = true, value_name = "NAME")]
+    goal: Option<String>,
+
+    /// Start from the state this scramble leads to instead of the solved state. Can be given more
+    /// than once, to start from all of them. Runs with scrambles have a `scrambles` config section
+    #[arg(long, global = true, value_name = "SCRAMBLE", conflicts_with = "goal")]
+    goal_scramble: Vec<String>,
+
+    #[command(subcommand)]
+    puzzle: Puzzle,
+}
+
+#[derive(Subcommand)]
+enum Puzzle {
+    /// 3x3 two-colour tennis ball pattern
+    #[command(name = "3x3-2-color-ufb")]
+    TwoColorUfb,
+
+    /// 3x3 two-colour corners pattern
+    #[command(name = "3x3-2-color-ufr")]
+    TwoColorUfr,
+
+    /// 3x3 edge permutation, all six faces
+    #[command(name = "3x3-ep")]
+    Ep {
+        #[arg(long, value_enum)]
+        metric: MetricWithUtm,
+
+        /// Search the classes of edge permutations under the symmetries of the cube instead of
+        /// every edge permutation
+        #[arg(long)]
+        symmetry: bool,
+    },
+
+    /// 3x3 <U, R, F2>
+    #[command(name = "3x3-U-F2-R")]
+    UF2R {
+        /// Search the classes of states under the mirror that swaps U and R and inversion,
+        /// instead of every state
+        #[arg(long)]
+        symmetry: bool,
+    },
+
+    /// 3x3 <U, r> or <U, R, r>
+    #[command(name = "3x3-U-r")]
+    Ur {
+        #[arg(long, value_enum)]
+        metric: Metric,
+
+        #[arg(long, value_enum)]
+        generators: Generators,
+
+        /// Search the classes of states under the mirror that swaps F and B and inversion,
+        /// instead of every state
+        #[arg(long)]
+        symmetry: bool,
+    },
+
+    /// 4x4 <U, 2R>
+    #[command(name = "4x4-U-2R")]
+    FourByFourU2R {
+        #[arg(long, value_enum)]
+        metric: MetricWithUtm,
+
+        /// Search the classes of states under the mirror that swaps F and B, instead of every
+        /// state. Not available for UTM
+        #[arg(long)]
+        symmetry: bool,
+    },
+
+    /// Kilominx corner permutation, six faces
+    #[command(name = "kilominx-6gen-permutation-only")]
+    Kilominx {
+        #[arg(long, value_enum)]
+        metric: Metric,
+    },
+
+    /// Megaminx <U, R>
+    #[command(name = "megaminx-U-R")]
+    MegaminxUR {
+        #[arg(long, value_enum)]
+        metric: Metric,
+
+        /// Search the classes of states under the mirror that swaps U and R and inversion,
+        /// instead of every state
+        #[arg(long)]
+        symmetry: bool,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Metric {
+    Htm,
+    Qtm,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum MetricWithUtm {
+    Htm,
+    Qtm,
+    Utm,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Generators {
+    /// <U, r>
+    Ur,
+    /// <U, R, r>
+    UrRw,
+}
+
+impl Puzzle {
+    /// The `ID` of the puzzle definition used for this run, and the size of the state space that is
+    /// searched, which is its `STATE_SIZE` unless the search is reduced by symmetry.
+    fn definition(&self) -> (&'static str, u64) {
+        fn definition<P: bfs_common::puzzle::Puzzle>() -> (&'static str, u64) {
+            (P::ID, P::STATE_SIZE)
+        }
+
+        match self {
+            Self::TwoColorUfb => definition::<bfs_3x3_2_color_ufb::Cube>(),
+            Self::TwoColorUfr => definition::<bfs_3x3_2_color_ufr::Cube>(),
+            Self::Ep {
+                metric,
+                symmetry: true,
+            } => (
+                <bfs_3x3_ep::Cube as bfs_common::puzzle::Puzzle>::ID,
+                bfs_3x3_ep::symmetry::class_count((*metric).into()),
+            ),
+            Self::Ep { .. } => definition::<bfs_3x3_ep::Cube>(),
+            Self::UF2R { .. } => definition::<bfs_3x3_U_F2_R::Cube>(),
+            Self::Ur { .. } => definition::<bfs_3x3_U_r::Cube>(),
+            Self::FourByFourU2R { .. } => definition::<bfs_4x4_U_2R::Cube>(),
+            Self::Kilominx { .. } => {
+                definition::<bfs_kilominx_6gen_permutation_only::minx::Kilominx>()
+            }
+            Self::MegaminxUR { .. } => definition::<bfs_megaminx_U_R::Megaminx>(),
+        }
+    }
+
+    /// Whether the search is reduced by symmetry.
+    fn symmetry(&self) -> bool {
+        match self {
+            Self::TwoColorUfb | Self::TwoColorUfr | Self::Kilominx { .. } => false,
+            Self::Ep { symmetry, .. }
+            | Self::UF2R { symmetry }
+            | Self::Ur { symmetry, .. }
+            | Self::FourByFourU2R { symmetry, .. }
+            | Self::MegaminxUR { symmetry, .. } => *symmetry,
+        }
+    }
+
+    /// The path of tables in the config file that apply to this run, from least to most specific.
+    /// Runs reduced by symmetry have a `sym` table right below the puzzle's, and runs from a goal
+    /// set other than the solved state have a table named after it at the bottom.
+    fn config_section(&self, goal: &Goal) -> Vec<String> {
+        let variants = match self {
+            Self::TwoColorUfb | Self::TwoColorUfr | Self::UF2R { .. } => vec![],
+            Self::Ep { metric, .. } | Self::FourByFourU2R { metric, .. } => {
+                vec![value_name(*metric)]
+            }
+            Self::Ur {
+                metric, generators, ..
+            } => vec![value_name(*generators), value_name(*metric)],
+            Self::Kilominx { metric } | Self::MegaminxUR { metric, .. } => {
+                vec![value_name(*metric)]
+            }
+        };
+
+        std::iter::once(self.definition().0.to_owned())
+            .chain(self.symmetry().then(|| "sym".to_owned()))
+            .chain(variants)
+            .chain(goal.name().map(str::to_owned))
+            .collect()
+    }
+}
+
+fn value_name<T: ValueEnum>(value: T) -> String {
+    value.to_possible_value().unwrap().get_name().to_owned()
+}
+
+impl From<MetricWithUtm> for bfs_3x3_ep::Metric {
+    fn from(value: MetricWithUtm) -> Self {
+        match value {
+            MetricWithUtm::Htm => Self::Htm,
+            MetricWithUtm::Qtm => Self::Qtm,
+            MetricWithUtm::Utm => Self::Utm,
+        }
+    }
+}
+
+impl From<Metric> for bfs_3x3_U_r::Metric {
+    fn from(value: Metric) -> Self {
+        match value {
+            Metric::Htm => Self::Htm,
+            Metric::Qtm => Self::Qtm,
+        }
+    }
+}
+
+impl From<Generators> for bfs_3x3_U_r::Generators {
+    fn from(value: Generators) -> Self {
+        match value {
+            Generators::Ur => Self::UR,
+            Generators::UrRw => Self::URRw,
+        }
+    }
+}
+
+impl From<MetricWithUtm> for bfs_4x4_U_2R::Metric {
+    fn from(value: MetricWithUtm) -> Self {
+        match value {
+            MetricWithUtm::Htm => Self::Htm,
+            MetricWithUtm::Qtm => Self::Qtm,
+            MetricWithUtm::Utm => Self::Utm,
+        }
+    }
+}
+
+impl From<Metric> for bfs_kilominx_6gen_permutation_only::Metric {
+    fn from(value: Metric) -> Self {
+        match value {
+            Metric::Htm => Self::Htm,
+            Metric::Qtm => Self::Qtm,
+        }
+    }
+}
+
+impl From<Metric> for bfs_megaminx_U_R::Metric {
+    fn from(value: Metric) -> Self {
+        match value {
+            Metric::Htm => Self::Htm,
+            Metric::Qtm => Self::Qtm,
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let goal = match cli.goal {
+        Some(name) if name != "solved" => Goal::Named(name),
+        _ if !cli.goal_scramble.is_empty() => Goal::Scrambles(cli.goal_scramble),
+        _ => Goal::Solved,
+    };
+
+    let section = cli.puzzle.config_section(&goal);
+    let section = section.iter().map(String::as_str).collect::<Vec<_>>();
+    let (_, state_size) = cli.puzzle.definition();
+    let mut config =
+        match ConfigFile::load(&cli.config).and_then(|file| file.resolve(&section, state_size)) {
+            Ok(config) => config,
+            Err(err) => {
+                eprintln!("error: {err}");
+                return ExitCode::FAILURE;
+            }
+        };
+    config.goal = goal;
+
+    let task = match (cli.solve, cli.ida, cli.estimate, cli.enumerate) {
+        (Some(scramble), _, _, _) => Task::Solve(scramble),
+        (_, Some(scramble), _, _) => Task::Ida {
+            scramble,
+            pruning: cli.pruning,
+        },
+        (_, _, Some(samples), _) => Task::Estimate {
+            samples,
+            max_states: cli.max_states,
+            seed: cli.seed,
+        },
+        (_, _, _, Some(scramble)) => Task::Enumerate {
+            scramble,
+            extra: cli.extra,
+            filters: cli.filter,
+        },
+        _ if cli.export_pruning => Task::ExportPruning(cli.pruning),
+        _ if cli.count_solutions => Task::CountSolutions,
+        _ => {
+            println!("{}", Footprint::new(&config, state_size));
+            Task::Search {
+                limits: Limits {
+                    max_depth: cli.max_depth,
+                    max_states: cli.max_visited,
+                },
+                dump: cli.dump.map(|depths| Dump {
+                    depths,
+                    format: cli.dump_format,
+                }),
+            }
+        }
+    };
+
+    runner::init_tracing();
+
+    let result = match cli.puzzle {
+        Puzzle::TwoColorUfb => bfs_3x3_2_color_ufb::run(&config, &task),
+        Puzzle::TwoColorUfr => bfs_3x3_2_color_ufr::run(&config, &task),
+        Puzzle::Ep { metric, symmetry } => bfs_3x3_ep::run(metric.into(), symmetry, &config, &task),
+        Puzzle::UF2R { symmetry } => bfs_3x3_U_F2_R::run(symmetry, &config, &task),
+        Puzzle::Ur {
+            metric,
+            generators,
+            symmetry,
+        } => bfs_3x3_U_r::run(metric.into(), generators.into(), symmetry, &config, &task),
+        Puzzle::FourByFourU2R { metric, symmetry } => {
+            bfs_4x4_U_2R::run(metric.into(), symmetry, &config, &task)
+        }
+        Puzzle::Kilominx { metric } => {
+            bfs_kilominx_6gen_permutation_only::run(metric.into(), &config, &task)
+        }
+        Puzzle::MegaminxUR { metric, symmetry } => {
+            bfs_megaminx_U_R::run(metric.into(), symmetry, &config, &task)
+        }
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}